@@ -0,0 +1,368 @@
+//! Compresses ALB responses according to the encodings accepted by the client.
+//!
+//! Responses are compressed with gzip or, when the `brotli` feature is enabled,
+//! with Brotli in case the client prefers it. The preference is inferred from
+//! the `Accept-Encoding` request header, respecting its quality values.
+//!
+//! Request bodies sent with a `Content-Encoding` (gzip, deflate or, with the `brotli`
+//! feature, Brotli) are transparently decompressed before being deserialized.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+//! use mu_alb::*;
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   listen_events(|req: AlbTargetGroupRequest| say_hello(req)).await
+//! }
+//!
+//! async fn say_hello(req: AlbTargetGroupRequest) -> AlbTargetGroupResponse {
+//!   let response = response::create_as_plain_text(200, Some("Hello, mate".to_string()));
+//!   compression::compress(&req, response)
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use aws_lambda_events::encodings::Body;
+use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+use http::HeaderValue;
+
+use mu_runtime::Error;
+
+use crate::response::{headers, ResponseExt};
+
+/// The encodings supported by this module.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ContentEncoding {
+    Gzip,
+    #[cfg(feature = "brotli")]
+    Brotli,
+}
+
+impl ContentEncoding {
+
+    /// The token used to identify this encoding in HTTP headers.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => "br",
+        }
+    }
+
+    /// Supported encodings, sorted by server preference.
+    fn supported() -> &'static [ContentEncoding] {
+        &[
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli,
+            ContentEncoding::Gzip,
+        ]
+    }
+
+    fn encode(&self, bytes: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            ContentEncoding::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                encoder.finish()
+            },
+            #[cfg(feature = "brotli")]
+            ContentEncoding::Brotli => {
+                let mut encoded = Vec::new();
+                {
+                    let mut encoder = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+                    encoder.write_all(bytes)?;
+                }
+                Ok(encoded)
+            },
+        }
+    }
+}
+
+/// Compresses the response body with the encoding preferred by the client,
+/// if any. Responses that are empty or already encoded are kept untouched.
+pub fn compress(req: &AlbTargetGroupRequest, response: AlbTargetGroupResponse) -> AlbTargetGroupResponse {
+    let accept_encoding = headers::from_request(req).get_all(ACCEPT_ENCODING).iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<&str>>()
+        .join(",");
+
+    match negotiate(&accept_encoding) {
+        Some(encoding) => compress_with(encoding, response),
+        None => response,
+    }
+}
+
+/// Compresses the response body with the given encoding. Base64 encoded bodies are
+/// decoded beforehand, thus clients get back the original bytes once decompressed.
+/// Responses that are empty or already encoded are kept untouched.
+pub fn compress_with(encoding: ContentEncoding, mut response: AlbTargetGroupResponse) -> AlbTargetGroupResponse {
+    if headers::from_response(&response).contains_key(CONTENT_ENCODING) {
+        return response
+    }
+
+    let encoded = match response.body_bytes() {
+        Some(bytes) if !bytes.is_empty() => encoding.encode(&bytes),
+        _ => return response,
+    };
+
+    match encoded {
+        Err(cause) => {
+            log::warn!("Unable to compress response with {}: {}", encoding.as_str(), cause);
+            response
+        },
+        Ok(encoded) => {
+            response.body = Some(Body::Binary(encoded));
+            response.is_base64_encoded = true;
+            let response_headers = headers::from_response_mut(&mut response);
+            response_headers.insert(CONTENT_ENCODING, HeaderValue::from_static(encoding.as_str()));
+            response_headers.append(VARY, HeaderValue::from_static("Accept-Encoding"));
+            response
+        }
+    }
+}
+
+/// The reasons a request body can't be decompressed.
+#[derive(Debug, Eq, PartialEq)]
+pub enum DecompressError {
+    /// The body is malformed, thus it can't be decoded.
+    Invalid(Error),
+    /// The body exceeds the given limit (in bytes), either encoded or once decoded.
+    TooLarge(usize),
+    /// The body is encoded with an unsupported encoding.
+    UnsupportedEncoding(String),
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::Invalid(cause) => write!(f, "{}", cause),
+            DecompressError::TooLarge(limit) => write!(f, "Payload Too Large: limit is {} bytes", limit),
+            DecompressError::UnsupportedEncoding(encoding) =>
+                write!(f, "Unsupported Media Type: content encoding {}", encoding),
+        }
+    }
+}
+
+impl std::error::Error for DecompressError {}
+
+/// Decompresses the request body according to its `Content-Encoding` header, removing
+/// it afterwards. Bodies that are still not valid UTF-8 are handed base64 encoded.
+///
+/// When `max_body_bytes` is defined, bodies exceeding it either before or after being
+/// decoded are rejected with [DecompressError::TooLarge]. Decoders stop reading right
+/// past the limit, thus a small but highly compressed body (a "decompression bomb")
+/// can't exhaust the function memory.
+pub fn decompress_request(
+    mut req: AlbTargetGroupRequest, max_body_bytes: Option<usize>
+) -> Result<AlbTargetGroupRequest, DecompressError> {
+    let content_encoding = match headers::from_request(&req).get(CONTENT_ENCODING) {
+        None => return Ok(req),
+        Some(value) => value.to_str().unwrap_or_default().to_string(),
+    };
+
+    let body = match req.body.take() {
+        None => Vec::new(),
+        Some(body) if req.is_base64_encoded => base64::decode(&body)
+            .map_err(|cause| DecompressError::Invalid(format!("Invalid base64 body: {}", cause).into()))?,
+        Some(body) => body.into_bytes(),
+    };
+
+    let limit = max_body_bytes.unwrap_or(usize::MAX);
+    if body.len() > limit {
+        return Err(DecompressError::TooLarge(limit))
+    }
+
+    // encodings are listed in the order they were applied
+    let mut decoded = body;
+    for encoding in content_encoding.rsplit(',').map(str::trim).filter(|e| !e.is_empty()) {
+        decoded = decode(encoding, decoded, limit)?;
+    }
+
+    match String::from_utf8(decoded) {
+        Ok(text) => {
+            req.body = Some(text);
+            req.is_base64_encoded = false;
+        },
+        Err(cause) => {
+            req.body = Some(base64::encode(cause.as_bytes()));
+            req.is_base64_encoded = true;
+        },
+    }
+
+    headers::from_request_mut(&mut req).remove(CONTENT_ENCODING);
+    Ok(req)
+}
+
+/// Decodes the bytes, reading at most one byte past the `limit` to tell whether it was exceeded.
+fn decode(encoding: &str, bytes: Vec<u8>, limit: usize) -> Result<Vec<u8>, DecompressError> {
+    let max_read = (limit as u64).saturating_add(1);
+    let mut decoded = Vec::new();
+    let result = match encoding.to_ascii_lowercase().as_str() {
+        "identity" => return Ok(bytes),
+        "gzip" | "x-gzip" => flate2::read::GzDecoder::new(&bytes[..]).take(max_read).read_to_end(&mut decoded),
+        "deflate" => flate2::read::ZlibDecoder::new(&bytes[..]).take(max_read).read_to_end(&mut decoded),
+        #[cfg(feature = "brotli")]
+        "br" => brotli::Decompressor::new(&bytes[..], 4096).take(max_read).read_to_end(&mut decoded),
+        _ => return Err(DecompressError::UnsupportedEncoding(encoding.to_string())),
+    };
+
+    match result {
+        Ok(_) if decoded.len() > limit => Err(DecompressError::TooLarge(limit)),
+        Ok(_) => Ok(decoded),
+        Err(cause) => Err(DecompressError::Invalid(
+            format!("Unable to decompress {} body: {}", encoding, cause).into())),
+    }
+}
+
+/// Picks the supported encoding with the highest quality value from an
+/// `Accept-Encoding` header. Ties are broken by the server preference.
+pub fn negotiate(accept_encoding: &str) -> Option<ContentEncoding> {
+    let preferences: Vec<(&str, f32)> = accept_encoding.split(',')
+        .filter_map(parse_preference)
+        .collect();
+
+    let quality_of = |encoding: &ContentEncoding| -> f32 {
+        let exact = preferences.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(encoding.as_str()));
+        let wildcard = preferences.iter()
+            .find(|(name, _)| *name == "*");
+        exact.or(wildcard).map(|(_, quality)| *quality).unwrap_or(0.0)
+    };
+
+    let mut chosen: Option<(ContentEncoding, f32)> = None;
+    for encoding in ContentEncoding::supported() {
+        let quality = quality_of(encoding);
+        let is_preferred = match chosen {
+            None => quality > 0.0,
+            Some((_, best)) => quality > best,
+        };
+        if is_preferred {
+            chosen = Some((*encoding, quality));
+        }
+    }
+
+    chosen.map(|(encoding, _)| encoding)
+}
+
+/// Parses an entry like `gzip;q=0.8` into its name and quality value.
+fn parse_preference(entry: &str) -> Option<(&str, f32)> {
+    let mut parts = entry.split(';').map(str::trim);
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let quality = parts
+        .find_map(|param| param.strip_prefix("q="))
+        .map(|q| q.parse::<f32>().unwrap_or(0.0))
+        .unwrap_or(1.0);
+    Some((name, quality))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use aws_lambda_events::encodings::Body;
+
+    use crate::compression::{compress_with, decompress_request, negotiate, ContentEncoding, DecompressError};
+    use crate::response::{self, headers};
+    use crate::testing::create_request;
+
+    #[test]
+    fn should_choose_gzip_when_it_is_the_only_accepted_encoding() {
+        assert_eq!(Some(ContentEncoding::Gzip), negotiate("gzip"));
+        assert_eq!(Some(ContentEncoding::Gzip), negotiate("deflate, gzip;q=0.5"));
+    }
+
+    #[test]
+    fn should_not_choose_rejected_or_unknown_encodings() {
+        assert_eq!(None, negotiate(""));
+        assert_eq!(None, negotiate("identity"));
+        assert_eq!(None, negotiate("gzip;q=0, *;q=0"));
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn should_choose_brotli_when_client_prefers_it() {
+        assert_eq!(Some(ContentEncoding::Brotli), negotiate("gzip;q=0.8, br"));
+        assert_eq!(Some(ContentEncoding::Brotli), negotiate("gzip, br"));
+        assert_eq!(Some(ContentEncoding::Gzip), negotiate("gzip, br;q=0.5"));
+    }
+
+    #[test]
+    fn should_compress_body_with_gzip() {
+        let alb_response = response::create_as_plain_text(200, Some("Hello, mate".to_string()));
+
+        let compressed = compress_with(ContentEncoding::Gzip, alb_response);
+        assert!(compressed.is_base64_encoded);
+        assert_eq!("gzip", headers::from_response(&compressed).get("Content-Encoding").unwrap());
+
+        let mut decoded = String::new();
+        match compressed.body {
+            Some(Body::Binary(bytes)) => flate2::read::GzDecoder::new(&bytes[..])
+                .read_to_string(&mut decoded).unwrap(),
+            other => panic!("Unexpected body: {:?}", other),
+        };
+        assert_eq!("Hello, mate", decoded);
+    }
+
+    #[test]
+    #[cfg(feature = "brotli")]
+    fn should_compress_body_with_brotli() {
+        let alb_response = response::create_as_plain_text(200, Some("Hello, mate".to_string()));
+
+        let compressed = compress_with(ContentEncoding::Brotli, alb_response);
+        assert_eq!("br", headers::from_response(&compressed).get("Content-Encoding").unwrap());
+
+        let mut decoded = String::new();
+        match compressed.body {
+            Some(Body::Binary(bytes)) => brotli::Decompressor::new(&bytes[..], 4096)
+                .read_to_string(&mut decoded).unwrap(),
+            other => panic!("Unexpected body: {:?}", other),
+        };
+        assert_eq!("Hello, mate", decoded);
+    }
+
+    #[test]
+    fn should_keep_empty_bodies_untouched() {
+        let alb_response = response::create_as_plain_text(204, None);
+
+        let compressed = compress_with(ContentEncoding::Gzip, alb_response);
+        assert!(!compressed.is_base64_encoded);
+        assert_eq!(Some(Body::Empty), compressed.body);
+    }
+
+    #[test]
+    fn should_compress_the_decoded_bytes_of_base64_bodies() {
+        let payload = vec![0u8, 159, 146, 150, 255];
+        let mut alb_response = response::create_as_plain_text(200, Some(base64::encode(&payload)));
+        alb_response.is_base64_encoded = true;
+
+        let compressed = compress_with(ContentEncoding::Gzip, alb_response);
+
+        let mut decoded = Vec::new();
+        match compressed.body {
+            Some(Body::Binary(bytes)) => flate2::read::GzDecoder::new(&bytes[..])
+                .read_to_end(&mut decoded).unwrap(),
+            other => panic!("Unexpected body: {:?}", other),
+        };
+        assert_eq!(payload, decoded);
+    }
+
+    #[test]
+    fn should_keep_empty_text_bodies_untouched() {
+        let alb_response = response::create_as_plain_text(200, Some(String::new()));
+
+        let compressed = compress_with(ContentEncoding::Gzip, alb_response);
+        assert!(!compressed.is_base64_encoded);
+        assert_eq!(Some(Body::Text(String::new())), compressed.body);
+        assert_eq!(None, headers::from_response(&compressed).get("Content-Encoding"));
+    }
+
+    #[test]
+    fn should_reject_encoded_bodies_exceeding_the_limit_before_decoding_them() {
+        let req = create_request(Some("not even gzip"), &[("Content-Encoding", "gzip")]);
+
+        assert_eq!(Err(DecompressError::TooLarge(4)), decompress_request(req, Some(4)));
+    }
+}
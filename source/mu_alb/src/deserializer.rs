@@ -2,11 +2,35 @@ use aws_lambda_events::event::alb::{
     AlbTargetGroupRequest as Request
 };
 
+use std::ops::Deref;
+
 use mu_runtime::Context;
 use mu_runtime::Error;
 
+use crate::ApiError;
+#[cfg(feature = "forms")]
+use crate::response::{content_types, headers};
+
 pub trait AlbDeserialize<T> {
+    /// The content type this type can be deserialized from. When defined, requests
+    /// with a different `Content-Type` header are rejected with `415 Unsupported Media Type`
+    /// before any deserialization is attempted.
+    const CONTENT_TYPE: Option<&'static str> = None;
+
+    /// Rejects requests whose media type can't be deserialized into this type, before
+    /// any deserialization is attempted. The runtime sends the returned error (usually
+    /// a `415 Unsupported Media Type`) back to the client. Defaults to accepting any.
+    fn check_media_type(_req: &Request) -> Result<(), ApiError> {
+        Ok(())
+    }
+
     fn from_alb_request(req: Request, ctx: Context) -> Result<T, Error>;
+
+    /// Deserializes from a borrowed request, leaving it usable afterwards (e.g. to
+    /// inspect its headers). Defaults to cloning it into [AlbDeserialize::from_alb_request].
+    fn from_alb_request_ref(req: &Request, ctx: &Context) -> Result<T, Error> {
+        Self::from_alb_request(req.clone(), ctx.clone())
+    }
 }
 
 impl AlbDeserialize<Request> for Request {
@@ -18,19 +42,412 @@ impl AlbDeserialize<Request> for Request {
     }
 }
 
-pub trait RpcRequest {}
+/// The raw body of a request, for handlers dealing with binary payloads (e.g. images)
+/// rather than JSON. Base64 encoded bodies are decoded beforehand, while missing
+/// bodies are handed as empty ones.
+///
+/// ```no_run
+/// use mu_alb::*;
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   listen_events(|image: Binary| measure(image)).await
+/// }
+///
+/// async fn measure(image: Binary) -> Result<usize, Error> {
+///   Ok(image.len())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Binary(pub Vec<u8>);
+
+impl Binary {
+    /// Unwraps the body bytes.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Deref for Binary {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl AlbDeserialize<Binary> for Binary {
+    fn from_alb_request(req: Request, _ctx: Context) -> Result<Binary, Error> {
+        match req.body {
+            Some(body) if !req.is_base64_encoded => Ok(Binary(body.into_bytes())),
+            body => decode_binary(body.as_deref()),
+        }
+    }
+
+    fn from_alb_request_ref(req: &Request, _ctx: &Context) -> Result<Binary, Error> {
+        match &req.body {
+            Some(body) if !req.is_base64_encoded => Ok(Binary(body.as_bytes().to_vec())),
+            body => decode_binary(body.as_deref()),
+        }
+    }
+}
+
+fn decode_binary(base64_body: Option<&str>) -> Result<Binary, Error> {
+    match base64_body {
+        None => Ok(Binary::default()),
+        Some(body) => base64::decode(body)
+            .map(Binary)
+            .map_err(|cause| format!("Invalid base64 body: {}", cause).into()),
+    }
+}
+
+const EMPTY_OBJECT: &str = "{}";
+const NULL: &str = "null";
+
+pub trait RpcRequest {
+    /// The content type expected for this request. See [AlbDeserialize::CONTENT_TYPE].
+    const CONTENT_TYPE: Option<&'static str> = None;
+}
 
 impl<T> AlbDeserialize<T> for T
 where
     T: for<'de> serde::Deserialize<'de> + RpcRequest,
 {
-    fn from_alb_request(req: Request, _ctx: Context) -> Result<T, Error> {
+    const CONTENT_TYPE: Option<&'static str> = <T as RpcRequest>::CONTENT_TYPE;
+
+    /// Only JSON and form bodies (or ones lacking a `Content-Type`) can be deserialized.
+    #[cfg(feature = "forms")]
+    fn check_media_type(req: &Request) -> Result<(), ApiError> {
+        match headers::media_type(req) {
+            Some(media_type) if !is_json(media_type) && !is_form(media_type) =>
+                Err(ApiError::new(415, format!("Unsupported Media Type: {}", media_type))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Missing or empty bodies are deserialized as an empty JSON object (`{}`), or as
+    /// `null` for types rejecting it (e.g. unit structs), allowing argument-less
+    /// requests to be sent without payload.
+    fn from_alb_request(req: Request, ctx: Context) -> Result<T, Error> {
+        Self::from_alb_request_ref(&req, &ctx)
+    }
+
+    fn from_alb_request_ref(req: &Request, _ctx: &Context) -> Result<T, Error> {
         match &req.body {
-            Some(body) => match serde_json::from_str(body) {
+            Some(body) if !body.trim().is_empty() => deserialize_body(req, body),
+            _ => from_empty_body(),
+        }
+    }
+}
+
+/// Deserializes a missing body. Failures are reported as the empty object ones,
+/// which are the meaningful ones for types expecting fields.
+fn from_empty_body<T>() -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    from_json(EMPTY_OBJECT).or_else(|cause| from_json(NULL).map_err(|_| cause))
+}
+
+#[cfg(not(feature = "forms"))]
+fn deserialize_body<T>(_req: &Request, body: &str) -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    from_json(body)
+}
+
+/// Picks the deserializer according to the request `Content-Type`, assuming
+/// JSON payloads when none was informed.
+#[cfg(feature = "forms")]
+fn deserialize_body<T>(req: &Request, body: &str) -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    match headers::media_type(req) {
+        None => from_json(body),
+        Some(media_type) if is_json(media_type) => from_json(body),
+        Some(media_type) if is_form(media_type) => {
+            match serde_urlencoded::from_str(body) {
                 Ok(deserialized) => Ok(deserialized),
                 Err(cause) => Err(format!("Failed {:?}", cause).into()),
-            },
-            None => Err("No payload defined".into()),
+            }
+        },
+        Some(media_type) => Err(format!("Unsupported Media Type: {}", media_type).into()),
+    }
+}
+
+#[cfg(feature = "forms")]
+fn is_form(media_type: &str) -> bool {
+    media_type.eq_ignore_ascii_case(content_types::FORM)
+}
+
+#[cfg(feature = "forms")]
+fn is_json(media_type: &str) -> bool {
+    media_type.eq_ignore_ascii_case(content_types::JSON)
+        || media_type.to_ascii_lowercase().ends_with("+json")
+}
+
+#[cfg(not(feature = "path_errors"))]
+fn from_json<T>(body: &str) -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    match serde_json::from_str(body) {
+        Ok(deserialized) => Ok(deserialized),
+        Err(cause) => Err(format!("Failed {:?}", cause).into()),
+    }
+}
+
+/// Deserializes JSON payloads, reporting where the failure happened.
+/// E.g. `Failed at .user.age: invalid type: string "ten", expected u8 at line 1 column 20`.
+#[cfg(feature = "path_errors")]
+fn from_json<T>(body: &str) -> Result<T, Error>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    let deserializer = &mut serde_json::Deserializer::from_str(body);
+    match serde_path_to_error::deserialize(deserializer) {
+        Ok(deserialized) => Ok(deserialized),
+        Err(cause) => Err(format!("Failed at .{}: {}", cause.path(), cause.inner()).into()),
+    }
+}
+
+#[cfg(test)]
+mod binary_bodies {
+    use mu_runtime::Context;
+
+    use crate::deserializer::{AlbDeserialize, Binary};
+    use crate::testing::create_request;
+
+    #[test]
+    fn should_decode_base64_bodies() {
+        let mut req = create_request(Some("iVBORw=="), &[("Content-Type", "application/octet-stream")]);
+        req.is_base64_encoded = true;
+
+        let binary = Binary::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Binary(vec![0x89, 0x50, 0x4e, 0x47]), binary)
+    }
+
+    #[test]
+    fn should_hand_plain_bodies_as_bytes() {
+        let req = create_request(Some("raw"), &[]);
+
+        let binary = Binary::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(b"raw".to_vec(), binary.into_inner())
+    }
+
+    #[test]
+    fn should_reject_invalid_base64_bodies() {
+        let mut req = create_request(Some("not base64!"), &[]);
+        req.is_base64_encoded = true;
+
+        assert!(Binary::from_alb_request(req, Context::default()).is_err())
+    }
+}
+
+#[cfg(test)]
+mod empty_bodies {
+    use serde::Deserialize;
+
+    use mu_runtime::Context;
+
+    use crate::deserializer::{AlbDeserialize, RpcRequest};
+    use crate::testing::create_request;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Ping {}
+
+    impl RpcRequest for Ping {}
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Refresh {
+        force: Option<bool>,
+    }
+
+    impl RpcRequest for Refresh {}
+
+    #[derive(Deserialize, Debug)]
+    struct Signup {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    impl RpcRequest for Signup {}
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Heartbeat;
+
+    impl RpcRequest for Heartbeat {}
+
+    #[test]
+    fn should_deserialize_requests_without_body_as_empty_objects() {
+        let req = create_request(None, &[]);
+
+        let ping = Ping::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Ping {}, ping)
+    }
+
+    #[test]
+    fn should_deserialize_empty_bodies_into_optional_fields() {
+        let req = create_request(Some(""), &[("Content-Type", "application/json")]);
+
+        let refresh = Refresh::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Refresh { force: None }, refresh)
+    }
+
+    #[test]
+    fn should_deserialize_requests_without_body_into_unit_structs() {
+        let req = create_request(None, &[]);
+
+        let heartbeat = Heartbeat::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Heartbeat, heartbeat)
+    }
+
+    #[test]
+    fn should_reject_missing_bodies_when_fields_are_required() {
+        let req = create_request(None, &[]);
+
+        assert!(Signup::from_alb_request(req, Context::default()).is_err())
+    }
+}
+
+#[cfg(test)]
+mod borrowed_requests {
+    use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+    use serde::Deserialize;
+
+    use mu_runtime::{Context, Error};
+
+    use crate::deserializer::{AlbDeserialize, Binary, RpcRequest};
+    use crate::testing::create_request;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Greeting {
+        name: String,
+    }
+
+    impl RpcRequest for Greeting {}
+
+    /// Only implements the owned deserialization, relying on the default borrowed one.
+    #[derive(Debug, PartialEq)]
+    struct BodyLength(usize);
+
+    impl AlbDeserialize<BodyLength> for BodyLength {
+        fn from_alb_request(req: AlbTargetGroupRequest, _: Context) -> Result<BodyLength, Error> {
+            Ok(BodyLength(req.body.map(|body| body.len()).unwrap_or(0)))
         }
     }
+
+    #[test]
+    fn should_leave_the_request_usable_afterwards() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("X-Correlation-Id", "abc-123")]);
+
+        let greeting = Greeting::from_alb_request_ref(&req, &Context::default()).unwrap();
+        assert_eq!(Greeting { name: "John".to_string() }, greeting);
+        assert_eq!("abc-123", req.headers["X-Correlation-Id"]);
+        assert_eq!(Some(r#"{"name":"John"}"#.to_string()), req.body);
+    }
+
+    #[test]
+    fn should_borrow_binary_bodies() {
+        let req = create_request(Some("raw"), &[]);
+
+        let binary = Binary::from_alb_request_ref(&req, &Context::default()).unwrap();
+        assert_eq!(b"raw".to_vec(), binary.into_inner());
+        assert_eq!(Some("raw".to_string()), req.body);
+    }
+
+    #[test]
+    fn should_clone_the_request_by_default() {
+        let req = create_request(Some("12345"), &[]);
+
+        assert_eq!(BodyLength(5), BodyLength::from_alb_request_ref(&req, &Context::default()).unwrap());
+        assert_eq!(Some("12345".to_string()), req.body);
+    }
+}
+
+#[cfg(all(test, feature = "forms"))]
+mod tests {
+    use serde::Deserialize;
+
+    use mu_runtime::{Context, Error};
+
+    use crate::ApiError;
+    use crate::deserializer::{AlbDeserialize, RpcRequest};
+    use crate::testing::create_request;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Signup {
+        name: String,
+        age: u8,
+    }
+
+    impl RpcRequest for Signup {}
+
+    #[test]
+    fn should_deserialize_json_bodies() {
+        let req = create_request(
+            Some(r#"{"name":"John","age":42}"#), &[("Content-Type", "application/json")]);
+
+        let signup = Signup::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Signup { name: "John".to_string(), age: 42 }, signup)
+    }
+
+    #[test]
+    fn should_deserialize_form_bodies() {
+        let req = create_request(
+            Some("name=John&age=42"), &[("Content-Type", "application/x-www-form-urlencoded; charset=utf-8")]);
+
+        let signup = Signup::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Signup { name: "John".to_string(), age: 42 }, signup)
+    }
+
+    #[test]
+    fn should_reject_unknown_content_types() {
+        let req = create_request(Some("name: John"), &[("Content-Type", "text/yaml")]);
+
+        assert_eq!(
+            Err(ApiError::new(415, "Unsupported Media Type: text/yaml")),
+            Signup::check_media_type(&req)
+        );
+
+        let result = Signup::from_alb_request(req, Context::default());
+        assert_eq!(Err(Error::from("Unsupported Media Type: text/yaml")), result)
+    }
+}
+
+#[cfg(all(test, feature = "path_errors"))]
+mod path_errors {
+    use serde::Deserialize;
+
+    use mu_runtime::{Context, Error};
+
+    use crate::deserializer::{AlbDeserialize, RpcRequest};
+    use crate::testing::create_request;
+
+    #[derive(Deserialize, Debug)]
+    struct Signup {
+        #[allow(dead_code)]
+        user: User,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct User {
+        #[allow(dead_code)]
+        age: u8,
+    }
+
+    impl RpcRequest for Signup {}
+
+    #[test]
+    fn should_report_the_path_of_nested_type_mismatches() {
+        let req = create_request(Some(r#"{"user":{"age":"ten"}}"#), &[]);
+
+        let result = Signup::from_alb_request(req, Context::default());
+        assert_eq!(
+            Some(Error::from(r#"Failed at .user.age: invalid type: string "ten", expected u8 at line 1 column 20"#)),
+            result.err()
+        )
+    }
 }
@@ -0,0 +1,351 @@
+//! Http-aware errors that handlers can return to convey a meaningful status code.
+
+use std::fmt::Formatter;
+use std::time::Duration;
+
+use aws_lambda_events::event::alb::AlbTargetGroupResponse;
+use serde::Serialize;
+
+use mu_runtime::Context;
+
+use crate::response::{content_types, headers, ResponseExt};
+use crate::{response, AlbSerialize};
+
+/// An error that maps into an ALB response with the given `status` code,
+/// having its `message` sent back to the client as `{ "error": message }`.
+///
+/// Handlers returning `Result<T, ApiError>` will have their failures
+/// serialized with the appropriate status code, instead of the default 500.
+/// Errors are created through the constructors below, e.g. [ApiError::not_found].
+///
+/// ```
+/// use mu_alb::{AlbSerialize, ApiError};
+///
+/// let result: Result<String, ApiError> = Err(ApiError::not_found("User not found"));
+/// assert_eq!(404, result.to_alb_response().status_code);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+    /// How long clients should back off before retrying, sent as the `Retry-After` header.
+    pub retry_after: Option<Duration>,
+}
+
+impl ApiError {
+
+    /// Creates a new error with an arbitrary status code.
+    pub fn new(status: u16, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into(), retry_after: None }
+    }
+
+    /// 400 - The received request is invalid.
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+
+    /// 404 - The requested resource doesn't exist.
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(404, message)
+    }
+
+    /// 409 - The request conflicts with the current state of the resource.
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(409, message)
+    }
+
+    /// 429 - A downstream dependency is throttling requests. Serialized as
+    /// [response::too_many_requests], telling clients to back off for `retry_after`.
+    pub fn throttled(retry_after: Duration) -> Self {
+        ApiError { retry_after: Some(retry_after), ..Self::new(429, "Too Many Requests") }
+    }
+
+    /// 500 - An unexpected failure happened while handling the request.
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(500, message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status, self.message)
+    }
+}
+
+impl AlbSerialize for ApiError {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        if let Some(retry_after) = self.retry_after {
+            return response::too_many_requests(retry_after)
+        }
+        let body = serde_json::json!({ "error": self.message });
+        response::create_json_from_obj(self.status as i64, &body)
+    }
+
+    fn to_alb_response_with(&self, ctx: &Context) -> AlbTargetGroupResponse {
+        if let Some(retry_after) = self.retry_after {
+            return response::too_many_requests(retry_after)
+        }
+        let body = serde_json::json!({ "error": self.message, "request_id": ctx.request_id });
+        response::create_json_from_obj(self.status as i64, &body)
+    }
+}
+
+/// An error sent as an [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) problem
+/// details document, giving clients a machine-readable error contract. It maps into
+/// an `application/problem+json` response with the given `status` code.
+///
+/// ```
+/// use mu_alb::{AlbSerialize, Problem};
+///
+/// let problem = Problem::not_found("User 42 doesn't exist").with_instance("/users/42");
+/// assert_eq!(404, problem.to_alb_response().status_code);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct Problem {
+    /// A URI identifying the problem type. Defaults to `about:blank`.
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    /// A short summary of the problem type. Defaults to the reason phrase of the `status`.
+    pub title: String,
+    /// The status code of the response.
+    pub status: u16,
+    /// An explanation specific to this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// A URI identifying this occurrence of the problem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+}
+
+impl Problem {
+
+    /// Creates a problem of the `about:blank` type, titled after the reason phrase
+    /// of the status code.
+    pub fn new(status: u16) -> Self {
+        let title = http::StatusCode::from_u16(status).ok()
+            .and_then(|status| status.canonical_reason())
+            .unwrap_or_default();
+
+        Problem {
+            problem_type: "about:blank".to_string(),
+            title: title.to_string(),
+            status,
+            detail: None,
+            instance: None,
+        }
+    }
+
+    /// 400 - The received request is invalid.
+    pub fn bad_request(detail: impl Into<String>) -> Self {
+        Self::new(400).with_detail(detail)
+    }
+
+    /// 401 - The request lacks valid credentials.
+    pub fn unauthorized(detail: impl Into<String>) -> Self {
+        Self::new(401).with_detail(detail)
+    }
+
+    /// 403 - The credentials don't grant access to the resource.
+    pub fn forbidden(detail: impl Into<String>) -> Self {
+        Self::new(403).with_detail(detail)
+    }
+
+    /// 404 - The requested resource doesn't exist.
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(404).with_detail(detail)
+    }
+
+    /// 409 - The request conflicts with the current state of the resource.
+    pub fn conflict(detail: impl Into<String>) -> Self {
+        Self::new(409).with_detail(detail)
+    }
+
+    /// 500 - An unexpected failure happened while handling the request.
+    pub fn internal(detail: impl Into<String>) -> Self {
+        Self::new(500).with_detail(detail)
+    }
+
+    /// Sets the URI identifying the problem type.
+    pub fn with_type(mut self, problem_type: impl Into<String>) -> Self {
+        self.problem_type = problem_type.into();
+        self
+    }
+
+    /// Replaces the title of the problem type.
+    pub fn with_title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    /// Sets the explanation specific to this occurrence of the problem.
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the URI identifying this occurrence of the problem.
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+}
+
+impl std::error::Error for Problem {}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{} {}: {}", self.status, self.title, detail),
+            None => write!(f, "{} {}", self.status, self.title),
+        }
+    }
+}
+
+impl AlbSerialize for Problem {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        let response = response::create_json_from_obj(self.status as i64, self);
+        // serialization failures are sent as they are
+        if response.status_code != self.status as i64 {
+            return response
+        }
+        response.with_header(headers::CONTENT_TYPE, content_types::PROBLEM_JSON)
+    }
+}
+
+/// How failures lacking their own representation (i.e. anything but [ApiError] or [Problem])
+/// are rendered by the [AlbSerialize] implementations of this crate.
+/// See [Options::error_format](crate::Options::error_format).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// `text/plain` bodies, like `Internal Server Error: <cause>`. The default.
+    #[default]
+    Text,
+    /// `application/json` bodies, like `{ "error": "<cause>", "type": "<error type>" }`.
+    Json,
+}
+
+/// Renders a `500 Internal Server Error` response in the given `format`. The `text`
+/// is used as plain text body, while `message` and `error_type` compose the JSON one,
+/// along with the request id of the invocation `ctx`, when available.
+pub(crate) fn render_internal_error(
+    format: ErrorFormat, text: String, message: String, error_type: &str, ctx: Option<&Context>
+) -> AlbTargetGroupResponse {
+    match format {
+        ErrorFormat::Text => response::create_as_plain_text(500, Some(text)),
+        ErrorFormat::Json => {
+            let body = match ctx {
+                Some(ctx) => serde_json::json!({ "error": message, "type": error_type, "request_id": ctx.request_id }),
+                None => serde_json::json!({ "error": message, "type": error_type }),
+            };
+            response::create_json_from_obj(500, &body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use aws_lambda_events::encodings::Body;
+
+    use crate::{response, AlbSerialize, ApiError, Problem};
+    use crate::response::{content_types, headers};
+    use crate::testing::create_context;
+
+    #[test]
+    fn should_serialize_as_json_with_matching_status() {
+        let response = ApiError::not_found("x").to_alb_response();
+
+        assert_eq!(404, response.status_code);
+        assert_eq!(Body::Text(r#"{"error":"x"}"#.to_string()), response.body.unwrap());
+
+        #[cfg(not(feature = "multi_header"))]
+        let headers = response.headers;
+        #[cfg(feature = "multi_header")]
+        let headers = response.multi_value_headers;
+        assert_eq!(content_types::JSON, headers.get("Content-Type").unwrap());
+    }
+
+    #[test]
+    fn should_include_the_request_id_when_context_is_available() {
+        let ctx = create_context("0000-0001");
+
+        let response = ApiError::internal("boom").to_alb_response_with(&ctx);
+        assert_eq!(500, response.status_code);
+        assert_eq!(
+            Body::Text(r#"{"error":"boom","request_id":"0000-0001"}"#.to_string()),
+            response.body.unwrap()
+        );
+    }
+
+    #[test]
+    fn should_map_failed_results_to_their_status() {
+        let result: Result<i32, ApiError> = Err(ApiError::bad_request("Missing name"));
+
+        let response = result.to_alb_response();
+        assert_eq!(400, response.status_code);
+        assert_eq!(Body::Text(r#"{"error":"Missing name"}"#.to_string()), response.body.unwrap());
+    }
+
+    #[test]
+    fn should_map_wrapped_failures_to_their_status() {
+        let result: Result<i32, Box<ApiError>> = Err(Box::new(ApiError::conflict("Taken")));
+
+        let response = result.to_alb_response();
+        assert_eq!(409, response.status_code);
+        assert_eq!(Body::Text(r#"{"error":"Taken"}"#.to_string()), response.body.unwrap());
+    }
+
+    #[test]
+    fn should_map_boxed_error_trait_objects_to_their_status() {
+        let result: Result<i32, Box<dyn std::error::Error>> = Err("Boom".into());
+        assert_eq!(500, result.to_alb_response().status_code);
+
+        let result: Result<i32, Box<dyn std::error::Error + Send>> = Err(Box::new(ApiError::conflict("Taken")));
+        assert_eq!(409, result.to_alb_response().status_code);
+    }
+
+    #[test]
+    fn should_ask_throttled_clients_to_retry_later() {
+        let response = ApiError::throttled(Duration::from_secs(5)).to_alb_response();
+
+        assert_eq!(429, response.status_code);
+        assert_eq!("5", headers::from_response(&response).get("Retry-After").unwrap());
+        assert_eq!(response::too_many_requests(Duration::from_secs(5)), response);
+
+        let response = ApiError::throttled(Duration::from_secs(5)).to_alb_response_with(&create_context("0000-0001"));
+        assert_eq!(response::too_many_requests(Duration::from_secs(5)), response);
+    }
+
+    #[test]
+    fn should_serialize_problems_as_problem_json() {
+        let response = Problem::bad_request("x").to_alb_response();
+
+        assert_eq!(400, response.status_code);
+        assert_eq!(
+            content_types::PROBLEM_JSON,
+            headers::from_response(&response).get("Content-Type").unwrap()
+        );
+        assert_eq!(
+            Body::Text(r#"{"type":"about:blank","title":"Bad Request","status":400,"detail":"x"}"#.to_string()),
+            response.body.unwrap()
+        );
+    }
+
+    #[test]
+    fn should_map_failed_results_to_their_problem() {
+        let result: Result<i32, Problem> = Err(Problem::new(409)
+            .with_type("https://example.com/probs/taken")
+            .with_instance("/users/42"));
+
+        let response = result.to_alb_response();
+        assert_eq!(409, response.status_code);
+        assert_eq!(
+            Body::Text(r#"{"type":"https://example.com/probs/taken","title":"Conflict","status":409,"instance":"/users/42"}"#.to_string()),
+            response.body.unwrap()
+        );
+    }
+}
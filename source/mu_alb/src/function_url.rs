@@ -0,0 +1,342 @@
+//! Exposes handlers through [Lambda Function URLs], with the same RPC and response
+//! ergonomics offered to ALB events. Requests are adapted into an
+//! [AlbTargetGroupRequest] before reaching the handler, and its response adapted
+//! back, thus every [AlbDeserialize] and [Responder] type works as usual.
+//!
+//! ```no_run
+//! use mu_alb::*;
+//! use mu_alb::function_url;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Greeting { name: String }
+//!
+//! impl RpcRequest for Greeting {}
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   function_url::listen_events(|greeting: Greeting| say_hello(greeting)).await
+//! }
+//!
+//! async fn say_hello(greeting: Greeting) -> Result<String, Error> {
+//!   Ok(format!("Hello, {}", greeting.name))
+//! }
+//! ```
+//!
+//! [Lambda Function URLs]: https://docs.aws.amazon.com/lambda/latest/dg/urls-invocation.html
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+
+use aws_lambda_events::encodings::Body;
+use aws_lambda_events::event::alb::{
+    AlbTargetGroupRequest, AlbTargetGroupRequestContext, AlbTargetGroupResponse, ElbContext,
+};
+use http::header::{HeaderName, COOKIE, SET_COOKIE};
+use http::{HeaderMap, HeaderValue, Method};
+use serde::{Deserialize, Serialize};
+
+use mu_runtime::Context;
+
+use crate::response::headers;
+use crate::runtime::{self, Options};
+use crate::{AlbDeserialize, Error, Responder};
+
+/// A request received through a Lambda Function URL (payload format version 2.0).
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionUrlRequest {
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub raw_path: Option<String>,
+    #[serde(default)]
+    pub raw_query_string: Option<String>,
+    /// The cookies sent by the client, which are left out of the `headers`.
+    #[serde(default)]
+    pub cookies: Option<Vec<String>>,
+    /// The request headers. Repeated headers are comma-separated.
+    #[serde(default)]
+    pub headers: Option<HashMap<String, String>>,
+    /// The query string parameters. Repeated parameters are comma-separated.
+    #[serde(default)]
+    pub query_string_parameters: Option<HashMap<String, String>>,
+    pub request_context: FunctionUrlRequestContext,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub is_base64_encoded: bool,
+}
+
+/// Identifies the function URL and the client calling it.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionUrlRequestContext {
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default)]
+    pub api_id: Option<String>,
+    #[serde(default)]
+    pub domain_name: Option<String>,
+    #[serde(default)]
+    pub domain_prefix: Option<String>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub time_epoch: Option<i64>,
+    pub http: FunctionUrlHttpContext,
+    /// Only present when the function URL uses the `AWS_IAM` auth type.
+    #[serde(default)]
+    pub authorizer: Option<FunctionUrlAuthorizer>,
+}
+
+/// The HTTP details of the request.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionUrlHttpContext {
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub protocol: Option<String>,
+    pub source_ip: String,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+/// The authorization details of requests to function URLs using the `AWS_IAM` auth type.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FunctionUrlAuthorizer {
+    #[serde(default)]
+    pub iam: Option<FunctionUrlIamIdentity>,
+}
+
+/// The IAM identity that signed the request.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionUrlIamIdentity {
+    #[serde(default)]
+    pub access_key: Option<String>,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default)]
+    pub caller_id: Option<String>,
+    #[serde(default)]
+    pub user_arn: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+}
+
+impl FunctionUrlRequest {
+
+    /// The IP address of the client.
+    pub fn source_ip(&self) -> &str {
+        &self.request_context.http.source_ip
+    }
+
+    /// The IAM identity that signed the request, when the function URL uses the `AWS_IAM` auth type.
+    pub fn iam_identity(&self) -> Option<&FunctionUrlIamIdentity> {
+        self.request_context.authorizer.as_ref()?.iam.as_ref()
+    }
+}
+
+/// Adapts the request, so it can be handled as if it came from an Application Load Balancer.
+/// Cookies are sent back into the `Cookie` header.
+impl From<FunctionUrlRequest> for AlbTargetGroupRequest {
+    fn from(req: FunctionUrlRequest) -> Self {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in req.headers.unwrap_or_default() {
+            if let (Ok(name), Ok(value)) = (HeaderName::from_str(&name), HeaderValue::from_str(&value)) {
+                header_map.append(name, value);
+            }
+        }
+        if let Some(cookies) = req.cookies.filter(|cookies| !cookies.is_empty()) {
+            if let Ok(value) = HeaderValue::from_str(&cookies.join("; ")) {
+                header_map.insert(COOKIE, value);
+            }
+        }
+
+        let query_string_parameters = req.query_string_parameters.unwrap_or_default();
+        let multi_value_query_string_parameters = query_string_parameters.iter()
+            .map(|(name, value)| (name.clone(), vec![value.clone()]))
+            .collect();
+
+        AlbTargetGroupRequest {
+            http_method: Method::from_str(&req.request_context.http.method).unwrap_or(Method::GET),
+            path: req.raw_path.or(Some(req.request_context.http.path)),
+            query_string_parameters,
+            multi_value_query_string_parameters,
+            headers: header_map.clone(),
+            multi_value_headers: header_map,
+            request_context: AlbTargetGroupRequestContext {
+                elb: ElbContext { target_group_arn: None },
+            },
+            is_base64_encoded: req.is_base64_encoded,
+            body: req.body,
+        }
+    }
+}
+
+/// The response sent back through a Lambda Function URL.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionUrlResponse {
+    pub status_code: i64,
+    /// The response headers. Repeated headers are comma-separated.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// The cookies set by the response, which are left out of the `headers`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cookies: Vec<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub is_base64_encoded: bool,
+}
+
+/// Adapts a response built for the Application Load Balancer. `Set-Cookie`
+/// headers are moved into the `cookies` of the response.
+impl From<AlbTargetGroupResponse> for FunctionUrlResponse {
+    fn from(alb_response: AlbTargetGroupResponse) -> Self {
+        let mut headers = HashMap::new();
+        let mut cookies = Vec::new();
+        for (name, value) in headers::from_response(&alb_response) {
+            let value = match value.to_str() {
+                Ok(value) => value.to_string(),
+                Err(_) => continue,
+            };
+
+            if name == SET_COOKIE {
+                cookies.push(value);
+            } else {
+                headers.entry(name.as_str().to_string())
+                    .and_modify(|joined: &mut String| { joined.push_str(", "); joined.push_str(&value) })
+                    .or_insert(value);
+            }
+        }
+
+        let (body, is_base64_encoded) = match alb_response.body {
+            None | Some(Body::Empty) => (None, alb_response.is_base64_encoded),
+            Some(Body::Text(text)) => (Some(text), alb_response.is_base64_encoded),
+            Some(Body::Binary(bytes)) => (Some(base64::encode(bytes)), true),
+        };
+
+        FunctionUrlResponse {
+            status_code: alb_response.status_code,
+            headers,
+            cookies,
+            body,
+            is_base64_encoded,
+        }
+    }
+}
+
+/// Listen to requests received through a Lambda Function URL, handing them to the
+/// `handler` as [crate::listen_events] does for ALB events.
+pub async fn listen_events<F, Fut, A, B>(handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    listen_events_with(Options::default(), handler).await
+}
+
+/// Listen to requests received through a Lambda Function URL, as [listen_events]
+/// does, using the given [Options].
+pub async fn listen_events_with<F, Fut, A, B>(options: Options, handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    mu_runtime::listen_events(
+        |req, ctx| handle_function_url_req(&options, &handler, req, ctx)
+    ).await
+}
+
+async fn handle_function_url_req<F, Fut, A, B>(
+    options: &Options,
+    func: &F,
+    req: FunctionUrlRequest,
+    ctx: Context,
+) -> Result<FunctionUrlResponse, Error>
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    let alb_response = runtime::handle_rpc_req(options, func, req.into(), ctx).await?;
+    Ok(alb_response.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::function_url::{handle_function_url_req, FunctionUrlRequest, FunctionUrlResponse};
+    use crate::response::{self, content_types};
+    use crate::runtime::Options;
+    use crate::testing::create_context;
+    use crate::{Error, ResponseExt, RpcRequest};
+
+    const SAMPLE_FUNCTION_URL_REQUEST: &str = include_str!("../tests/sample_function_url_request.json");
+
+    #[derive(Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    impl RpcRequest for Greeting {}
+
+    #[derive(Serialize)]
+    struct Reply {
+        message: String,
+    }
+
+    async fn greet(greeting: Greeting) -> Result<Reply, Error> {
+        Ok(Reply { message: format!("Hello, {}", greeting.name) })
+    }
+
+    #[test]
+    fn should_parse_function_url_requests() {
+        let req: FunctionUrlRequest = serde_json::from_str(SAMPLE_FUNCTION_URL_REQUEST).unwrap();
+
+        assert_eq!("203.0.113.42", req.source_ip());
+        assert_eq!(
+            Some("arn:aws:iam::111122223333:user/example-user"),
+            req.iam_identity().and_then(|identity| identity.user_arn.as_deref())
+        );
+        assert_eq!(Some("/greetings"), req.raw_path.as_deref());
+    }
+
+    #[tokio::test]
+    async fn should_answer_with_json_responses() {
+        let req: FunctionUrlRequest = serde_json::from_str(SAMPLE_FUNCTION_URL_REQUEST).unwrap();
+
+        let response = handle_function_url_req(&Options::default(), &greet, req, create_context("0000-0001"))
+            .await.unwrap();
+
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(r#"{"message":"Hello, John"}"#.to_string()), response.body);
+        assert_eq!(Some(content_types::JSON), response.headers.get("content-type").map(String::as_str));
+        assert!(!response.is_base64_encoded);
+    }
+
+    #[test]
+    fn should_move_set_cookie_headers_into_cookies() {
+        let alb_response = response::created("/greetings/1")
+            .with_header("Set-Cookie", "theme=dark");
+
+        let response: FunctionUrlResponse = alb_response.into();
+        assert_eq!(vec!["theme=dark".to_string()], response.cookies);
+        assert_eq!(Some("/greetings/1"), response.headers.get("location").map(String::as_str));
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(201, json["statusCode"]);
+        assert_eq!("theme=dark", json["cookies"][0]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Lets clients safely retry requests carrying an [IDEMPOTENCY_KEY_HEADER], answering
+//! duplicates with the response of the first request rather than re-executing the
+//! handler (and its side effects).
+//!
+//! Responses are kept in a user-provided [IdempotencyStore] (e.g. a DynamoDB table),
+//! keyed by the request method and path along with the header value, thus a key sent
+//! to another endpoint never replays its response. The fingerprint of the request body
+//! is kept as well: reusing a key with a different body is rejected with
+//! `422 Unprocessable Entity`. Callers are only told apart by their keys, which are
+//! expected to be unique (e.g. UUIDs). Requests racing with the same key aren't
+//! coordinated, thus both may reach the handler.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+//! use mu_alb::*;
+//! use mu_alb::idempotency::{self, InMemoryStore};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Order { item: String }
+//!
+//! impl RpcRequest for Order {}
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   let store = Arc::new(InMemoryStore::default());
+//!   listen_events_full(|order: Order, ctx: Context, req: AlbTargetGroupRequest| {
+//!     let store = store.clone();
+//!     async move {
+//!       idempotency::idempotent(store.as_ref(), &req, &ctx, || place_order(order)).await
+//!     }
+//!   }).await
+//! }
+//!
+//! async fn place_order(order: Order) -> Result<String, Error> {
+//!   Ok(format!("Ordered {}", order.item))
+//! }
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+
+use mu_runtime::Context;
+
+use crate::response::headers;
+use crate::{AlbSerialize, ApiError, Error, Responder};
+
+/// The header holding the key clients use to identify retries of the same request.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// A response kept by an [IdempotencyStore], along with the fingerprint of the
+/// body of the request it was sent to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredResponse {
+    /// Tells whether a retry carries the same body as the original request.
+    pub fingerprint: String,
+    /// The response sent to the original request.
+    pub response: AlbTargetGroupResponse,
+}
+
+/// Keeps the responses sent to the requests carrying an [IDEMPOTENCY_KEY_HEADER].
+/// Keys are made of the request method, its path and the header value
+/// (e.g. `POST /orders abc-123`).
+#[async_trait]
+pub trait IdempotencyStore: Sync {
+    /// The response previously sent to the request identified by `key`, if any.
+    async fn get(&self, key: &str) -> Result<Option<StoredResponse>, Error>;
+
+    /// Keeps the response sent to the request identified by `key`.
+    async fn put(&self, key: &str, stored: &StoredResponse) -> Result<(), Error>;
+}
+
+/// An [IdempotencyStore] keeping responses in memory. Handy for tests and for
+/// retries reaching the same execution environment, as nothing is shared across them.
+#[derive(Debug, Default)]
+pub struct InMemoryStore {
+    responses: Mutex<HashMap<String, StoredResponse>>,
+}
+
+#[async_trait]
+impl IdempotencyStore for InMemoryStore {
+    async fn get(&self, key: &str) -> Result<Option<StoredResponse>, Error> {
+        Ok(self.responses.lock().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, stored: &StoredResponse) -> Result<(), Error> {
+        self.responses.lock().unwrap().insert(key.to_string(), stored.clone());
+        Ok(())
+    }
+}
+
+/// Runs the `handler`, unless the request carries an [IDEMPOTENCY_KEY_HEADER] whose
+/// response is already in the `store`, in which case that response is sent instead.
+/// Requests without the header always reach the handler, while the ones reusing
+/// a key with a different body are answered with `422 Unprocessable Entity`.
+///
+/// Server errors (`5xx`) aren't kept, so clients can retry them. Failing to read from
+/// the store is answered with `500 Internal Server Error`, while failing to write
+/// to it is only logged, as the handler has already run.
+pub async fn idempotent<S, F, Fut, B>(
+    store: &S,
+    req: &AlbTargetGroupRequest,
+    ctx: &Context,
+    handler: F,
+) -> AlbTargetGroupResponse
+where
+    S: IdempotencyStore + ?Sized,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = B>,
+    B: Responder,
+{
+    let key = match idempotency_key_of(req) {
+        Some(key) => store_key_of(req, &key),
+        None => return handler().await.into_alb_response_with(ctx),
+    };
+    let fingerprint = fingerprint_of(req);
+
+    match store.get(&key).await {
+        Ok(Some(stored)) if stored.fingerprint == fingerprint => return stored.response,
+        Ok(Some(_)) => {
+            let cause = ApiError::new(422, format!("{} reused with a different request body", IDEMPOTENCY_KEY_HEADER));
+            return cause.to_alb_response_with(ctx)
+        },
+        Ok(None) => {},
+        Err(cause) => {
+            log::error!("Unable to read the response of idempotency key {}: {}", key, cause);
+            return cause.to_alb_response()
+        }
+    }
+
+    let response = handler().await.into_alb_response_with(ctx);
+    if response.status_code < 500 {
+        let stored = StoredResponse { fingerprint, response };
+        if let Err(cause) = store.put(&key, &stored).await {
+            log::warn!("Unable to keep the response of idempotency key {}: {}", key, cause);
+        }
+        return stored.response
+    }
+    response
+}
+
+/// Scopes the idempotency `key` to the method and path of the request.
+fn store_key_of(req: &AlbTargetGroupRequest, key: &str) -> String {
+    format!("{} {} {}", req.http_method, req.path.as_deref().unwrap_or("/"), key)
+}
+
+/// Identifies the request body with a non-cryptographic hash, e.g. `1b3c6f5e0a9d2c47`.
+fn fingerprint_of(req: &AlbTargetGroupRequest) -> String {
+    let mut hasher = DefaultHasher::new();
+    req.body.as_deref().unwrap_or_default().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn idempotency_key_of(req: &AlbTargetGroupRequest) -> Option<String> {
+    headers::from_request(req).get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use aws_lambda_events::encodings::Body;
+
+    use crate::idempotency::{idempotent, InMemoryStore};
+    use crate::testing::{create_context, create_request};
+    use crate::Error;
+
+    #[tokio::test]
+    async fn should_answer_duplicates_with_the_cached_response() {
+        let store = InMemoryStore::default();
+        let ctx = create_context("0000-0001");
+        let invocations = AtomicUsize::new(0);
+        let place_order = || async {
+            let order = invocations.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok::<_, Error>(format!("order {}", order))
+        };
+
+        let req = create_request(Some(r#"{"item":"book"}"#), &[("Idempotency-Key", "abc-123")]);
+        let first = idempotent(&store, &req, &ctx, place_order).await;
+        let second = idempotent(&store, &req, &ctx, place_order).await;
+
+        assert_eq!(1, invocations.load(Ordering::SeqCst));
+        assert_eq!(Some(Body::Text(r#""order 1""#.to_string())), first.body);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn should_not_replay_responses_of_other_endpoints() {
+        let store = InMemoryStore::default();
+        let ctx = create_context("0000-0001");
+        let invocations = AtomicUsize::new(0);
+        let handle = || async {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Error>(())
+        };
+
+        let mut order = create_request(Some(r#"{"item":"book"}"#), &[("Idempotency-Key", "abc-123")]);
+        order.path = Some("/orders".to_string());
+        let mut refund = order.clone();
+        refund.path = Some("/refunds".to_string());
+        idempotent(&store, &order, &ctx, handle).await;
+        idempotent(&store, &refund, &ctx, handle).await;
+
+        assert_eq!(2, invocations.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_reject_keys_reused_with_a_different_body() {
+        let store = InMemoryStore::default();
+        let ctx = create_context("0000-0001");
+        let invocations = AtomicUsize::new(0);
+        let place_order = || async {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Error>(())
+        };
+
+        let book = create_request(Some(r#"{"item":"book"}"#), &[("Idempotency-Key", "abc-123")]);
+        let pen = create_request(Some(r#"{"item":"pen"}"#), &[("Idempotency-Key", "abc-123")]);
+        idempotent(&store, &book, &ctx, place_order).await;
+        let response = idempotent(&store, &pen, &ctx, place_order).await;
+
+        assert_eq!(422, response.status_code);
+        assert_eq!(
+            Some(Body::Text(r#"{"error":"Idempotency-Key reused with a different request body","request_id":"0000-0001"}"#.to_string())),
+            response.body
+        );
+        assert_eq!(1, invocations.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_always_run_the_handler_without_a_key() {
+        let store = InMemoryStore::default();
+        let ctx = create_context("0000-0001");
+        let invocations = AtomicUsize::new(0);
+        let place_order = || async {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, Error>(())
+        };
+
+        let req = create_request(Some(r#"{"item":"book"}"#), &[]);
+        idempotent(&store, &req, &ctx, place_order).await;
+        idempotent(&store, &req, &ctx, place_order).await;
+
+        assert_eq!(2, invocations.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_not_keep_server_errors() {
+        let store = InMemoryStore::default();
+        let ctx = create_context("0000-0001");
+        let invocations = AtomicUsize::new(0);
+        let place_order = || async {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(Error::from("Database unavailable"))
+        };
+
+        let req = create_request(Some(r#"{"item":"book"}"#), &[("Idempotency-Key", "abc-123")]);
+        let response = idempotent(&store, &req, &ctx, place_order).await;
+        idempotent(&store, &req, &ctx, place_order).await;
+
+        assert_eq!(500, response.status_code);
+        assert_eq!(2, invocations.load(Ordering::SeqCst));
+    }
+}
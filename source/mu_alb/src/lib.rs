@@ -52,6 +52,35 @@
 //! }
 //! ```
 //!
+//! Handlers may return any [crate::Responder], which every [crate::AlbSerialize] type is.
+//! Plain text (`String`, `&'static str` or `Cow<'static, str>`), raw `Vec<u8>` and
+//! `(StatusCode, T)` tuples are supported out of the box.
+//!
+//! Types that can't derive `Serialize`, like those coming from generated crates, can still be
+//! returned as they are. Implement [crate::AlbSerialize] for them, handing their own JSON
+//! representation to [crate::response::create_prebuilt_json], which sends it verbatim.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::alb::AlbTargetGroupResponse;
+//! use mu_alb;
+//!
+//! struct GeneratedUser {
+//!     name: String,
+//! }
+//!
+//! impl GeneratedUser {
+//!     fn to_json(&self) -> String {
+//!         format!(r#"{{"name":"{}"}}"#, self.name)
+//!     }
+//! }
+//!
+//! impl mu_alb::AlbSerialize for GeneratedUser {
+//!     fn to_alb_response(&self) -> AlbTargetGroupResponse {
+//!         mu_alb::response::create_prebuilt_json(200, self.to_json())
+//!     }
+//! }
+//! ```
+//!
 //! ## Custom Request Deserialization
 //! It is also possible to replace the [aws_lambda_events::event::alb::AlbTargetGroupRequest] type
 //! with your custom type in the listener function, it might be convenient when desiging RPC
@@ -88,12 +117,36 @@
 //!
 //! ## Features
 //! - `multi_header`: enables support to multi-value headers and query strings.
-//!    For more on that check the official [AWS documentation about this
-//!    topic](https://docs.aws.amazon.com/elasticloadbalancing/latest/application/lambda-functions.html#multi-value-headers).
+//!   For more on that check the official [AWS documentation about this
+//!   topic](https://docs.aws.amazon.com/elasticloadbalancing/latest/application/lambda-functions.html#multi-value-headers).
+//! - `compression`: enables gzip compression of responses and decompression of gzip or deflate
+//!   encoded request bodies (see the `compression` module).
+//! - `brotli`: enables Brotli as an alternative to gzip in the compression module.
+//! - `forms`: deserializes [RpcRequest] payloads from either JSON or `application/x-www-form-urlencoded`
+//!   bodies, according to the request `Content-Type`. Unknown content types are rejected with 415.
+//! - `path_errors`: includes the path of the offending field (e.g. `.user.age`) in JSON
+//!   deserialization failures.
+//! - `multipart`: enables parsing `multipart/form-data` bodies (see the `multipart` module).
+//! - `json_access_log`: formats the access logs emitted by `listen_events_logged` as JSON.
+//! - `derive`: enables `#[derive(RpcDispatch)]`, routing tagged RPC requests to their handlers.
+//! - `function_url`: exposes handlers through Lambda Function URLs (see the `function_url` module).
 //!
 
 // Internal modules are public, so people can use it whenever it makes sense.
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod deserializer;
+pub mod error;
+#[cfg(feature = "function_url")]
+pub mod function_url;
+pub mod idempotency;
+#[cfg(feature = "multipart")]
+pub mod multipart;
+pub mod pagination;
+pub mod path;
+pub mod query;
+pub mod request;
+pub mod responder;
 pub mod response;
 pub mod runtime;
 pub mod serializer;
@@ -101,14 +154,85 @@ pub mod serializer;
 // Stable, long-term API
 pub use crate::{
     deserializer::AlbDeserialize,
+    deserializer::Binary,
     deserializer::RpcRequest,
+    error::ApiError,
+    error::ErrorFormat,
+    error::Problem,
+    pagination::Paginated,
+    path::PathAndBody,
+    path::PathParams,
+    query::Query,
+    request::RequestContextExt,
+    request::RequestExt,
+    responder::Responder,
+    response::ResponseExt,
     runtime::listen_events,
+    runtime::listen_events_full,
+    runtime::listen_events_full_with,
+    runtime::listen_events_logged,
+    runtime::listen_events_secure,
+    runtime::listen_events_with,
+    runtime::listen_events_with_timeout,
+    runtime::Options,
+    runtime::ALB_MAX_RESPONSE_BYTES,
     serializer::AlbSerialize,
+    serializer::IntoAlbError,
 };
 
+/// Turns an internally tagged enum into an [RpcRequest], generating a `dispatch`
+/// method that routes each variant to the handler defined by its `#[rpc(handler = "...")]`
+/// attribute. Handlers can return any [Responder] type. Requests with an unknown
+/// tag are rejected with `400 Bad Request`.
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct CreateUser { name: String }
+///
+/// #[derive(Deserialize)]
+/// struct DeleteUser { id: u64 }
+///
+/// #[derive(Deserialize, RpcDispatch)]
+/// #[serde(tag = "type")]
+/// enum Operation {
+///     #[rpc(handler = "create_user")]
+///     CreateUser(CreateUser),
+///     #[rpc(handler = "delete_user")]
+///     DeleteUser(DeleteUser),
+/// }
+///
+/// async fn create_user(req: CreateUser) -> Result<String, ApiError> {
+///     Ok(format!("Created {}", req.name))
+/// }
+///
+/// async fn delete_user(req: DeleteUser) -> Result<u64, ApiError> {
+///     Err(ApiError::not_found(format!("User {} not found", req.id)))
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   listen_events(|op: Operation| op.dispatch()).await
+/// }
+/// ```
+#[cfg(feature = "derive")]
+pub use mu_alb_derive::RpcDispatch;
+
+// Used by the code generated by derive macros.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use aws_lambda_events::event::alb::AlbTargetGroupResponse;
+}
+
 // Re-exporting a few entries from mu_runtime, for convenience.
 pub use mu_runtime::{
     Error,
     RuntimeResult,
     Context,
-};
\ No newline at end of file
+};
+
+#[cfg(test)]
+mod testing;
\ No newline at end of file
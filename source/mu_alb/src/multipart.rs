@@ -0,0 +1,231 @@
+//! Parses `multipart/form-data` request bodies, as sent by file-upload forms.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+//! use mu_alb::*;
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   listen_events(|req: AlbTargetGroupRequest| upload(req)).await
+//! }
+//!
+//! async fn upload(req: AlbTargetGroupRequest) -> Result<Vec<String>, Error> {
+//!   let parts = multipart::parse(&req)?;
+//!   Ok(parts.into_iter().filter_map(|part| part.filename).collect())
+//! }
+//! ```
+
+use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+
+use mu_runtime::Error;
+
+use crate::response::headers;
+
+const CRLF: &[u8] = b"\r\n";
+const HEADERS_END: &[u8] = b"\r\n\r\n";
+
+/// A single part of a `multipart/form-data` body.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Part {
+    /// The form field name, from the `Content-Disposition` header.
+    pub name: String,
+    /// The name of the uploaded file, if this part is a file.
+    pub filename: Option<String>,
+    /// The `Content-Type` of this part, if informed.
+    pub content_type: Option<String>,
+    /// The raw content of this part.
+    pub data: Vec<u8>,
+}
+
+impl Part {
+
+    /// The content of this part as text, if it is valid UTF-8.
+    pub fn text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.data).ok()
+    }
+}
+
+/// Parses the `multipart/form-data` body of the given request, using the boundary
+/// defined in its `Content-Type` header. Base64 encoded bodies are decoded first.
+pub fn parse(req: &AlbTargetGroupRequest) -> Result<Vec<Part>, Error> {
+    let boundary = boundary_of(req)?;
+    let body = match &req.body {
+        None => return Err("No payload defined".into()),
+        Some(body) if req.is_base64_encoded => base64::decode(body)
+            .map_err(|cause| format!("Invalid base64 body: {}", cause))?,
+        Some(body) => body.as_bytes().to_vec(),
+    };
+
+    parse_body(&body, &boundary)
+}
+
+/// Extracts the boundary parameter of a `multipart/form-data` content type.
+fn boundary_of(req: &AlbTargetGroupRequest) -> Result<String, Error> {
+    let content_type = headers::from_request(req).get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or("Missing Content-Type header")?;
+
+    let mut params = content_type.split(';');
+    let media_type = params.next().unwrap_or("").trim();
+    if !media_type.eq_ignore_ascii_case("multipart/form-data") {
+        return Err(format!("Unsupported Media Type: {}", media_type).into())
+    }
+
+    params
+        .filter_map(parse_param)
+        .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value)
+        .ok_or_else(|| "Missing multipart boundary".into())
+}
+
+fn parse_body(body: &[u8], boundary: &str) -> Result<Vec<Part>, Error> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    // parts are terminated by a CRLF followed by the delimiter
+    let next_part = format!("\r\n--{}", boundary).into_bytes();
+
+    let mut cursor = find(body, &delimiter, 0)
+        .ok_or("Missing opening multipart boundary")? + delimiter.len();
+    let mut parts = Vec::new();
+
+    loop {
+        let remaining = &body[cursor..];
+        if remaining.starts_with(b"--") {
+            return Ok(parts)
+        }
+        if !remaining.starts_with(CRLF) {
+            return Err("Malformed multipart boundary".into())
+        }
+        cursor += CRLF.len();
+
+        let end = find(body, &next_part, cursor)
+            .ok_or("Missing closing multipart boundary")?;
+        parts.push(parse_part(&body[cursor..end])?);
+        cursor = end + next_part.len();
+    }
+}
+
+fn parse_part(part: &[u8]) -> Result<Part, Error> {
+    let headers_end = find(part, HEADERS_END, 0)
+        .ok_or("Malformed multipart part: missing headers")?;
+    let raw_headers = std::str::from_utf8(&part[..headers_end])
+        .map_err(|cause| format!("Malformed multipart headers: {}", cause))?;
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+
+    for line in raw_headers.split("\r\n") {
+        let (header, value) = match line.split_once(':') {
+            Some(header) => header,
+            None => continue,
+        };
+
+        if header.trim().eq_ignore_ascii_case("content-disposition") {
+            for (key, value) in value.split(';').skip(1).filter_map(parse_param) {
+                if key.eq_ignore_ascii_case("name") {
+                    name = Some(value)
+                } else if key.eq_ignore_ascii_case("filename") {
+                    filename = Some(value)
+                }
+            }
+        } else if header.trim().eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.trim().to_string())
+        }
+    }
+
+    Ok(Part {
+        name: name.ok_or("Malformed multipart part: missing name")?,
+        filename,
+        content_type,
+        data: part[headers_end + HEADERS_END.len()..].to_vec(),
+    })
+}
+
+/// Parses a `key=value` (or `key="value"`) header parameter.
+fn parse_param(param: &str) -> Option<(String, String)> {
+    let (key, value) = param.split_once('=')?;
+    let value = value.trim();
+    let value = value.strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((key.trim().to_string(), value.to_string()))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|position| position + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use mu_runtime::Error;
+
+    use crate::multipart::{self, Part};
+    use crate::testing::create_request;
+
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=\"----mu-boundary\"";
+    const BODY: &str = "------mu-boundary\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\
+        \r\n\
+        Holiday\r\n\
+        ------mu-boundary\r\n\
+        Content-Disposition: form-data; name=\"photo\"; filename=\"beach.png\"\r\n\
+        Content-Type: image/png\r\n\
+        \r\n\
+        \u{1}PNG\r\n\r\nbytes\r\n\
+        ------mu-boundary--\r\n";
+
+    fn expected_parts() -> Vec<Part> {
+        vec![
+            Part {
+                name: "title".to_string(),
+                filename: None,
+                content_type: None,
+                data: b"Holiday".to_vec(),
+            },
+            Part {
+                name: "photo".to_string(),
+                filename: Some("beach.png".to_string()),
+                content_type: Some("image/png".to_string()),
+                data: b"\x01PNG\r\n\r\nbytes".to_vec(),
+            },
+        ]
+    }
+
+    #[test]
+    fn should_parse_text_fields_and_files() {
+        let req = create_request(Some(BODY), &[("Content-Type", CONTENT_TYPE)]);
+
+        let parts = multipart::parse(&req).unwrap();
+        assert_eq!(expected_parts(), parts);
+        assert_eq!(Some("Holiday"), parts[0].text());
+    }
+
+    #[test]
+    fn should_parse_base64_encoded_bodies() {
+        let encoded = base64::encode(BODY);
+        let mut req = create_request(Some(&encoded), &[("Content-Type", CONTENT_TYPE)]);
+        req.is_base64_encoded = true;
+
+        assert_eq!(expected_parts(), multipart::parse(&req).unwrap());
+    }
+
+    #[test]
+    fn should_reject_other_content_types() {
+        let req = create_request(Some(BODY), &[("Content-Type", "application/json")]);
+
+        let result = multipart::parse(&req);
+        assert_eq!(Err(Error::from("Unsupported Media Type: application/json")), result);
+    }
+
+    #[test]
+    fn should_reject_unterminated_bodies() {
+        let req = create_request(
+            Some("------mu-boundary\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday"),
+            &[("Content-Type", CONTENT_TYPE)]);
+
+        assert!(multipart::parse(&req).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+//! Standardizes list endpoints, sending a page of a collection along with headers
+//! describing where it sits in the whole collection.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+//! use mu_alb::*;
+//! use mu_alb::query::Query;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Paging { page: u64, per_page: u64 }
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   listen_events_full(|paging: Query<Paging>, _ctx, req| list_users(paging.into_inner(), req)).await
+//! }
+//!
+//! async fn list_users(paging: Paging, req: AlbTargetGroupRequest) -> Paginated<String> {
+//!   let users = vec!["John".to_string(), "Jane".to_string()];
+//!   Paginated::new(users, 42, paging.page, paging.per_page).with_request(&req)
+//! }
+//! ```
+
+use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+use serde::Serialize;
+
+use crate::query::query_string_of;
+use crate::response::{self, ResponseExt};
+use crate::AlbSerialize;
+
+/// The total number of items in the collection.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+/// The (1-based) number of the page being sent.
+pub const PAGE_HEADER: &str = "X-Page";
+
+/// A page of a collection. Its items are sent as a JSON array, described by the
+/// [TOTAL_COUNT_HEADER] and [PAGE_HEADER] headers. When there are adjacent pages,
+/// a `Link` header points at them (`rel="next"` and `rel="prev"`). The links are
+/// built from the request given to [Paginated::with_request], keeping its path and
+/// other query parameters, otherwise they only carry the paging parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paginated<T> {
+    /// The items of the page.
+    pub items: Vec<T>,
+    /// The total number of items in the collection.
+    pub total: u64,
+    /// The (1-based) number of the page.
+    pub page: u64,
+    /// The maximum number of items per page.
+    pub per_page: u64,
+    /// The link to the current page, up to its paging parameters (e.g. `/users?status=active&`).
+    link_prefix: String,
+}
+
+impl<T> Paginated<T> {
+
+    /// Creates the page number `page` (1-based) of a collection holding `total` items.
+    pub fn new(items: Vec<T>, total: u64, page: u64, per_page: u64) -> Self {
+        Paginated { items, total, page, per_page, link_prefix: "?".to_string() }
+    }
+
+    /// Links adjacent pages to the path of `req`, keeping its query parameters other
+    /// than `page` and `per_page`. Parameters are sorted by name, as the load balancer
+    /// doesn't keep their order.
+    pub fn with_request(mut self, req: &AlbTargetGroupRequest) -> Self {
+        let query_string = query_string_of(req);
+        let mut params: Vec<&str> = query_string.split('&')
+            .filter(|param| !param.is_empty())
+            .filter(|param| !matches!(param.split('=').next(), Some("page") | Some("per_page")))
+            .collect();
+        params.sort_unstable();
+
+        let path = req.path.as_deref().unwrap_or("/");
+        self.link_prefix = match params.is_empty() {
+            true => format!("{}?", path),
+            false => format!("{}?{}&", path, params.join("&")),
+        };
+        self
+    }
+
+    fn link(&self) -> Option<String> {
+        let mut links = Vec::new();
+        if self.page.saturating_mul(self.per_page) < self.total {
+            links.push(format!("<{}page={}&per_page={}>; rel=\"next\"", self.link_prefix, self.page + 1, self.per_page));
+        }
+        if self.page > 1 {
+            links.push(format!("<{}page={}&per_page={}>; rel=\"prev\"", self.link_prefix, self.page - 1, self.per_page));
+        }
+
+        match links.is_empty() {
+            true => None,
+            false => Some(links.join(", ")),
+        }
+    }
+}
+
+impl<T: Serialize> AlbSerialize for Paginated<T> {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        let alb_response = response::create_json_from_obj(200, &self.items);
+        // serialization failures are sent as they are
+        if alb_response.status_code != 200 {
+            return alb_response
+        }
+
+        let alb_response = alb_response
+            .with_header(TOTAL_COUNT_HEADER, &self.total.to_string())
+            .with_header(PAGE_HEADER, &self.page.to_string());
+        match self.link() {
+            Some(link) => alb_response.with_header(http::header::LINK.as_str(), &link),
+            None => alb_response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_lambda_events::encodings::Body;
+
+    use crate::pagination::Paginated;
+    use crate::response::headers;
+    use crate::testing::create_request;
+    use crate::AlbSerialize;
+
+    #[test]
+    fn should_send_the_items_along_with_pagination_headers() {
+        let response = Paginated::new(vec!["a", "b", "c"], 3, 1, 10).to_alb_response();
+
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(Body::Text(r#"["a","b","c"]"#.to_string())), response.body);
+
+        let headers = headers::from_response(&response);
+        assert_eq!("3", headers.get("X-Total-Count").unwrap());
+        assert_eq!("1", headers.get("X-Page").unwrap());
+        assert!(!headers.contains_key("Link"));
+    }
+
+    #[test]
+    fn should_link_to_adjacent_pages() {
+        let response = Paginated::new(vec![4, 5, 6], 9, 2, 3).to_alb_response();
+
+        assert_eq!(
+            r#"<?page=3&per_page=3>; rel="next", <?page=1&per_page=3>; rel="prev""#,
+            headers::from_response(&response).get("Link").unwrap()
+        );
+
+        let last_page = Paginated::new(vec![7, 8, 9], 9, 3, 3).to_alb_response();
+        assert_eq!(
+            r#"<?page=2&per_page=3>; rel="prev""#,
+            headers::from_response(&last_page).get("Link").unwrap()
+        );
+    }
+
+    #[test]
+    fn should_keep_the_path_and_other_query_parameters_in_links() {
+        let mut req = create_request(None, &[]);
+        req.path = Some("/users".to_string());
+        for (name, value) in [("status", "active"), ("page", "2"), ("per_page", "3"), ("name", "J%C3%BAlio")] {
+            req.query_string_parameters.insert(name.to_string(), value.to_string());
+            req.multi_value_query_string_parameters.insert(name.to_string(), vec![value.to_string()]);
+        }
+
+        let response = Paginated::new(vec![4, 5, 6], 9, 2, 3).with_request(&req).to_alb_response();
+
+        assert_eq!(
+            r#"</users?name=J%C3%BAlio&status=active&page=3&per_page=3>; rel="next", </users?name=J%C3%BAlio&status=active&page=1&per_page=3>; rel="prev""#,
+            headers::from_response(&response).get("Link").unwrap()
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! Utilities to deserialize the parameters of request paths (e.g. the `3` of
+//! `/users/3`) into ordinary structures.
+//!
+//! Parameters are named in a pattern, registered by implementing [PathParams]
+//! for the target structure, and deserialized with Serde as query strings are.
+//!
+//! ```
+//! use mu_alb::path::{self, PathParams};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct UserId { id: u32 }
+//!
+//! impl PathParams for UserId {
+//!     const PATTERN: &'static str = "/users/{id}";
+//! }
+//!
+//! let user: UserId = path::from_path(UserId::PATTERN, "/users/3").unwrap();
+//! assert_eq!(3, user.id);
+//! ```
+//!
+//! RESTful updates, needing both the path parameters and the body of the request,
+//! can receive them at once through [PathAndBody].
+//!
+//! ```no_run
+//! use mu_alb::*;
+//! use mu_alb::path::{PathAndBody, PathParams};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct UserId { id: u32 }
+//!
+//! impl PathParams for UserId {
+//!     const PATTERN: &'static str = "/users/{id}";
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct User { name: String }
+//!
+//! impl RpcRequest for User {}
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   listen_events(|req: PathAndBody<UserId, User>| update_user(req)).await
+//! }
+//!
+//! async fn update_user(req: PathAndBody<UserId, User>) -> Result<String, Error> {
+//!   let (user_id, user) = req.into_inner();
+//!   Ok(format!("Renamed user {} to {}", user_id.id, user.name))
+//! }
+//! ```
+
+use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+use serde::Deserialize;
+
+use mu_runtime::{Context, Error};
+
+use crate::deserializer::AlbDeserialize;
+use crate::ApiError;
+
+/// Structures deserialized from the parameters of request paths.
+pub trait PathParams {
+    /// The pattern paths are matched against, naming each parameter between braces
+    /// (e.g. `/users/{id}/posts/{post_id}`). Other segments must match literally.
+    const PATTERN: &'static str;
+}
+
+/// Matches the `path` against the `pattern`, deserializing its parameters into `T`.
+/// Paths not matching the pattern are rejected.
+pub fn from_path<T>(pattern: &str, path: &str) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let params = match_path(pattern, path)
+        .ok_or_else(|| Error::from(format!("Path {} doesn't match {}", path, pattern)))?;
+
+    // the load balancer hands paths still url-encoded, thus only the characters
+    // meaningful to query strings (but not to paths) are escaped
+    let query = params.iter()
+        .map(|(name, value)| format!("{}={}", name, escape_query_chars(value)))
+        .collect::<Vec<String>>()
+        .join("&");
+
+    serde_urlencoded::from_str(&query)
+        .map_err(|cause| format!("Invalid path parameters: {}", cause).into())
+}
+
+/// Pairs the parameters named in the `pattern` with their values in the `path`.
+fn match_path<'a>(pattern: &'a str, path: &'a str) -> Option<Vec<(&'a str, &'a str)>> {
+    let mut patterns = segments_of(pattern);
+    let mut segments = segments_of(path);
+    let mut params = Vec::new();
+
+    loop {
+        match (patterns.next(), segments.next()) {
+            (None, None) => return Some(params),
+            (Some(expected), Some(segment)) => {
+                match expected.strip_prefix('{').and_then(|name| name.strip_suffix('}')) {
+                    Some(name) => params.push((name, segment)),
+                    None if expected == segment => {},
+                    None => return None,
+                }
+            },
+            _ => return None,
+        }
+    }
+}
+
+fn segments_of(path: &str) -> impl Iterator<Item=&str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn escape_query_chars(value: &str) -> String {
+    value.replace('+', "%2B")
+        .replace('&', "%26")
+        .replace('=', "%3D")
+}
+
+/// The parameters of the request path, deserialized into `P` according to its
+/// [PathParams::PATTERN], along with the request body, deserialized into `B`.
+/// Requests whose path doesn't match the pattern are rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PathAndBody<P, B>(pub P, pub B);
+
+impl<P, B> PathAndBody<P, B> {
+    /// Unwraps the path parameters and the body.
+    pub fn into_inner(self) -> (P, B) {
+        (self.0, self.1)
+    }
+
+    /// The path parameters.
+    pub fn path(&self) -> &P {
+        &self.0
+    }
+
+    /// The request body.
+    pub fn body(&self) -> &B {
+        &self.1
+    }
+}
+
+impl<P, B> AlbDeserialize<PathAndBody<P, B>> for PathAndBody<P, B>
+where
+    P: for<'de> Deserialize<'de> + PathParams,
+    B: AlbDeserialize<B>,
+{
+    const CONTENT_TYPE: Option<&'static str> = B::CONTENT_TYPE;
+
+    fn check_media_type(req: &AlbTargetGroupRequest) -> Result<(), ApiError> {
+        B::check_media_type(req)
+    }
+
+    fn from_alb_request(req: AlbTargetGroupRequest, ctx: Context) -> Result<PathAndBody<P, B>, Error> {
+        let params = from_path(P::PATTERN, req.path.as_deref().unwrap_or("/"))?;
+        let body = B::from_alb_request(req, ctx)?;
+        Ok(PathAndBody(params, body))
+    }
+
+    fn from_alb_request_ref(req: &AlbTargetGroupRequest, ctx: &Context) -> Result<PathAndBody<P, B>, Error> {
+        let params = from_path(P::PATTERN, req.path.as_deref().unwrap_or("/"))?;
+        let body = B::from_alb_request_ref(req, ctx)?;
+        Ok(PathAndBody(params, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use mu_runtime::Context;
+
+    use crate::deserializer::{AlbDeserialize, RpcRequest};
+    use crate::path::{self, PathAndBody, PathParams};
+    use crate::testing::create_request;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct UserId {
+        id: u32,
+    }
+
+    impl PathParams for UserId {
+        const PATTERN: &'static str = "/users/{id}";
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct User {
+        name: String,
+    }
+
+    impl RpcRequest for User {}
+
+    #[test]
+    fn should_deserialize_the_path_and_the_body_at_once() {
+        let mut req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        req.path = Some("/users/3".to_string());
+
+        let update = PathAndBody::<UserId, User>::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!((UserId { id: 3 }, User { name: "John".to_string() }), update.into_inner());
+    }
+
+    #[test]
+    fn should_reject_paths_not_matching_the_pattern() {
+        let mut req = create_request(Some(r#"{"name":"John"}"#), &[]);
+        req.path = Some("/groups/3".to_string());
+
+        let error = PathAndBody::<UserId, User>::from_alb_request(req, Context::default()).unwrap_err();
+        assert_eq!("Path /groups/3 doesn't match /users/{id}", format!("{}", error));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct PostId {
+        user: String,
+        post: u64,
+    }
+
+    #[test]
+    fn should_decode_the_parameters() {
+        let post: PostId = path::from_path("/users/{user}/posts/{post}", "/users/john%20doe+1/posts/7/").unwrap();
+        assert_eq!(PostId { user: "john doe+1".to_string(), post: 7 }, post);
+
+        assert!(path::from_path::<PostId>("/users/{user}/posts/{post}", "/users/john/posts/seven").is_err());
+    }
+}
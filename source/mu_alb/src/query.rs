@@ -0,0 +1,238 @@
+//! Utilities to deserialize query strings into ordinary structures.
+//!
+//! Query strings are deserialized with Serde, thus structures can
+//! rely on `#[serde(default)]` and custom deserializers as usual.
+//!
+//! ```
+//! use mu_alb::query::{self, CommaSeparated};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Filter {
+//!     ids: CommaSeparated<i32>,
+//!     #[serde(default)]
+//!     page: u32,
+//! }
+//!
+//! let filter: Filter = query::from_query_string("ids=1,2,3").unwrap();
+//! assert_eq!(vec![1, 2, 3], filter.ids.into_inner());
+//! assert_eq!(0, filter.page);
+//! ```
+//!
+//! Handlers of `GET` endpoints can receive the query string of the request, already
+//! deserialized, by wrapping the target structure in a [Query].
+//!
+//! ```no_run
+//! use mu_alb::*;
+//! use mu_alb::query::Query;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Paging { page: u32, limit: u32 }
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   listen_events(|paging: Query<Paging>| list_users(paging.into_inner())).await
+//! }
+//!
+//! async fn list_users(paging: Paging) -> Result<Vec<String>, Error> {
+//!   Ok(vec![format!("page {} of {} users", paging.page, paging.limit)])
+//! }
+//! ```
+
+use std::fmt::Display;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+use serde::de::{Deserialize, Deserializer, Error as DeError, Visitor};
+
+use mu_runtime::{Context, Error};
+
+use crate::deserializer::AlbDeserialize;
+
+/// Deserializes a url-encoded query string (without the leading `?`) into `T`.
+pub fn from_query_string<T>(query: &str) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    serde_urlencoded::from_str(query)
+        .map_err(|cause| format!("Invalid query string: {}", cause).into())
+}
+
+/// The query string of a request, deserialized into `T`. Requests missing a required
+/// parameter are rejected with `400 Bad Request`, naming the missing parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Query<T>(pub T);
+
+impl<T> Query<T> {
+    /// Unwraps the deserialized query string.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Query<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> AlbDeserialize<Query<T>> for Query<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    fn from_alb_request(req: AlbTargetGroupRequest, ctx: Context) -> Result<Query<T>, Error> {
+        Self::from_alb_request_ref(&req, &ctx)
+    }
+
+    fn from_alb_request_ref(req: &AlbTargetGroupRequest, _ctx: &Context) -> Result<Query<T>, Error> {
+        from_query_string(&query_string_of(req)).map(Query)
+    }
+}
+
+/// Rebuilds the query string of the request. The load balancer hands its
+/// parameters still url-encoded, thus they are joined as they are.
+#[cfg(not(feature = "multi_header"))]
+pub(crate) fn query_string_of(req: &AlbTargetGroupRequest) -> String {
+    req.query_string_parameters.iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// Rebuilds the query string of the request. The load balancer hands its
+/// parameters still url-encoded, thus they are joined as they are.
+#[cfg(feature = "multi_header")]
+pub(crate) fn query_string_of(req: &AlbTargetGroupRequest) -> String {
+    req.multi_value_query_string_parameters.iter()
+        .flat_map(|(name, values)| values.iter().map(move |value| format!("{}={}", name, value)))
+        .collect::<Vec<String>>()
+        .join("&")
+}
+
+/// A list of values sent as a single comma-separated parameter (e.g. `?ids=1,2,3`).
+/// Each item is parsed individually with its [FromStr] implementation.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CommaSeparated<T>(pub Vec<T>);
+
+impl<T> CommaSeparated<T> {
+    /// Unwraps the parsed values.
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> Deref for CommaSeparated<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.0
+    }
+}
+
+impl<'de, T> Deserialize<'de> for CommaSeparated<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(CommaSeparatedVisitor(PhantomData))
+    }
+}
+
+struct CommaSeparatedVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for CommaSeparatedVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Value = CommaSeparated<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a comma-separated list of values")
+    }
+
+    fn visit_str<E: DeError>(self, value: &str) -> Result<Self::Value, E> {
+        let values = value.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| item.parse::<T>().map_err(E::custom))
+            .collect::<Result<Vec<T>, E>>()?;
+        Ok(CommaSeparated(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use mu_runtime::Context;
+
+    use crate::deserializer::AlbDeserialize;
+    use crate::query::{self, CommaSeparated, Query};
+    use crate::testing::create_request;
+
+    #[derive(Deserialize)]
+    struct Filter {
+        #[serde(default)]
+        ids: CommaSeparated<i32>,
+        #[serde(default)]
+        limit: u32,
+    }
+
+    #[test]
+    fn should_deserialize_comma_separated_values() {
+        let filter: Filter = query::from_query_string("ids=1,2,3&limit=10").unwrap();
+        assert_eq!(vec![1, 2, 3], filter.ids.into_inner());
+        assert_eq!(10, filter.limit);
+    }
+
+    #[test]
+    fn should_fallback_to_defaults() {
+        let filter: Filter = query::from_query_string("").unwrap();
+        assert!(filter.ids.is_empty());
+        assert_eq!(0, filter.limit);
+    }
+
+    #[test]
+    fn should_report_invalid_values() {
+        let result = query::from_query_string::<Filter>("ids=1,two,3");
+        assert!(result.is_err());
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Paging {
+        page: u32,
+        limit: u32,
+    }
+
+    fn create_request_with_query(params: &[(&str, &str)]) -> aws_lambda_events::event::alb::AlbTargetGroupRequest {
+        let mut req = create_request(None, &[]);
+        for (name, value) in params {
+            req.query_string_parameters.insert(name.to_string(), value.to_string());
+            req.multi_value_query_string_parameters.insert(name.to_string(), vec![value.to_string()]);
+        }
+        req
+    }
+
+    #[test]
+    fn should_deserialize_the_query_string_of_requests() {
+        let req = create_request_with_query(&[("page", "2"), ("limit", "10")]);
+
+        let paging = Query::<Paging>::from_alb_request(req, Context::default()).unwrap();
+        assert_eq!(Paging { page: 2, limit: 10 }, paging.into_inner());
+    }
+
+    #[test]
+    fn should_name_missing_parameters() {
+        let req = create_request_with_query(&[("page", "2")]);
+
+        let error = Query::<Paging>::from_alb_request(req, Context::default()).unwrap_err();
+        assert_eq!("Invalid query string: missing field `limit`", format!("{}", error));
+    }
+}
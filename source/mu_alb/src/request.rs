@@ -0,0 +1,149 @@
+//! Convenience accessors for [AlbTargetGroupRequest] metadata.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+//! use mu_alb::*;
+//!
+//! #[tokio::main]
+//! async fn main() -> RuntimeResult {
+//!   listen_events(|req: AlbTargetGroupRequest| describe(req)).await
+//! }
+//!
+//! async fn describe(req: AlbTargetGroupRequest) -> Result<String, Error> {
+//!   let target_group = req.target_group_name().unwrap_or("unknown");
+//!   let trace_id = req.trace_id().unwrap_or("none");
+//!   Ok(format!("Served by {} (trace {})", target_group, trace_id))
+//! }
+//! ```
+
+use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+use http::Method;
+pub use aws_lambda_events::event::alb::{
+    AlbTargetGroupRequestContext as RequestContext, ElbContext
+};
+
+use crate::response::headers;
+
+/// The header carrying the trace id assigned by the Application Load Balancer.
+pub const TRACE_ID: &str = "X-Amzn-Trace-Id";
+
+/// Typed getters for the [RequestContext] of a request, and the tracing information
+/// attached to it by the Application Load Balancer. Missing data yields `None`.
+pub trait RequestContextExt {
+    /// The ARN of the target group that forwarded the request.
+    fn target_group_arn(&self) -> Option<&str>;
+
+    /// The name of the target group, as defined in its ARN.
+    fn target_group_name(&self) -> Option<&str>;
+
+    /// The region of the load balancer, as defined in the target group ARN.
+    fn region(&self) -> Option<&str>;
+
+    /// The AWS account owning the load balancer, as defined in the target group ARN.
+    fn account_id(&self) -> Option<&str>;
+
+    /// The trace id assigned by the load balancer (e.g. `Root=1-6098e233-3954de7d7d5907870e5fafb1`).
+    fn trace_id(&self) -> Option<&str>;
+}
+
+impl RequestContextExt for AlbTargetGroupRequest {
+    fn target_group_arn(&self) -> Option<&str> {
+        self.request_context.elb.target_group_arn.as_deref()
+            .filter(|arn| !arn.is_empty())
+    }
+
+    // e.g. arn:aws:elasticloadbalancing:us-west-2:140868587617:targetgroup/events/b20a2a8c23a30ff9
+    fn target_group_name(&self) -> Option<&str> {
+        let resource = self.target_group_arn()?.splitn(6, ':').nth(5)?;
+        resource.strip_prefix("targetgroup/")?.split('/').next()
+    }
+
+    fn region(&self) -> Option<&str> {
+        arn_segment(self.target_group_arn()?, 3)
+    }
+
+    fn account_id(&self) -> Option<&str> {
+        arn_segment(self.target_group_arn()?, 4)
+    }
+
+    fn trace_id(&self) -> Option<&str> {
+        headers::from_request(self).get(TRACE_ID)
+            .and_then(|value| value.to_str().ok())
+    }
+}
+
+/// Typed getters for the request line of a request.
+pub trait RequestExt {
+    /// The HTTP method of the request, regardless of its case, allowing
+    /// handlers to `match req.method() { Method::POST => ... }`.
+    fn method(&self) -> Method;
+}
+
+impl RequestExt for AlbTargetGroupRequest {
+    // non-standard casings (e.g. "post") are kept as extension methods when deserialized
+    fn method(&self) -> Method {
+        let method = self.http_method.as_str();
+        match method.bytes().any(|byte| byte.is_ascii_lowercase()) {
+            false => self.http_method.clone(),
+            true => Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .unwrap_or(Method::GET),
+        }
+    }
+}
+
+fn arn_segment(arn: &str, index: usize) -> Option<&str> {
+    arn.split(':').nth(index).filter(|segment| !segment.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+
+    use http::Method;
+
+    use crate::request::{RequestContextExt, RequestExt};
+    use crate::testing::create_request;
+
+    const SAMPLE_ALB_REQUEST: &str = include_str!("../tests/sample_alb_request.json");
+
+    #[test]
+    fn should_extract_the_target_group_details() {
+        let req: AlbTargetGroupRequest = serde_json::from_str(SAMPLE_ALB_REQUEST).unwrap();
+
+        assert_eq!(
+            Some("arn:aws:elasticloadbalancing:us-west-2:140868587617:targetgroup/events-list-live/b20a2a8c23a30ff9"),
+            req.target_group_arn()
+        );
+        assert_eq!(Some("events-list-live"), req.target_group_name());
+        assert_eq!(Some("us-west-2"), req.region());
+        assert_eq!(Some("140868587617"), req.account_id());
+    }
+
+    #[test]
+    fn should_extract_the_trace_id() {
+        let req = create_request(None, &[("X-Amzn-Trace-Id", "Root=1-6098e233-3954de7d7d5907870e5fafb1")]);
+
+        assert_eq!(Some("Root=1-6098e233-3954de7d7d5907870e5fafb1"), req.trace_id());
+    }
+
+    #[test]
+    fn should_return_none_when_the_context_is_absent() {
+        let req = create_request(None, &[]);
+
+        assert_eq!(None, req.target_group_arn());
+        assert_eq!(None, req.target_group_name());
+        assert_eq!(None, req.region());
+        assert_eq!(None, req.account_id());
+        assert_eq!(None, req.trace_id());
+    }
+
+    #[test]
+    fn should_read_the_method_regardless_of_its_case() {
+        for method in &["POST", "post", "Post"] {
+            let sample = SAMPLE_ALB_REQUEST.replace(r#""GET""#, &format!("{:?}", method));
+            let req: AlbTargetGroupRequest = serde_json::from_str(&sample).unwrap();
+
+            assert_eq!(Method::POST, req.method());
+        }
+    }
+}
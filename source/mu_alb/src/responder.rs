@@ -0,0 +1,195 @@
+//! Provides the [Responder] trait, implemented by the values handlers can return.
+//!
+//! Every [AlbSerialize] type is a [Responder], so it remains the lower-level escape
+//! hatch to define how custom types are sent to the Application Load Balancer.
+//! On top of that, a few convenient conversions are provided:
+//!
+//! ```no_run
+//! use http::StatusCode;
+//! use mu_alb::*;
+//!
+//! async fn hello() -> String {
+//!     "Hello, mate".to_string()
+//! }
+//!
+//! async fn ping() -> &'static str {
+//!     "pong"
+//! }
+//!
+//! async fn created() -> (StatusCode, Vec<u64>) {
+//!     (StatusCode::CREATED, vec![1, 2, 3])
+//! }
+//! ```
+
+use std::borrow::Cow;
+
+use aws_lambda_events::encodings::Body;
+use aws_lambda_events::event::alb::AlbTargetGroupResponse;
+use http::StatusCode;
+use serde::Serialize;
+
+use mu_runtime::Context;
+
+use crate::error::ErrorFormat;
+use crate::response::{self, content_types};
+use crate::AlbSerialize;
+
+/// Converts the value returned by a handler into an ALB response.
+pub trait Responder {
+    fn into_alb_response(self) -> AlbTargetGroupResponse;
+
+    /// Converts into an ALB response, having access to the invocation context.
+    /// Defaults to [Responder::into_alb_response].
+    fn into_alb_response_with(self, _ctx: &Context) -> AlbTargetGroupResponse
+    where
+        Self: Sized,
+    {
+        self.into_alb_response()
+    }
+
+    /// Converts into an ALB response, as [Responder::into_alb_response_with] does,
+    /// rendering failures lacking their own representation in the given `format`.
+    /// Defaults to [Responder::into_alb_response_with].
+    fn into_alb_response_formatted(self, ctx: &Context, _format: ErrorFormat) -> AlbTargetGroupResponse
+    where
+        Self: Sized,
+    {
+        self.into_alb_response_with(ctx)
+    }
+}
+
+/// Keeps every [AlbSerialize] type, including `Result`, `Option` and `http::Response`, working.
+impl<T: AlbSerialize> Responder for T {
+    fn into_alb_response(self) -> AlbTargetGroupResponse {
+        self.to_alb_response()
+    }
+
+    fn into_alb_response_with(self, ctx: &Context) -> AlbTargetGroupResponse {
+        self.to_alb_response_with(ctx)
+    }
+
+    fn into_alb_response_formatted(self, ctx: &Context, format: ErrorFormat) -> AlbTargetGroupResponse {
+        self.to_alb_response_formatted(ctx, format)
+    }
+}
+
+/// Sent as a `200 OK` plain text response.
+impl Responder for String {
+    fn into_alb_response(self) -> AlbTargetGroupResponse {
+        response::create_as_plain_text(200, Some(self))
+    }
+}
+
+/// Sent as a `200 OK` plain text response, rather than as a JSON-quoted string.
+impl Responder for &'static str {
+    fn into_alb_response(self) -> AlbTargetGroupResponse {
+        response::create_as_plain_text(200, Some(self.to_string()))
+    }
+}
+
+/// Sent as a `200 OK` plain text response.
+impl Responder for Cow<'static, str> {
+    fn into_alb_response(self) -> AlbTargetGroupResponse {
+        response::create_as_plain_text(200, Some(self.into_owned()))
+    }
+}
+
+/// Sent as a base64 encoded `200 OK` response, typed as `application/octet-stream`.
+impl Responder for Vec<u8> {
+    fn into_alb_response(self) -> AlbTargetGroupResponse {
+        let mut alb_response = response::create_with_content_type(
+            200, None, content_types::OCTET_STREAM.to_string());
+        alb_response.body = Some(Body::Text(base64::encode(self)));
+        alb_response.is_base64_encoded = true;
+        alb_response
+    }
+}
+
+/// The value is sent as JSON, using the given status code.
+impl<T: Serialize> Responder for (StatusCode, T) {
+    fn into_alb_response(self) -> AlbTargetGroupResponse {
+        let (status, body) = self;
+        response::create_json_from_obj(status.as_u16() as i64, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use aws_lambda_events::encodings::Body;
+    use aws_lambda_events::event::alb::AlbTargetGroupResponse;
+    use http::StatusCode;
+
+    use crate::response::{self, headers};
+    use crate::{ApiError, Responder};
+
+    fn body_of(response: &AlbTargetGroupResponse) -> &str {
+        match &response.body {
+            Some(Body::Text(body)) => body,
+            other => panic!("Unexpected body: {:?}", other),
+        }
+    }
+
+    fn content_type_of(response: &AlbTargetGroupResponse) -> &str {
+        headers::from_response(response)[headers::CONTENT_TYPE].to_str().unwrap()
+    }
+
+    #[test]
+    fn should_keep_alb_serialize_types_working() {
+        let response = response::create_as_plain_text(202, Some("Accepted".to_string()))
+            .into_alb_response();
+        assert_eq!(202, response.status_code);
+
+        let result: Result<u64, ApiError> = Err(ApiError::not_found("x"));
+        assert_eq!(404, result.into_alb_response().status_code);
+
+        assert_eq!(404, Option::<u64>::None.into_alb_response().status_code);
+    }
+
+    #[test]
+    fn should_send_strings_as_plain_text() {
+        let response = "Hello".to_string().into_alb_response();
+
+        assert_eq!(200, response.status_code);
+        assert_eq!("text/plain", content_type_of(&response));
+        assert_eq!("Hello", body_of(&response));
+    }
+
+    #[test]
+    fn should_send_static_strs_as_plain_text_without_quotes() {
+        let response = "pong".into_alb_response();
+
+        assert_eq!(200, response.status_code);
+        assert_eq!("text/plain", content_type_of(&response));
+        assert_eq!("pong", body_of(&response));
+    }
+
+    #[test]
+    fn should_send_cows_as_plain_text() {
+        let borrowed: Cow<'static, str> = Cow::Borrowed("pong");
+        assert_eq!("pong", body_of(&borrowed.into_alb_response()));
+
+        let owned: Cow<'static, str> = Cow::Owned("pong".to_string());
+        assert_eq!("text/plain", content_type_of(&owned.into_alb_response()));
+    }
+
+    #[test]
+    fn should_send_bytes_base64_encoded() {
+        let response = vec![0u8, 1, 2].into_alb_response();
+
+        assert_eq!(200, response.status_code);
+        assert!(response.is_base64_encoded);
+        assert_eq!("application/octet-stream", content_type_of(&response));
+        assert_eq!("AAEC", body_of(&response));
+    }
+
+    #[test]
+    fn should_send_values_with_the_given_status_code() {
+        let response = (StatusCode::CREATED, vec![1, 2]).into_alb_response();
+
+        assert_eq!(201, response.status_code);
+        assert_eq!("application/json", content_type_of(&response));
+        assert_eq!("[1,2]", body_of(&response));
+    }
+}
@@ -1,21 +1,33 @@
 use serde::Serialize;
+use aws_lambda_events::event::alb::AlbTargetGroupRequest;
 use aws_lambda_events::event::alb::AlbTargetGroupResponse as Response;
 use http::{HeaderMap, HeaderValue};
 use aws_lambda_events::encodings::Body;
 use http::header::HeaderName;
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
 
 /// Known content types.
 pub mod content_types {
     pub const JSON: &str = "application/json";
+    pub const FORM: &str = "application/x-www-form-urlencoded";
     pub const PLAIN_TEXT: &str = "text/plain";
+    pub const OCTET_STREAM: &str = "application/octet-stream";
+    pub const PROBLEM_JSON: &str = "application/problem+json";
 }
 
 /// Common header utilities.
 pub mod headers {
     use std::collections::HashMap;
 
+    use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+
     pub const CONTENT_TYPE: &str = "Content-Type";
+    pub const REQUEST_ID: &str = "X-Request-Id";
 
     #[cfg(not(feature = "multi_header"))]
     pub type HeaderMap = HashMap<String, String>;
@@ -23,31 +35,311 @@ pub mod headers {
     #[cfg(feature = "multi_header")]
     pub type HeaderMap = HashMap<String, Vec<String>>;
 
+    /// Response headers presenting the same API whether `multi_header` is enabled or not,
+    /// sparing handlers from duplicating their code for each configuration. It converts
+    /// into the [HeaderMap] of the enabled configuration.
+    ///
+    /// ```
+    /// use mu_alb::response::{self, headers::Headers};
+    ///
+    /// let mut headers = Headers::new();
+    /// headers.insert("Content-Type", "text/csv");
+    /// headers.append("Set-Cookie", "theme=dark");
+    ///
+    /// let res = response::create(200, Some("id,name".to_string()), headers.into());
+    /// ```
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Headers(HeaderMap);
+
+    impl Headers {
+
+        /// Creates an empty set of headers.
+        pub fn new() -> Self {
+            Headers(HashMap::new())
+        }
+
+        /// Sets the header, replacing any previous value.
+        #[cfg(not(feature = "multi_header"))]
+        pub fn insert(&mut self, name: &str, value: &str) {
+            self.0.insert(name.to_string(), value.to_string());
+        }
+
+        /// Sets the header, replacing any previous value.
+        #[cfg(feature = "multi_header")]
+        pub fn insert(&mut self, name: &str, value: &str) {
+            self.0.insert(name.to_string(), vec![value.to_string()]);
+        }
+
+        /// Adds a value to the header. Without `multi_header`, a header holds a single
+        /// value, thus only the last appended one is kept.
+        #[cfg(not(feature = "multi_header"))]
+        pub fn append(&mut self, name: &str, value: &str) {
+            self.insert(name, value)
+        }
+
+        /// Adds a value to the header. Without `multi_header`, a header holds a single
+        /// value, thus only the last appended one is kept.
+        #[cfg(feature = "multi_header")]
+        pub fn append(&mut self, name: &str, value: &str) {
+            self.0.entry(name.to_string()).or_default().push(value.to_string());
+        }
+
+        /// The first value of the header, if any.
+        pub fn get(&self, name: &str) -> Option<&str> {
+            self.get_all(name).into_iter().next()
+        }
+
+        /// All the values of the header, in the order they were added.
+        #[cfg(not(feature = "multi_header"))]
+        pub fn get_all(&self, name: &str) -> Vec<&str> {
+            self.0.get(name).map(String::as_str).into_iter().collect()
+        }
+
+        /// All the values of the header, in the order they were added.
+        #[cfg(feature = "multi_header")]
+        pub fn get_all(&self, name: &str) -> Vec<&str> {
+            self.0.get(name).into_iter().flatten().map(String::as_str).collect()
+        }
+
+        /// The headers, shaped after the enabled configuration.
+        pub fn into_inner(self) -> HeaderMap {
+            self.0
+        }
+    }
+
+    impl From<Headers> for HeaderMap {
+        fn from(headers: Headers) -> Self {
+            headers.into_inner()
+        }
+    }
+
     /// Creates a single entry header for the given __header_name__ and __value__ arguments.
-    #[cfg(not(feature = "multi_header"))]
     pub fn create_for(header_name: &str, value: &str) -> HeaderMap {
-        let mut headers = HashMap::new();
-        headers.insert(header_name.to_string(), value.to_string());
-        headers
+        let mut headers = Headers::new();
+        headers.insert(header_name, value);
+        headers.into()
     }
 
-    /// Creates a single entry header for the given __header_name__ and __value__ arguments.
+    /// Returns the headers of a request, taking into account whether `multi_header` is enabled.
+    #[cfg(not(feature = "multi_header"))]
+    pub fn from_request(req: &AlbTargetGroupRequest) -> &http::HeaderMap {
+        &req.headers
+    }
+
+    /// Returns the headers of a request, taking into account whether `multi_header` is enabled.
     #[cfg(feature = "multi_header")]
-    pub fn create_for(header_name: &str, value: &str) -> HeaderMap {
-        let mut headers = HashMap::new();
-        headers.insert(header_name.to_string(), vec![value.to_string()]);
-        headers
+    pub fn from_request(req: &AlbTargetGroupRequest) -> &http::HeaderMap {
+        &req.multi_value_headers
+    }
+
+    /// Returns the mutable headers of a request, taking into account whether `multi_header` is enabled.
+    #[cfg(not(feature = "multi_header"))]
+    pub fn from_request_mut(req: &mut AlbTargetGroupRequest) -> &mut http::HeaderMap {
+        &mut req.headers
+    }
+
+    /// Returns the mutable headers of a request, taking into account whether `multi_header` is enabled.
+    #[cfg(feature = "multi_header")]
+    pub fn from_request_mut(req: &mut AlbTargetGroupRequest) -> &mut http::HeaderMap {
+        &mut req.multi_value_headers
+    }
+
+    /// Returns the media type of the request `Content-Type` header, disregarding
+    /// parameters like `charset`.
+    pub fn media_type(req: &AlbTargetGroupRequest) -> Option<&str> {
+        from_request(req).get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim)
+    }
+
+    /// Returns the headers of a response, taking into account whether `multi_header` is enabled.
+    #[cfg(not(feature = "multi_header"))]
+    pub fn from_response(response: &AlbTargetGroupResponse) -> &http::HeaderMap {
+        &response.headers
+    }
+
+    /// Returns the headers of a response, taking into account whether `multi_header` is enabled.
+    #[cfg(feature = "multi_header")]
+    pub fn from_response(response: &AlbTargetGroupResponse) -> &http::HeaderMap {
+        &response.multi_value_headers
+    }
+
+    /// Mutable version of [from_response].
+    #[cfg(not(feature = "multi_header"))]
+    pub fn from_response_mut(response: &mut AlbTargetGroupResponse) -> &mut http::HeaderMap {
+        &mut response.headers
+    }
+
+    /// Mutable version of [from_response].
+    #[cfg(feature = "multi_header")]
+    pub fn from_response_mut(response: &mut AlbTargetGroupResponse) -> &mut http::HeaderMap {
+        &mut response.multi_value_headers
+    }
+}
+
+/// The environment variable enabling pretty-printed JSON responses (e.g. `MU_PRETTY_JSON=1`),
+/// easing debugging on local and development environments.
+pub const PRETTY_JSON_ENV: &str = "MU_PRETTY_JSON";
+
+static PRETTY_JSON: OnceLock<bool> = OnceLock::new();
+
+/// Whether JSON responses are pretty-printed. Read from [PRETTY_JSON_ENV] once.
+fn pretty_json() -> bool {
+    *PRETTY_JSON.get_or_init(|| {
+        std::env::var(PRETTY_JSON_ENV)
+            .map(|value| matches!(value.as_str(), "1" | "true"))
+            .unwrap_or(false)
+    })
+}
+
+/// Serializes the object as JSON, compact unless [PRETTY_JSON_ENV] is enabled.
+pub(crate) fn to_json<T: Serialize>(object: &T) -> serde_json::Result<String> {
+    to_json_with(object, pretty_json())
+}
+
+fn to_json_with<T: Serialize>(object: &T, pretty: bool) -> serde_json::Result<String> {
+    match pretty {
+        true => serde_json::to_string_pretty(object),
+        false => serde_json::to_string(object),
     }
 }
 
 /// Creates an ALB-compatible response wrapping a Serde-Serializable object as Json.
+/// It is pretty-printed when the [PRETTY_JSON_ENV] environment variable is enabled.
 pub fn create_json_from_obj<T: Serialize>(status: i64, object: &T) -> Response {
-    match serde_json::to_string(object) {
+    match to_json(object) {
         Ok(serialized) => create_as_json(status, Some(serialized)),
         Err(cause) => create_as_plain_text(500, Some(format!("{}", cause))),
     }
 }
 
+/// Creates an ALB-compatible response wrapping a Serde-Serializable object as Json, with a
+/// typed status code. Same as [create_json_from_obj] otherwise.
+pub fn json<T: Serialize>(status: http::StatusCode, object: &T) -> Response {
+    create_json_from_obj(status.as_u16() as i64, object)
+}
+
+/// Creates an empty-bodied response with a typed status code, without any `Content-Type`.
+pub fn empty(status: http::StatusCode) -> Response {
+    create(status.as_u16() as i64, None, headers::HeaderMap::new())
+}
+
+/// Creates an ALB-compatible response wrapping an in-memory JSON [serde_json::Value]. Unlike
+/// [create_json_from_obj], it writes the value straight out, and can't fail to do so.
+pub fn create_json_value(status: i64, value: serde_json::Value) -> Response {
+    create_as_json(status, Some(json_value_to_string(&value)))
+}
+
+/// Creates an ALB-compatible response from an already serialized JSON string, sending it
+/// verbatim. It suits types that can't derive `Serialize` but know how to write themselves
+/// as JSON. The string is trusted to be valid JSON, and isn't checked nor re-serialized.
+pub fn create_prebuilt_json(status: i64, json: String) -> Response {
+    create_as_json(status, Some(json))
+}
+
+/// Writes the value as JSON, compact unless [PRETTY_JSON_ENV] is enabled.
+pub(crate) fn json_value_to_string(value: &serde_json::Value) -> String {
+    match pretty_json() {
+        true => format!("{:#}", value),
+        false => value.to_string(),
+    }
+}
+
+/// The status code and headers of a response whose body is serialized as JSON. Handlers
+/// return it alongside their result as a `(ResponseParts, T)` tuple when the default
+/// `200 OK` doesn't fit, e.g. when a resource has been created.
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use mu_alb::response::ResponseParts;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Deserialize)]
+/// struct NewUser { name: String }
+///
+/// impl RpcRequest for NewUser {}
+///
+/// #[derive(Serialize)]
+/// struct User { id: u64, name: String }
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   listen_events(|req: NewUser| create_user(req)).await
+/// }
+///
+/// async fn create_user(req: NewUser) -> (ResponseParts, User) {
+///   let user = User { id: 42, name: req.name };
+///   let parts = ResponseParts::new(201).with_header("Location", "/users/42");
+///   (parts, user)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResponseParts {
+    /// The status code of the response.
+    pub status: i64,
+    /// Headers added to the response, besides `Content-Type`.
+    pub headers: HeaderMap,
+}
+
+impl ResponseParts {
+
+    /// Creates the parts of a response with the given status code.
+    pub fn new(status: i64) -> Self {
+        ResponseParts { status, headers: HeaderMap::new() }
+    }
+
+    /// Appends a header to the response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::from_str(name).unwrap(),
+            HeaderValue::from_str(value).unwrap()
+        );
+        self
+    }
+}
+
+impl Default for ResponseParts {
+    fn default() -> Self {
+        ResponseParts::new(200)
+    }
+}
+
+/// The value of the `Strict-Transport-Security` header sent by default (one year).
+pub const DEFAULT_HSTS: &str = "max-age=31536000; includeSubDomains";
+
+/// Hardening headers added to every response, unless the handler has already set
+/// them: `X-Content-Type-Options: nosniff`, `X-Frame-Options: DENY` and, when
+/// defined, `Strict-Transport-Security`.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    /// The value of the `Strict-Transport-Security` header. It is omitted when `None`.
+    /// Defaults to [DEFAULT_HSTS].
+    pub hsts: Option<String>,
+}
+
+impl SecurityHeaders {
+
+    /// Adds the missing security headers to the response.
+    pub fn apply(&self, response: &mut Response) {
+        let headers = headers::from_response_mut(response);
+        headers.entry(http::header::X_CONTENT_TYPE_OPTIONS)
+            .or_insert_with(|| HeaderValue::from_static("nosniff"));
+        headers.entry(http::header::X_FRAME_OPTIONS)
+            .or_insert_with(|| HeaderValue::from_static("DENY"));
+
+        if let Some(hsts) = self.hsts.as_ref().and_then(|hsts| HeaderValue::from_str(hsts).ok()) {
+            headers.entry(http::header::STRICT_TRANSPORT_SECURITY).or_insert(hsts);
+        }
+    }
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        SecurityHeaders { hsts: Some(DEFAULT_HSTS.to_string()) }
+    }
+}
+
 /// Creates an ALB-compatible response wrapping an optional object as JSON.
 pub fn create_as_json(status_code: i64, body: Option<String>) -> Response {
     create_with_content_type(status_code, body, content_types::JSON.to_string())
@@ -58,7 +350,110 @@ pub fn create_as_plain_text(status_code: i64, body: Option<String>) -> Response
     create_with_content_type(status_code, body, content_types::PLAIN_TEXT.to_string())
 }
 
+/// Creates an empty-bodied `204 No Content` response, without any `Content-Type`.
+pub fn no_content() -> Response {
+    create(204, None, headers::HeaderMap::new())
+}
+
+/// Creates an empty-bodied `304 Not Modified` response, without any `Content-Type`.
+pub fn not_modified() -> Response {
+    create(304, None, headers::HeaderMap::new())
+}
+
+/// Creates an empty-bodied `201 Created` response, whose `Location` header points
+/// at the created resource.
+pub fn created(location: &str) -> Response {
+    create(201, None, headers::HeaderMap::new())
+        .with_header(http::header::LOCATION.as_str(), location)
+}
+
+/// Creates an empty-bodied `202 Accepted` response, whose `Location` header points
+/// at the URL clients can poll for the status of the asynchronous job.
+pub fn accepted(status_url: &str) -> Response {
+    create(202, None, headers::HeaderMap::new())
+        .with_header(http::header::LOCATION.as_str(), status_url)
+}
+
+/// Creates a `429 Too Many Requests` response, whose `Retry-After` header tells clients
+/// how long to back off before retrying. See [retry_after_secs] for how it is rounded.
+pub fn too_many_requests(retry_after: Duration) -> Response {
+    create_as_plain_text(429, Some("Too Many Requests".to_string()))
+        .with_header(http::header::RETRY_AFTER.as_str(), &retry_after_secs(retry_after).to_string())
+}
+
+/// The value of the `Retry-After` header for the given duration, in whole seconds.
+/// Sub-second remainders are rounded up, so clients never retry too early.
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    match retry_after.subsec_nanos() {
+        0 => retry_after.as_secs(),
+        _ => retry_after.as_secs() + 1,
+    }
+}
+
+/// Creates an ALB-compatible response wrapping a Serde-Serializable object as Json, tagged
+/// with a weak `ETag` computed from the serialized body. When the `If-None-Match` header of
+/// `req` matches it, an empty-bodied `304 Not Modified` response is returned instead.
 ///
+/// The tag is derived from a non-cryptographic hash, stable across instances running the
+/// same build, thus only meant to spare clients from downloading unchanged representations.
+pub fn json_with_etag<T: Serialize>(status: i64, object: &T, req: &AlbTargetGroupRequest) -> Response {
+    let serialized = match to_json(object) {
+        Ok(serialized) => serialized,
+        Err(cause) => return create_as_plain_text(500, Some(format!("{}", cause))),
+    };
+
+    let etag = weak_etag_of(&serialized);
+    let response = match matches_if_none_match(req, &etag) {
+        true => not_modified(),
+        false => create_as_json(status, Some(serialized)),
+    };
+    response.with_header("ETag", &etag)
+}
+
+/// Creates an ALB-compatible response wrapping a Serde-Serializable object as Json, meant
+/// for static-ish data (e.g. configs, feature flags). It is tagged with a weak `ETag`, as
+/// [json_with_etag] does, and cacheable for `max_age` (`Cache-Control: public, max-age=N`).
+pub fn json_cached<T: Serialize>(status: i64, object: &T, max_age: Duration) -> Response {
+    let serialized = match to_json(object) {
+        Ok(serialized) => serialized,
+        Err(cause) => return create_as_plain_text(500, Some(format!("{}", cause))),
+    };
+
+    let etag = weak_etag_of(&serialized);
+    create_as_json(status, Some(serialized))
+        .with_header("Cache-Control", &format!("public, max-age={}", max_age.as_secs()))
+        .with_header("ETag", &etag)
+}
+
+/// Creates an ALB-compatible response wrapping a Serde-Serializable object as Json,
+/// forbidding any cache from storing it (`Cache-Control: no-store`). Meant for
+/// sensitive endpoints.
+pub fn json_no_store<T: Serialize>(status: i64, object: &T) -> Response {
+    create_json_from_obj(status, object)
+        .with_header("Cache-Control", "no-store")
+}
+
+/// Computes a weak entity tag (e.g. `W/"1b3c6f5e0a9d2c47"`) from the hash of the body.
+fn weak_etag_of(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Checks whether any of the tags of the `If-None-Match` request header matches
+/// the given one, using the weak comparison defined by RFC 7232.
+fn matches_if_none_match(req: &AlbTargetGroupRequest, etag: &str) -> bool {
+    let opaque_tag = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let expected = opaque_tag(etag);
+
+    headers::from_request(req).get_all(http::header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque_tag(tag) == expected)
+}
+
+/// Creates an ALB-compatible response with the given content type.
 pub fn create_with_content_type(
     status_code: i64,
     body: Option<String>,
@@ -70,11 +465,34 @@ pub fn create_with_content_type(
 
 /// Creates a normalised [aws_lambda_events::event::alb::AlbTargetGroupResponse], taking care of
 /// a few details that might lead to 502 errors on the Application Load Balancer.
-#[cfg(not(feature = "multi_header"))]
 pub fn create(
     status_code: i64,
     body: Option<String>,
     headers: headers::HeaderMap,
+) -> Response {
+    let description = format!("{} Response", status_code);
+    create_with_description(status_code, body, headers, Some(description))
+}
+
+/// Creates a normalised [aws_lambda_events::event::alb::AlbTargetGroupResponse], as [create]
+/// does, also setting its `Content-Length` header. Meant for strict downstreams.
+pub fn create_with_content_length(
+    status_code: i64,
+    body: Option<String>,
+    headers: headers::HeaderMap,
+) -> Response {
+    create(status_code, body, headers).with_content_length()
+}
+
+/// Creates a normalised [aws_lambda_events::event::alb::AlbTargetGroupResponse], as [create]
+/// does, but with a custom status description. When no description is given, the canonical
+/// reason phrase of the status code (e.g. "Not Found") is used.
+#[cfg(not(feature = "multi_header"))]
+pub fn create_with_description(
+    status_code: i64,
+    body: Option<String>,
+    headers: headers::HeaderMap,
+    description: Option<String>,
 ) -> Response {
     let mut adapted_headers = HeaderMap::with_capacity(headers.len());
 
@@ -85,21 +503,24 @@ pub fn create(
         );
     }
 
-    create_with_header(status_code, body, adapted_headers)
+    let description = description
+        .or_else(|| reason_phrase(status_code).map(str::to_string));
+    create_with_header(status_code, body, adapted_headers, description)
 }
 
 #[cfg(not(feature = "multi_header"))]
 fn create_with_header(
     status_code: i64,
     body: Option<String>,
-    headers: HeaderMap
+    headers: HeaderMap,
+    status_description: Option<String>,
 ) -> Response {
     Response {
         status_code,
         headers,
         multi_value_headers: HeaderMap::new(),
         is_base64_encoded: false,
-        status_description: Some(format!("{} Response", status_code)),
+        status_description,
         body: match body {
             None => Some(Body::Empty),
             Some(content) => Some(Body::Text(content)),
@@ -107,13 +528,15 @@ fn create_with_header(
     }
 }
 
-/// Creates a normalised [aws_lambda_events::event::alb::AlbTargetGroupResponse], taking care of
-/// a few details that might lead to 502 errors on the Application Load Balancer.
+/// Creates a normalised [aws_lambda_events::event::alb::AlbTargetGroupResponse], as [create]
+/// does, but with a custom status description. When no description is given, the canonical
+/// reason phrase of the status code (e.g. "Not Found") is used.
 #[cfg(feature = "multi_header")]
-pub fn create(
+pub fn create_with_description(
     status_code: i64,
     body: Option<String>,
     headers: headers::HeaderMap,
+    description: Option<String>,
 ) -> Response {
     let mut adapted_headers = HeaderMap::with_capacity(headers.len());
 
@@ -126,21 +549,24 @@ pub fn create(
         }
     }
 
-    create_with_header(status_code, body, adapted_headers)
+    let description = description
+        .or_else(|| reason_phrase(status_code).map(str::to_string));
+    create_with_header(status_code, body, adapted_headers, description)
 }
 
 #[cfg(feature = "multi_header")]
 fn create_with_header(
     status_code: i64,
     body: Option<String>,
-    headers: HeaderMap
+    headers: HeaderMap,
+    status_description: Option<String>,
 ) -> Response {
     Response {
         status_code,
         multi_value_headers: headers,
         headers: HeaderMap::new(),
         is_base64_encoded: false,
-        status_description: Some(format!("{} Response", status_code)),
+        status_description,
         body: match body {
             None => Some(Body::Empty),
             Some(content) => Some(Body::Text(content)),
@@ -148,6 +574,131 @@ fn create_with_header(
     }
 }
 
+/// Computes the size of a base64 encoded payload once decoded, without decoding it.
+pub(crate) fn base64_decoded_len(encoded: &str) -> usize {
+    let padding = encoded.bytes().rev().take_while(|b| *b == b'=').count();
+    (encoded.len() * 3 / 4).saturating_sub(padding)
+}
+
+/// Returns the canonical reason phrase of the given status code (e.g. "OK" for 200).
+fn reason_phrase(status_code: i64) -> Option<&'static str> {
+    http::StatusCode::from_u16(status_code as u16).ok()
+        .and_then(|status| status.canonical_reason())
+}
+
+/// Convenience methods for [aws_lambda_events::event::alb::AlbTargetGroupResponse].
+pub trait ResponseExt {
+    /// Renders the response as a raw HTTP/1.1 message (status line, headers and body),
+    /// allowing developers to eyeball what the client will receive.
+    fn to_http_string(&self) -> String;
+
+    /// Inserts a header into the response, replacing any previous value with the
+    /// same name. It takes into account whether `multi_header` is enabled.
+    ///
+    /// ```
+    /// use mu_alb::{response, ResponseExt};
+    ///
+    /// let res = response::create_as_plain_text(200, Some("hello".to_string()))
+    ///     .with_header("Cache-Control", "no-cache")
+    ///     .with_header("ETag", "\"33a64df5\"");
+    /// ```
+    fn with_header(self, name: &str, value: &str) -> Response;
+
+    /// Inserts all the given headers into the response. See [ResponseExt::with_header].
+    fn with_headers(self, headers: headers::HeaderMap) -> Response;
+
+    /// Sets the `Content-Length` header to the byte length of the body, as received
+    /// by the client (i.e. after base64 decoding). Chunked responses are left untouched.
+    fn with_content_length(self) -> Response;
+
+    /// The body as text. `None` for empty bodies, or bodies that aren't valid UTF-8.
+    fn body_text(&self) -> Option<&str>;
+
+    /// The body as raw bytes, decoding base64 encoded text bodies. `None` for empty bodies.
+    fn body_bytes(&self) -> Option<Cow<'_, [u8]>>;
+}
+
+impl ResponseExt for Response {
+    fn with_header(mut self, name: &str, value: &str) -> Response {
+        headers::from_response_mut(&mut self).insert(
+            HeaderName::from_str(name).unwrap(),
+            HeaderValue::from_str(value).unwrap()
+        );
+        self
+    }
+
+    fn with_content_length(self) -> Response {
+        let is_chunked = headers::from_response(&self).get_all(http::header::TRANSFER_ENCODING)
+            .iter()
+            .any(|value| value.to_str().map(|v| v.to_ascii_lowercase().contains("chunked")).unwrap_or(false));
+        if is_chunked {
+            return self
+        }
+
+        let length = match &self.body {
+            Some(Body::Text(text)) if self.is_base64_encoded => base64_decoded_len(text),
+            Some(Body::Text(text)) => text.len(),
+            Some(Body::Binary(bytes)) => bytes.len(),
+            Some(Body::Empty) | None => 0,
+        };
+        self.with_header("Content-Length", &length.to_string())
+    }
+
+    fn body_text(&self) -> Option<&str> {
+        match &self.body {
+            Some(Body::Text(text)) if !self.is_base64_encoded => Some(text),
+            Some(Body::Binary(bytes)) => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        }
+    }
+
+    fn body_bytes(&self) -> Option<Cow<'_, [u8]>> {
+        match &self.body {
+            Some(Body::Text(text)) if self.is_base64_encoded => base64::decode(text).ok().map(Cow::Owned),
+            Some(Body::Text(text)) => Some(Cow::Borrowed(text.as_bytes())),
+            Some(Body::Binary(bytes)) => Some(Cow::Borrowed(bytes)),
+            Some(Body::Empty) | None => None,
+        }
+    }
+
+    #[cfg(not(feature = "multi_header"))]
+    fn with_headers(self, headers: headers::HeaderMap) -> Response {
+        headers.iter().fold(self, |response, (name, value)| response.with_header(name, value))
+    }
+
+    #[cfg(feature = "multi_header")]
+    fn with_headers(mut self, headers: headers::HeaderMap) -> Response {
+        let response_headers = headers::from_response_mut(&mut self);
+        for (name, values) in headers.iter() {
+            let name = HeaderName::from_str(name).unwrap();
+            response_headers.remove(&name);
+            for value in values {
+                response_headers.append(name.clone(), HeaderValue::from_str(value).unwrap());
+            }
+        }
+        self
+    }
+
+    fn to_http_string(&self) -> String {
+        let reason = reason_phrase(self.status_code).unwrap_or("");
+
+        let mut message = format!("HTTP/1.1 {} {}\r\n", self.status_code, reason);
+        for (name, value) in self.headers.iter().chain(self.multi_value_headers.iter()) {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            message.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        message.push_str("\r\n");
+
+        match &self.body {
+            Some(Body::Text(text)) => message.push_str(text),
+            Some(Body::Binary(bytes)) => message.push_str(&String::from_utf8_lossy(bytes)),
+            Some(Body::Empty) | None => {}
+        }
+
+        message
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -192,4 +743,409 @@ mod tests {
         }
     }
 
-}
\ No newline at end of file
+    mod status_description {
+        use crate::response;
+
+        #[test]
+        fn should_keep_the_legacy_description_by_default() {
+            let alb_response = response::create(200, None, response::headers::HeaderMap::new());
+            assert_eq!(Some("200 Response".to_string()), alb_response.status_description)
+        }
+
+        #[test]
+        fn should_use_canonical_reason_phrases() {
+            let ok = response::create_with_description(
+                200, None, response::headers::HeaderMap::new(), None);
+            assert_eq!(Some("OK".to_string()), ok.status_description);
+
+            let not_found = response::create_with_description(
+                404, None, response::headers::HeaderMap::new(), None);
+            assert_eq!(Some("Not Found".to_string()), not_found.status_description);
+        }
+
+        #[test]
+        fn should_allow_custom_descriptions() {
+            let alb_response = response::create_with_description(
+                200, None, response::headers::HeaderMap::new(), Some("All Good".to_string()));
+            assert_eq!(Some("All Good".to_string()), alb_response.status_description)
+        }
+    }
+
+    mod extra_headers {
+        use crate::response::{self, headers, ResponseExt};
+
+        #[test]
+        fn should_chain_extra_headers() {
+            let alb_response = response::create_json_from_obj(200, &42)
+                .with_header("ETag", "\"42\"")
+                .with_header("Cache-Control", "no-cache");
+
+            let response_headers = headers::from_response(&alb_response);
+            assert_eq!("\"42\"", response_headers.get("ETag").unwrap());
+            assert_eq!("no-cache", response_headers.get("Cache-Control").unwrap());
+            assert_eq!("application/json", response_headers.get(headers::CONTENT_TYPE).unwrap());
+        }
+
+        #[test]
+        fn should_replace_existing_headers() {
+            let alb_response = response::create_as_plain_text(200, None)
+                .with_header("Content-Type", "text/html");
+
+            let response_headers = headers::from_response(&alb_response);
+            assert_eq!(1, response_headers.get_all(headers::CONTENT_TYPE).iter().count());
+            assert_eq!("text/html", response_headers.get(headers::CONTENT_TYPE).unwrap());
+        }
+
+        #[test]
+        #[cfg(not(feature = "multi_header"))]
+        fn should_insert_multiple_headers() {
+            let mut extra = headers::HeaderMap::new();
+            extra.insert("ETag".to_string(), "\"42\"".to_string());
+            extra.insert("Cache-Control".to_string(), "no-cache".to_string());
+
+            let alb_response = response::no_content().with_headers(extra);
+            assert_eq!(2, alb_response.headers.len());
+            assert!(alb_response.multi_value_headers.is_empty());
+        }
+
+        #[test]
+        #[cfg(feature = "multi_header")]
+        fn should_insert_multiple_headers() {
+            let mut extra = headers::HeaderMap::new();
+            extra.insert("ETag".to_string(), vec!["\"42\"".to_string()]);
+            extra.insert("Cache-Control".to_string(), vec!["no-cache".to_string(), "no-store".to_string()]);
+
+            let alb_response = response::no_content().with_headers(extra);
+            assert_eq!(3, alb_response.multi_value_headers.len());
+            assert!(alb_response.headers.is_empty());
+        }
+    }
+
+    mod content_length {
+        use crate::response::{self, headers, ResponseExt};
+
+        #[test]
+        fn should_set_the_byte_length_of_multibyte_bodies() {
+            let alb_response = response::create_with_content_length(
+                200, Some("héllo wörld".to_string()), response::headers::HeaderMap::new());
+
+            assert_eq!("13", headers::from_response(&alb_response).get("Content-Length").unwrap());
+        }
+
+        #[test]
+        fn should_set_the_decoded_length_of_base64_bodies() {
+            let mut alb_response = response::create_as_plain_text(200, Some("aGVsbG8=".to_string()));
+            alb_response.is_base64_encoded = true;
+
+            let alb_response = alb_response.with_content_length();
+            assert_eq!("5", headers::from_response(&alb_response).get("Content-Length").unwrap());
+        }
+
+        #[test]
+        fn should_skip_chunked_responses() {
+            let alb_response = response::create_as_plain_text(200, Some("hello".to_string()))
+                .with_header("Transfer-Encoding", "chunked")
+                .with_content_length();
+
+            assert_eq!(None, headers::from_response(&alb_response).get("Content-Length"));
+        }
+    }
+
+    mod body_accessors {
+        use aws_lambda_events::encodings::Body;
+
+        use crate::response::{self, ResponseExt};
+
+        #[test]
+        fn should_read_text_bodies() {
+            let alb_response = response::create_as_plain_text(200, Some("héllo".to_string()));
+
+            assert_eq!(Some("héllo"), alb_response.body_text());
+            assert_eq!(Some("héllo".as_bytes()), alb_response.body_bytes().as_deref());
+        }
+
+        #[test]
+        fn should_decode_base64_encoded_text_bodies() {
+            let mut alb_response = response::create_as_plain_text(200, Some("aGVsbG8=".to_string()));
+            alb_response.is_base64_encoded = true;
+
+            assert_eq!(None, alb_response.body_text());
+            assert_eq!(Some("hello".as_bytes()), alb_response.body_bytes().as_deref());
+        }
+
+        #[test]
+        fn should_read_binary_bodies() {
+            let mut alb_response = response::create_as_plain_text(200, None);
+            alb_response.body = Some(Body::Binary(vec![104, 105]));
+
+            assert_eq!(Some("hi"), alb_response.body_text());
+            assert_eq!(Some(&[104u8, 105][..]), alb_response.body_bytes().as_deref());
+
+            alb_response.body = Some(Body::Binary(vec![0xff, 0xfe]));
+            assert_eq!(None, alb_response.body_text());
+            assert_eq!(Some(&[0xffu8, 0xfe][..]), alb_response.body_bytes().as_deref());
+        }
+
+        #[test]
+        fn should_read_empty_bodies() {
+            let alb_response = response::no_content();
+
+            assert_eq!(Some(Body::Empty), alb_response.body);
+            assert_eq!(None, alb_response.body_text());
+            assert_eq!(None, alb_response.body_bytes());
+        }
+    }
+
+    mod empty_responses {
+        use std::time::Duration;
+
+        use crate::response;
+
+        #[test]
+        fn should_create_no_content_responses_without_content_type() {
+            let alb_response = response::no_content();
+
+            assert_eq!(204, alb_response.status_code);
+            assert!(alb_response.headers.is_empty());
+            assert!(alb_response.multi_value_headers.is_empty());
+            assert_eq!(None, response::headers::from_response(&alb_response).get(response::headers::CONTENT_TYPE))
+        }
+
+        #[test]
+        fn should_create_not_modified_responses_without_content_type() {
+            let alb_response = response::not_modified();
+
+            assert_eq!(304, alb_response.status_code);
+            assert!(alb_response.headers.is_empty());
+            assert!(alb_response.multi_value_headers.is_empty());
+            assert_eq!(None, response::headers::from_response(&alb_response).get(response::headers::CONTENT_TYPE))
+        }
+
+        #[test]
+        fn should_point_created_responses_at_the_resource() {
+            let alb_response = response::created("/users/42");
+
+            assert_eq!(201, alb_response.status_code);
+            assert_eq!("/users/42", response::headers::from_response(&alb_response)["Location"]);
+            assert_eq!(None, response::headers::from_response(&alb_response).get(response::headers::CONTENT_TYPE))
+        }
+
+        #[test]
+        fn should_point_accepted_responses_at_the_job_status() {
+            let alb_response = response::accepted("/jobs/7/status");
+
+            assert_eq!(202, alb_response.status_code);
+            assert_eq!("/jobs/7/status", response::headers::from_response(&alb_response)["Location"]);
+            assert_eq!(None, response::headers::from_response(&alb_response).get(response::headers::CONTENT_TYPE))
+        }
+
+        #[test]
+        fn should_tell_throttled_clients_when_to_retry() {
+            let alb_response = response::too_many_requests(Duration::from_secs(30));
+
+            assert_eq!(429, alb_response.status_code);
+            assert_eq!("30", response::headers::from_response(&alb_response)["Retry-After"]);
+
+            let alb_response = response::too_many_requests(Duration::from_millis(1500));
+            assert_eq!("2", response::headers::from_response(&alb_response)["Retry-After"]);
+        }
+    }
+
+    mod pretty_json {
+        use serde::Serialize;
+
+        use crate::response::to_json_with;
+
+        #[derive(Serialize)]
+        struct User {
+            name: &'static str,
+        }
+
+        #[test]
+        fn should_switch_between_compact_and_pretty_json() {
+            let user = User { name: "John" };
+
+            assert_eq!(r#"{"name":"John"}"#, to_json_with(&user, false).unwrap());
+            assert_eq!("{\n  \"name\": \"John\"\n}", to_json_with(&user, true).unwrap());
+        }
+    }
+
+    mod entity_tags {
+        use aws_lambda_events::encodings::Body;
+        use serde::Serialize;
+
+        use crate::response;
+        use crate::testing::create_request;
+
+        #[derive(Serialize)]
+        struct User {
+            name: &'static str,
+        }
+
+        fn etag_of(alb_response: &aws_lambda_events::event::alb::AlbTargetGroupResponse) -> String {
+            response::headers::from_response(alb_response).get("ETag").unwrap()
+                .to_str().unwrap().to_string()
+        }
+
+        #[test]
+        fn should_tag_json_responses() {
+            let req = create_request(None, &[]);
+            let alb_response = response::json_with_etag(200, &User { name: "John" }, &req);
+
+            assert_eq!(200, alb_response.status_code);
+            assert_eq!(Some(Body::Text(r#"{"name":"John"}"#.to_string())), alb_response.body);
+            assert!(etag_of(&alb_response).starts_with("W/\""));
+        }
+
+        #[test]
+        fn should_return_not_modified_when_if_none_match_matches() {
+            let req = create_request(None, &[]);
+            let etag = etag_of(&response::json_with_etag(200, &User { name: "John" }, &req));
+
+            let if_none_match = format!("\"other\", {}", etag);
+            let req = create_request(None, &[("If-None-Match", &if_none_match)]);
+            let alb_response = response::json_with_etag(200, &User { name: "John" }, &req);
+
+            assert_eq!(304, alb_response.status_code);
+            assert_eq!(Some(Body::Empty), alb_response.body);
+            assert_eq!(etag, etag_of(&alb_response));
+        }
+
+        #[test]
+        fn should_return_the_body_when_if_none_match_differs() {
+            let req = create_request(None, &[("If-None-Match", "W/\"stale\"")]);
+            let alb_response = response::json_with_etag(200, &User { name: "John" }, &req);
+
+            assert_eq!(200, alb_response.status_code);
+        }
+    }
+
+    mod cache_control {
+        use std::time::Duration;
+
+        use crate::response;
+
+        fn cache_control_of(alb_response: &aws_lambda_events::event::alb::AlbTargetGroupResponse) -> &str {
+            response::headers::from_response(alb_response).get("Cache-Control").unwrap()
+                .to_str().unwrap()
+        }
+
+        #[test]
+        fn should_make_responses_cacheable() {
+            let alb_response = response::json_cached(200, &vec!["dark-mode"], Duration::from_secs(300));
+
+            assert_eq!(200, alb_response.status_code);
+            assert_eq!("public, max-age=300", cache_control_of(&alb_response));
+            assert!(response::headers::from_response(&alb_response).contains_key("ETag"));
+        }
+
+        #[test]
+        fn should_forbid_caches_from_storing_responses() {
+            let alb_response = response::json_no_store(200, &vec!["secret"]);
+
+            assert_eq!(200, alb_response.status_code);
+            assert_eq!("no-store", cache_control_of(&alb_response));
+        }
+    }
+
+    mod typed_status {
+        use aws_lambda_events::encodings::Body;
+        use http::StatusCode;
+        use serde::Serialize;
+
+        use crate::response::{self, content_types, headers};
+
+        #[derive(Serialize)]
+        struct NotFound {
+            error: &'static str,
+        }
+
+        #[test]
+        fn should_build_json_responses_in_one_call() {
+            let alb_response = response::json(StatusCode::NOT_FOUND, &NotFound { error: "user not found" });
+
+            assert_eq!(404, alb_response.status_code);
+            assert_eq!(Some(Body::Text(r#"{"error":"user not found"}"#.to_string())), alb_response.body);
+            assert_eq!(content_types::JSON, headers::from_response(&alb_response).get(headers::CONTENT_TYPE).unwrap());
+        }
+
+        #[test]
+        fn should_build_empty_responses() {
+            let alb_response = response::empty(StatusCode::GONE);
+
+            assert_eq!(410, alb_response.status_code);
+            assert_eq!(Some(Body::Empty), alb_response.body);
+            assert!(!headers::from_response(&alb_response).contains_key(headers::CONTENT_TYPE));
+        }
+    }
+
+    mod unified_headers {
+        use crate::response::{self, headers::{self, Headers}};
+
+        // compiled, and expected to behave the same, with and without `multi_header`
+        fn create_headers() -> Headers {
+            let mut headers = Headers::new();
+            headers.insert("Content-Type", "text/csv");
+            headers.insert("Content-Type", "text/plain");
+            headers.append("Set-Cookie", "theme=dark");
+            headers.append("Set-Cookie", "lang=en");
+            headers
+        }
+
+        #[test]
+        fn should_read_the_values_of_headers() {
+            let headers = create_headers();
+
+            assert_eq!(Some("text/plain"), headers.get("Content-Type"));
+            assert_eq!(vec!["text/plain"], headers.get_all("Content-Type"));
+            assert_eq!(Some("lang=en"), headers.get_all("Set-Cookie").last().copied());
+            assert_eq!(None, headers.get("ETag"));
+            assert!(headers.get_all("ETag").is_empty());
+        }
+
+        #[test]
+        fn should_build_responses_from_headers() {
+            let alb_response = response::create(200, None, create_headers().into());
+
+            let sent = headers::from_response(&alb_response);
+            assert_eq!("text/plain", sent.get("Content-Type").unwrap());
+            assert_eq!("lang=en", sent.get_all("Set-Cookie").iter().next_back().unwrap());
+        }
+    }
+
+    mod prebuilt_json {
+        use aws_lambda_events::encodings::Body;
+
+        use crate::response::{self, content_types, headers};
+
+        #[test]
+        fn should_send_the_json_verbatim() {
+            let json = r#"{ "name" :"John",  "tags":[] }"#.to_string();
+
+            let alb_response = response::create_prebuilt_json(201, json.clone());
+
+            assert_eq!(201, alb_response.status_code);
+            assert_eq!(Some(Body::Text(json)), alb_response.body);
+            assert_eq!(content_types::JSON, headers::from_response(&alb_response).get(headers::CONTENT_TYPE).unwrap());
+        }
+    }
+
+    mod raw_http_rendering {
+        use crate::response::{self, ResponseExt};
+
+        #[test]
+        fn should_render_response_as_raw_http_message() {
+            let alb_response = response::create_as_plain_text(404, Some("Not here".to_string()));
+
+            let expected = "HTTP/1.1 404 Not Found\r\ncontent-type: text/plain\r\n\r\nNot here";
+            assert_eq!(expected, alb_response.to_http_string())
+        }
+
+        #[test]
+        fn should_render_empty_bodies() {
+            let alb_response = response::create(204, None, response::headers::HeaderMap::new());
+
+            assert_eq!("HTTP/1.1 204 No Content\r\n\r\n", alb_response.to_http_string())
+        }
+    }
+}
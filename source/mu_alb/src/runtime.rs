@@ -1,13 +1,20 @@
+use std::fmt;
 use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use aws_lambda_events::encodings::Body;
 use aws_lambda_events::event::alb::{
     AlbTargetGroupRequest, AlbTargetGroupResponse
 };
+use http::{HeaderValue, Method};
 
 use mu_runtime::{Context, Error};
 
 use crate::deserializer::AlbDeserialize;
-use crate::{response, AlbSerialize};
+use crate::error::ErrorFormat;
+use crate::response::{headers, SecurityHeaders};
+use crate::{response, AlbSerialize, Responder};
 
 /// Listen to ALB events. Unlike [mu_runtime::listen_events], this method
 /// expects you to respect the AWS Application Load Balancer contract by returning
@@ -39,16 +46,394 @@ where
     F: Fn(A) -> Fut + Sync + Send,
     Fut: Future<Output = B> + Send,
     A: AlbDeserialize<A> + Send,
-    B: AlbSerialize,
+    B: Responder,
 {
+    listen_events_with(Options::default(), handler).await
+}
+
+/// The maximum size of the response payload accepted by the Application Load Balancer.
+pub const ALB_MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+
+/// Maps request deserialization failures into the response sent to the client.
+pub type DeserializeErrorMapper = Arc<dyn Fn(Error) -> AlbTargetGroupResponse + Send + Sync>;
+
+/// Decides whether a request is allowed to reach the handler.
+pub type RequestPredicate = Arc<dyn Fn(&AlbTargetGroupRequest) -> bool + Send + Sync>;
+
+/// Transforms the response right before it is sent to the load balancer.
+pub type ResponseMapper = Arc<dyn Fn(AlbTargetGroupResponse, &Context) -> AlbTargetGroupResponse + Send + Sync>;
+
+/// Options to customise how ALB events are handled by [listen_events_with].
+#[derive(Clone)]
+pub struct Options {
+    /// The maximum size (in bytes) of the decoded request body. Requests
+    /// exceeding it are rejected with `413 Payload Too Large` before being
+    /// deserialized, never reaching the handler.
+    pub max_body_bytes: Option<usize>,
+    /// Requests whose decoded body exceeds this size (in bytes) are still processed,
+    /// but logged with a `WARN` entry stating their request id and size. It brings
+    /// growing payloads to light before they risk exhausting the function memory.
+    /// Disabled when `None`.
+    pub warn_body_bytes: Option<usize>,
+    /// The maximum time the handler is given to process a request, bounded by
+    /// the invocation deadline. Requests exceeding it are answered with
+    /// `504 Gateway Timeout`.
+    pub timeout: Option<Duration>,
+    /// The maximum size (in bytes) of the response body, as sent to the load balancer.
+    /// Responses exceeding it are replaced by a `500 Internal Server Error` stating
+    /// so, rather than being rejected by the platform with an opaque error.
+    /// Defaults to [ALB_MAX_RESPONSE_BYTES].
+    pub max_response_bytes: Option<usize>,
+    /// Defines the response sent when the request can't be deserialized, allowing
+    /// endpoints to follow their own error contract (e.g. `application/problem+json`).
+    /// Defaults to a `400 Bad Request` with a plain text body.
+    pub on_deserialize_error: Option<DeserializeErrorMapper>,
+    /// A path prefix (e.g. `/api/v1`) removed from the request path before it
+    /// reaches the handler, decoupling its routing from the load balancer rules.
+    /// Paths not starting with it are left untouched.
+    pub strip_prefix: Option<String>,
+    /// Hardening headers added to every response, unless already set by the handler.
+    /// Disabled when `None`.
+    pub security_headers: Option<SecurityHeaders>,
+    /// Requests failing this predicate (e.g. lacking a bearer token) are rejected with
+    /// `401 Unauthorized` before being deserialized, never reaching the handler.
+    pub authorize: Option<RequestPredicate>,
+    /// A final transformation of every response (e.g. stripping debug headers), applied
+    /// after the handler result is serialized and every other option has taken effect.
+    pub map_response: Option<ResponseMapper>,
+    /// How failures lacking their own representation (i.e. anything but an [ApiError](crate::ApiError)
+    /// or a [Problem](crate::Problem)) are rendered. Defaults to [ErrorFormat::Text].
+    pub error_format: ErrorFormat,
+}
+
+impl Options {
+
+    /// Defines how deserialization failures are reported. See [Options::on_deserialize_error].
+    pub fn on_deserialize_error<M>(mut self, mapper: M) -> Self
+    where
+        M: Fn(Error) -> AlbTargetGroupResponse + Send + Sync + 'static,
+    {
+        self.on_deserialize_error = Some(Arc::new(mapper));
+        self
+    }
+
+    /// Rejects requests failing the given predicate. See [Options::authorize].
+    pub fn authorize<P>(mut self, predicate: P) -> Self
+    where
+        P: Fn(&AlbTargetGroupRequest) -> bool + Send + Sync + 'static,
+    {
+        self.authorize = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Rejects requests lacking the given header (e.g. `Authorization`). See [Options::authorize].
+    pub fn require_header(self, name: &str) -> Self {
+        let name = name.to_string();
+        self.authorize(move |req| headers::from_request(req).contains_key(name.as_str()))
+    }
+
+    /// Transforms every response before it is sent. See [Options::map_response].
+    pub fn map_response<M>(mut self, mapper: M) -> Self
+    where
+        M: Fn(AlbTargetGroupResponse, &Context) -> AlbTargetGroupResponse + Send + Sync + 'static,
+    {
+        self.map_response = Some(Arc::new(mapper));
+        self
+    }
+
+    /// Removes the given prefix from request paths. See [Options::strip_prefix].
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.strip_prefix = Some(prefix.to_string());
+        self
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            max_body_bytes: None,
+            warn_body_bytes: None,
+            timeout: None,
+            max_response_bytes: Some(ALB_MAX_RESPONSE_BYTES),
+            on_deserialize_error: None,
+            strip_prefix: None,
+            security_headers: None,
+            authorize: None,
+            map_response: None,
+            error_format: ErrorFormat::Text,
+        }
+    }
+}
+
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Options")
+            .field("max_body_bytes", &self.max_body_bytes)
+            .field("warn_body_bytes", &self.warn_body_bytes)
+            .field("timeout", &self.timeout)
+            .field("max_response_bytes", &self.max_response_bytes)
+            .field("on_deserialize_error", &self.on_deserialize_error.as_ref().map(|_| "Fn(Error)"))
+            .field("strip_prefix", &self.strip_prefix)
+            .field("security_headers", &self.security_headers)
+            .field("authorize", &self.authorize.as_ref().map(|_| "Fn(&AlbTargetGroupRequest)"))
+            .field("map_response", &self.map_response.as_ref().map(|_| "Fn(AlbTargetGroupResponse, &Context)"))
+            .field("error_format", &self.error_format)
+            .finish()
+    }
+}
+
+/// Listen to ALB events, as [listen_events] does, using the given [Options].
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use aws_lambda_events::event::alb::{
+///     AlbTargetGroupRequest,
+///     AlbTargetGroupResponse
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   let options = Options { max_body_bytes: Some(64 * 1024), ..Default::default() };
+///   listen_events_with(options, |req: AlbTargetGroupRequest| {
+///     say_hello()
+///   }).await
+/// }
+///
+/// async fn say_hello() -> AlbTargetGroupResponse {
+///  response::create_as_plain_text(
+///    200, Some("Hello World".to_string()))
+/// }
+/// ```
+pub async fn listen_events_with<F, Fut, A, B>(options: Options, handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    mu_runtime::listen_events(
+        |req, ctx| handle_rpc_req(&options, &handler, req, ctx)
+    ).await
+}
+
+/// Listen to ALB events, as [listen_events] does, answering with `504 Gateway Timeout`
+/// whenever the `handler` takes longer than `timeout` (or the remaining time of the
+/// invocation, whichever comes first). See [Options::timeout].
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use mu_alb::*;
+/// use aws_lambda_events::event::alb::{
+///     AlbTargetGroupRequest,
+///     AlbTargetGroupResponse
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   listen_events_with_timeout(Duration::from_secs(2), |req: AlbTargetGroupRequest| {
+///     say_hello()
+///   }).await
+/// }
+///
+/// async fn say_hello() -> AlbTargetGroupResponse {
+///  response::create_as_plain_text(
+///    200, Some("Hello World".to_string()))
+/// }
+/// ```
+pub async fn listen_events_with_timeout<F, Fut, A, B>(timeout: Duration, handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    let options = Options { timeout: Some(timeout), ..Default::default() };
+    listen_events_with(options, handler).await
+}
+
+/// Listen to ALB events, as [listen_events] does, adding the given [SecurityHeaders]
+/// to every response. See [Options::security_headers].
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use mu_alb::response::SecurityHeaders;
+/// use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   let security_headers = SecurityHeaders { hsts: Some("max-age=63072000".to_string()) };
+///   listen_events_secure(security_headers, |req: AlbTargetGroupRequest| {
+///     say_hello()
+///   }).await
+/// }
+///
+/// async fn say_hello() -> Result<String, Error> {
+///   Ok("Hello World".to_string())
+/// }
+/// ```
+pub async fn listen_events_secure<F, Fut, A, B>(
+    security_headers: SecurityHeaders, handler: F
+) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    let options = Options { security_headers: Some(security_headers), ..Default::default() };
+    listen_events_with(options, handler).await
+}
+
+/// Listen to ALB events, as [listen_events] does, but also handing the invocation
+/// [Context] and the original request to the `handler`. This allows one to read
+/// request metadata (e.g. auth tokens, correlation ids) alongside the deserialized payload.
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Greeting { name: String }
+///
+/// impl RpcRequest for Greeting {}
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   listen_events_full(|greeting: Greeting, ctx: Context, req: AlbTargetGroupRequest| {
+///     say_hello(greeting, req)
+///   }).await
+/// }
+///
+/// async fn say_hello(greeting: Greeting, req: AlbTargetGroupRequest) -> Result<String, Error> {
+///   let agent = req.headers.get("User-Agent").and_then(|h| h.to_str().ok()).unwrap_or("unknown");
+///   Ok(format!("Hello {}, from {}", greeting.name, agent))
+/// }
+/// ```
+pub async fn listen_events_full<F, Fut, A, B>(handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A, Context, AlbTargetGroupRequest) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    listen_events_full_with(Options::default(), handler).await
+}
+
+/// Listen to ALB events, as [listen_events_full] does, using the given [Options].
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Greeting { name: String }
+///
+/// impl RpcRequest for Greeting {}
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   let options = Options::default().require_header("Authorization");
+///   listen_events_full_with(options, |greeting: Greeting, ctx: Context, req: AlbTargetGroupRequest| {
+///     say_hello(greeting)
+///   }).await
+/// }
+///
+/// async fn say_hello(greeting: Greeting) -> Result<String, Error> {
+///   Ok(format!("Hello {}", greeting.name))
+/// }
+/// ```
+pub async fn listen_events_full_with<F, Fut, A, B>(options: Options, handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A, Context, AlbTargetGroupRequest) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    mu_runtime::listen_events(
+        |req, ctx| handle_full_rpc_req(&options, &handler, req, ctx)
+    ).await
+}
+
+/// Listen to ALB events, as [listen_events] does, emitting a one-line access log
+/// entry (method, path, status and handler latency) for each request. The entry
+/// is formatted as JSON when the `json_access_log` feature is enabled.
+///
+/// ```no_run
+/// use mu_alb::*;
+/// use aws_lambda_events::event::alb::{
+///     AlbTargetGroupRequest,
+///     AlbTargetGroupResponse
+/// };
+///
+/// #[tokio::main]
+/// async fn main() -> RuntimeResult {
+///   listen_events_logged(|req: AlbTargetGroupRequest| {
+///     say_hello()
+///   }).await
+/// }
+///
+/// async fn say_hello() -> AlbTargetGroupResponse {
+///  response::create_as_plain_text(
+///    200, Some("Hello World".to_string()))
+/// }
+/// ```
+pub async fn listen_events_logged<F, Fut, A, B>(handler: F) -> mu_runtime::RuntimeResult
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    let options = Options::default();
     mu_runtime::listen_events(
-        |req, ctx| handle_rpc_req(&handler, req, ctx)
+        |req, ctx| handle_logged_rpc_req(&options, &handler, req, ctx)
     ).await
 }
 
+/// Handle the RPC request, logging its outcome.
+#[inline]
+async fn handle_logged_rpc_req<F, Fut, A, B>(
+    options: &Options,
+    func: &F,
+    req: AlbTargetGroupRequest,
+    ctx: Context,
+) -> Result<AlbTargetGroupResponse, Error>
+where
+    F: Fn(A) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    let method = req.http_method.clone();
+    let path = req.path.clone().unwrap_or_default();
+    let started_at = Instant::now();
+
+    let response = handle_rpc_req(options, func, req, ctx).await?;
+    log::info!("{}", format_access_log(&method, &path, response.status_code, started_at.elapsed()));
+    Ok(response)
+}
+
+#[cfg(not(feature = "json_access_log"))]
+fn format_access_log(method: &Method, path: &str, status: i64, latency: Duration) -> String {
+    format!("{} {} {} {}ms", method, path, status, latency.as_millis())
+}
+
+#[cfg(feature = "json_access_log")]
+fn format_access_log(method: &Method, path: &str, status: i64, latency: Duration) -> String {
+    serde_json::json!({
+        "method": method.as_str(),
+        "path": path,
+        "status": status,
+        "latency_ms": latency.as_millis() as u64,
+    }).to_string()
+}
+
 /// Handle the RPC request.
 #[inline]
-async fn handle_rpc_req<F, Fut, A, B>(
+pub(crate) async fn handle_rpc_req<F, Fut, A, B>(
+    options: &Options,
     func: &F,
     req: AlbTargetGroupRequest,
     ctx: Context,
@@ -57,13 +442,730 @@ where
     F: Fn(A) -> Fut + Sync + Send,
     Fut: Future<Output = B> + Send,
     A: AlbDeserialize<A> + Send,
-    B: AlbSerialize,
+    B: Responder,
+{
+    let invoke = |req, ctx: Context| A::from_alb_request(req, ctx).map(func);
+    handle_with::<_, _, A, _>(options, invoke, req, ctx).await
+}
+
+/// Handle the RPC request, keeping the original request around for the handler.
+#[inline]
+async fn handle_full_rpc_req<F, Fut, A, B>(
+    options: &Options,
+    func: &F,
+    req: AlbTargetGroupRequest,
+    ctx: Context,
+) -> Result<AlbTargetGroupResponse, Error>
+where
+    F: Fn(A, Context, AlbTargetGroupRequest) -> Fut + Sync + Send,
+    Fut: Future<Output = B> + Send,
+    A: AlbDeserialize<A> + Send,
+    B: Responder,
+{
+    // deserialized from a borrowed request, handing it afterwards to the handler
+    let invoke = |req, ctx| A::from_alb_request_ref(&req, &ctx).map(|deserialized| (func)(deserialized, ctx, req));
+    handle_with::<_, _, A, _>(options, invoke, req, ctx).await
+}
+
+/// Deserializes the request into `A` and invokes the handler, both through `invoke`.
+/// Error responses are tagged with the request id, allowing clients to report it to support.
+#[inline]
+async fn handle_with<I, Fut, A, B>(
+    options: &Options,
+    invoke: I,
+    req: AlbTargetGroupRequest,
+    ctx: Context,
+) -> Result<AlbTargetGroupResponse, Error>
+where
+    I: FnOnce(AlbTargetGroupRequest, Context) -> Result<Fut, Error>,
+    Fut: Future<Output = B>,
+    A: AlbDeserialize<A>,
+    B: Responder,
+{
+    let request_id = ctx.request_id.clone();
+    let response_ctx = options.map_response.as_ref().map(|_| ctx.clone());
+    let mut response = match options.timeout {
+        None => process_rpc_req::<_, _, A, _>(options, invoke, req, ctx).await,
+        Some(timeout) => {
+            let processed = process_rpc_req::<_, _, A, _>(options, invoke, req, ctx.clone());
+            match mu_runtime::with_timeout(timeout, &ctx, processed).await {
+                Ok(response) => response,
+                Err(cause) => response::create_as_plain_text(
+                    504, Some(format!("Gateway Timeout: {}", cause))
+                ),
+            }
+        }
+    };
+
+    if let Some(max_response_bytes) = options.max_response_bytes {
+        let length = response_body_len(&response);
+        if length > max_response_bytes {
+            log::error!("Response of invocation {} has {} bytes, exceeding the limit of {} bytes",
+                        request_id, length, max_response_bytes);
+            response = response::create_as_plain_text(
+                500, Some(format!("Internal Server Error: response exceeds {} bytes", max_response_bytes))
+            );
+        }
+    }
+
+    if let Some(security_headers) = &options.security_headers {
+        security_headers.apply(&mut response);
+    }
+
+    if response.status_code >= 400 && !request_id.is_empty() {
+        if let Ok(value) = HeaderValue::from_str(&request_id) {
+            headers::from_response_mut(&mut response).insert(headers::REQUEST_ID, value);
+        }
+    }
+
+    if let (Some(map_response), Some(ctx)) = (&options.map_response, &response_ctx) {
+        response = (map_response)(response, ctx);
+    }
+
+    Ok(response)
+}
+
+#[inline]
+async fn process_rpc_req<I, Fut, A, B>(
+    options: &Options,
+    invoke: I,
+    req: AlbTargetGroupRequest,
+    ctx: Context,
+) -> AlbTargetGroupResponse
+where
+    I: FnOnce(AlbTargetGroupRequest, Context) -> Result<Fut, Error>,
+    Fut: Future<Output = B>,
+    A: AlbDeserialize<A>,
+    B: Responder,
 {
-    let result: Result<A, Error> = A::from_alb_request(req, ctx);
-    Ok(match result {
-        Ok(deserialized) => (func)(deserialized).await.to_alb_response(),
-        Err(cause) => response::create_as_plain_text(
+    if let Some(authorize) = &options.authorize {
+        if !(authorize)(&req) {
+            return response::create_as_plain_text(401, Some("Unauthorized".to_string()))
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    let req = {
+        use crate::compression::DecompressError;
+        match crate::compression::decompress_request(req, options.max_body_bytes) {
+            Ok(decompressed) => decompressed,
+            Err(DecompressError::Invalid(cause)) => return deserialization_failure(options, cause),
+            Err(cause @ DecompressError::TooLarge(_)) =>
+                return response::create_as_plain_text(413, Some(format!("{}", cause))),
+            Err(cause @ DecompressError::UnsupportedEncoding(_)) =>
+                return response::create_as_plain_text(415, Some(format!("{}", cause))),
+        }
+    };
+
+    if let Some(max_body_bytes) = options.max_body_bytes {
+        if decoded_body_len(&req) > max_body_bytes {
+            return response::create_as_plain_text(
+                413, Some(format!("Payload Too Large: limit is {} bytes", max_body_bytes))
+            )
+        }
+    }
+
+    if let Some(warn_body_bytes) = options.warn_body_bytes {
+        let body_len = decoded_body_len(&req);
+        if body_len > warn_body_bytes {
+            log::warn!("Request {} has a body of {} bytes, exceeding the warning threshold of {} bytes",
+                ctx.request_id, body_len, warn_body_bytes);
+        }
+    }
+
+    if let Some(expected) = A::CONTENT_TYPE {
+        if !has_content_type(&req, expected) {
+            return response::create_as_plain_text(
+                415, Some(format!("Unsupported Media Type: expected {}", expected))
+            )
+        }
+    }
+
+    if let Err(cause) = A::check_media_type(&req) {
+        return cause.to_alb_response_with(&ctx)
+    }
+
+    let mut req = req;
+    if let Some(prefix) = &options.strip_prefix {
+        req.path = req.path.map(|path| strip_path_prefix(path, prefix));
+    }
+
+    let response_ctx = ctx.clone();
+    match invoke(req, ctx) {
+        Ok(handled) => handled.await.into_alb_response_formatted(&response_ctx, options.error_format),
+        Err(cause) => deserialization_failure(options, cause),
+    }
+}
+
+/// Reports a request that couldn't be deserialized.
+fn deserialization_failure(options: &Options, cause: Error) -> AlbTargetGroupResponse {
+    match &options.on_deserialize_error {
+        Some(mapper) => (mapper)(cause),
+        None => response::create_as_plain_text(
             400, Some(format!("Bad Request {}", cause))
         ),
-    })
+    }
+}
+
+/// Computes the size of the request body once decoded, without decoding it.
+/// Base64 encoded bodies are ~33% larger than their decoded counterparts.
+fn decoded_body_len(req: &AlbTargetGroupRequest) -> usize {
+    let body = match &req.body {
+        None => return 0,
+        Some(body) => body,
+    };
+
+    match req.is_base64_encoded {
+        true => response::base64_decoded_len(body),
+        false => body.len(),
+    }
+}
+
+/// Removes the `prefix` from the `path`, as long as it ends at a segment boundary
+/// (i.e. `/api` is stripped from `/api/users`, but not from `/apis`).
+fn strip_path_prefix(path: String, prefix: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    match path.strip_prefix(prefix) {
+        Some("") => "/".to_string(),
+        Some(rest) if rest.starts_with('/') => rest.to_string(),
+        _ => path,
+    }
+}
+
+/// Computes the size of the response body, as sent to the load balancer.
+fn response_body_len(response: &AlbTargetGroupResponse) -> usize {
+    match &response.body {
+        Some(Body::Text(text)) => text.len(),
+        Some(Body::Binary(bytes)) => bytes.len(),
+        Some(Body::Empty) | None => 0,
+    }
+}
+
+/// Checks whether the request `Content-Type` matches the expected media type,
+/// disregarding parameters like `charset`.
+fn has_content_type(req: &AlbTargetGroupRequest, expected: &str) -> bool {
+    headers::media_type(req)
+        .map(|media_type| media_type.eq_ignore_ascii_case(expected))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use aws_lambda_events::encodings::Body;
+    use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupResponse};
+    use serde::{Deserialize, Serialize};
+
+    use mu_runtime::{Context, Error};
+
+    use crate::deserializer::AlbDeserialize;
+    use crate::response::{self, content_types, headers, SecurityHeaders};
+    use crate::runtime::{
+        decoded_body_len, handle_full_rpc_req, handle_logged_rpc_req, handle_rpc_req, strip_path_prefix, Options
+    };
+    use crate::testing::{self, create_context, create_request};
+    use crate::{ApiError, ErrorFormat, ResponseExt, RpcRequest};
+
+    #[derive(Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    impl RpcRequest for Greeting {
+        const CONTENT_TYPE: Option<&'static str> = Some(content_types::JSON);
+    }
+
+    async fn greet(greeting: Greeting) -> AlbTargetGroupResponse {
+        response::create_as_plain_text(200, Some(format!("Hello, {}", greeting.name)))
+    }
+
+    async fn fail_greeting(_: Greeting) -> Result<String, ApiError> {
+        Err(ApiError::internal("Unable to greet"))
+    }
+
+    async fn greet_slowly(greeting: Greeting) -> AlbTargetGroupResponse {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        greet(greeting).await
+    }
+
+    #[tokio::test]
+    async fn should_answer_with_gateway_timeout_when_the_override_elapses() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let mut ctx = create_context("0000-0001");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        ctx.deadline = (now + Duration::from_secs(30)).as_millis() as u64;
+        let options = Options { timeout: Some(Duration::from_millis(50)), ..Default::default() };
+
+        let started_at = Instant::now();
+        let response = handle_rpc_req(&options, &greet_slowly, req, ctx).await.unwrap();
+        assert!(started_at.elapsed() < Duration::from_secs(1));
+        assert_eq!(504, response.status_code);
+        assert_eq!(
+            Some(Body::Text("Gateway Timeout: Handler timed out after 50ms".to_string())),
+            response.body
+        );
+        assert_eq!("0000-0001", headers::from_response(&response).get(headers::REQUEST_ID).unwrap());
+    }
+
+    #[derive(Serialize)]
+    struct Page {
+        items: Vec<String>,
+    }
+
+    async fn list_greetings(greeting: Greeting) -> Result<Page, ApiError> {
+        Ok(Page { items: vec![greeting.name; 100] })
+    }
+
+    #[tokio::test]
+    async fn should_replace_oversized_responses_with_an_error() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options { max_response_bytes: Some(128), ..Default::default() };
+
+        let response = handle_rpc_req(&options, &list_greetings, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(500, response.status_code);
+        assert_eq!(
+            Some(Body::Text("Internal Server Error: response exceeds 128 bytes".to_string())),
+            response.body
+        );
+    }
+
+    #[tokio::test]
+    async fn should_keep_responses_within_the_limit() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+
+        let response = handle_rpc_req(&Options::default(), &list_greetings, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(200, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_map_deserialization_errors_with_the_custom_mapper() {
+        let req = create_request(Some(r#"{"nome":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options::default().on_deserialize_error(|cause| {
+            let problem = serde_json::json!({
+                "type": "about:blank",
+                "title": "Bad Request",
+                "status": 400,
+                "detail": cause.to_string(),
+            });
+            response::create_with_content_type(400, Some(problem.to_string()), "application/problem+json".to_string())
+        });
+
+        let response = handle_rpc_req(&options, &greet, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(400, response.status_code);
+        assert_eq!("application/problem+json", headers::from_response(&response).get(headers::CONTENT_TYPE).unwrap());
+
+        let problem: serde_json::Value = serde_json::from_str(response.body_text().unwrap()).unwrap();
+        assert_eq!("Bad Request", problem["title"]);
+        assert!(problem["detail"].as_str().unwrap().contains("missing field `name`"));
+    }
+
+    #[test]
+    fn should_strip_matching_path_prefixes() {
+        assert_eq!("/users/3", strip_path_prefix("/api/v1/users/3".to_string(), "/api/v1"));
+        assert_eq!("/users/3", strip_path_prefix("/api/v1/users/3".to_string(), "/api/v1/"));
+        assert_eq!("/", strip_path_prefix("/api/v1".to_string(), "/api/v1"));
+    }
+
+    #[test]
+    fn should_leave_non_matching_paths_untouched() {
+        assert_eq!("/health", strip_path_prefix("/health".to_string(), "/api/v1"));
+        assert_eq!("/api/v10/users", strip_path_prefix("/api/v10/users".to_string(), "/api/v1"));
+    }
+
+    async fn echo_path(req: AlbTargetGroupRequest) -> AlbTargetGroupResponse {
+        response::create_as_plain_text(200, req.path)
+    }
+
+    #[tokio::test]
+    async fn should_hand_the_stripped_path_to_the_handler() {
+        let mut req = create_request(None, &[]);
+        req.path = Some("/api/v1/users/3".to_string());
+        let options = Options::default().strip_prefix("/api/v1");
+
+        let response = handle_rpc_req(&options, &echo_path, req, Context::default()).await.unwrap();
+        assert_eq!(Some(Body::Text("/users/3".to_string())), response.body);
+    }
+
+    #[tokio::test]
+    async fn should_send_json_values_returned_by_handlers() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let handler = |greeting: Greeting| async move {
+            serde_json::json!({ "greeting": format!("Hello, {}", greeting.name) })
+        };
+
+        let response = handle_rpc_req(&Options::default(), &handler, req, Context::default()).await.unwrap();
+        assert_eq!(200, response.status_code);
+        assert_eq!("application/json", headers::from_response(&response).get(headers::CONTENT_TYPE).unwrap());
+        assert_eq!(Some(Body::Text(r#"{"greeting":"Hello, John"}"#.to_string())), response.body);
+    }
+
+    #[tokio::test]
+    async fn should_add_security_headers_to_responses() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options { security_headers: Some(SecurityHeaders::default()), ..Default::default() };
+
+        let response = handle_rpc_req(&options, &greet, req, Context::default()).await.unwrap();
+        let headers = headers::from_response(&response);
+        assert_eq!("nosniff", headers["X-Content-Type-Options"]);
+        assert_eq!("DENY", headers["X-Frame-Options"]);
+        assert_eq!(response::DEFAULT_HSTS, headers["Strict-Transport-Security"]);
+    }
+
+    #[tokio::test]
+    async fn should_keep_security_headers_set_by_the_handler() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options { security_headers: Some(SecurityHeaders { hsts: None }), ..Default::default() };
+        let handler = |greeting: Greeting| async move {
+            greet(greeting).await.with_header("X-Frame-Options", "SAMEORIGIN")
+        };
+
+        let response = handle_rpc_req(&options, &handler, req, Context::default()).await.unwrap();
+        let headers = headers::from_response(&response);
+        assert_eq!("SAMEORIGIN", headers["X-Frame-Options"]);
+        assert_eq!("nosniff", headers["X-Content-Type-Options"]);
+        assert_eq!(None, headers.get("Strict-Transport-Security"));
+    }
+
+    #[tokio::test]
+    async fn should_map_responses_before_sending_them() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options::default().map_response(|response, ctx| {
+            response.with_header(headers::REQUEST_ID, &ctx.request_id)
+        });
+
+        let response = handle_rpc_req(&options, &greet, req, create_context("0000-0651")).await.unwrap();
+        assert_eq!(200, response.status_code);
+        assert_eq!("0000-0651", headers::from_response(&response)[headers::REQUEST_ID]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "compression")]
+    async fn should_deserialize_gzip_encoded_bodies() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(br#"{"name":"John"}"#).unwrap();
+        let compressed = base64::encode(encoder.finish().unwrap());
+
+        let mut req = create_request(
+            Some(&compressed), &[("Content-Type", "application/json"), ("Content-Encoding", "gzip")]);
+        req.is_base64_encoded = true;
+
+        let response = handle_rpc_req(&Options::default(), &greet, req, Context::default()).await.unwrap();
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(Body::Text("Hello, John".to_string())), response.body);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "compression")]
+    async fn should_reject_unknown_content_encodings() {
+        let req = create_request(
+            Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json"), ("Content-Encoding", "zstd")]);
+
+        let response = handle_rpc_req(&Options::default(), &greet, req, Context::default()).await.unwrap();
+        assert_eq!(415, response.status_code);
+        assert_eq!(
+            Some(Body::Text("Unsupported Media Type: content encoding zstd".to_string())),
+            response.body
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "compression")]
+    async fn should_reject_bodies_expanding_past_the_limit() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&vec![b' '; 1024 * 1024]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < 4 * 1024);
+
+        let mut req = create_request(
+            Some(&base64::encode(compressed)), &[("Content-Type", "application/json"), ("Content-Encoding", "gzip")]);
+        req.is_base64_encoded = true;
+        let options = Options { max_body_bytes: Some(64 * 1024), ..Default::default() };
+        let handler = |_: Unreachable| async { response::no_content() };
+
+        let response = handle_rpc_req(&options, &handler, req, Context::default()).await.unwrap();
+        assert_eq!(413, response.status_code);
+        assert_eq!(Some(Body::Text("Payload Too Large: limit is 65536 bytes".to_string())), response.body);
+    }
+
+    /// Fails the test if ever deserialized.
+    struct Unreachable;
+
+    impl AlbDeserialize<Unreachable> for Unreachable {
+        fn from_alb_request(_: AlbTargetGroupRequest, _: Context) -> Result<Unreachable, Error> {
+            panic!("Unauthorized requests should not be deserialized")
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_requests_lacking_the_required_header() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options::default().require_header("Authorization");
+        let handler = |_: Unreachable| async { response::no_content() };
+
+        let response = handle_rpc_req(&options, &handler, req, Context::default()).await.unwrap();
+        assert_eq!(401, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_hand_authorized_requests_to_the_handler() {
+        let req = create_request(
+            Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json"), ("Authorization", "Bearer abc")]);
+        let options = Options::default()
+            .authorize(|req| headers::from_request(req).get("Authorization")
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.starts_with("Bearer ")));
+
+        let response = handle_rpc_req(&options, &greet, req, Context::default()).await.unwrap();
+        assert_eq!(200, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_include_request_id_on_error_responses() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let ctx = create_context("0000-0001");
+
+        let response = handle_rpc_req(&Options::default(), &fail_greeting, req, ctx).await.unwrap();
+        assert_eq!(500, response.status_code);
+        assert_eq!(
+            Some(Body::Text(r#"{"error":"Unable to greet","request_id":"0000-0001"}"#.to_string())),
+            response.body
+        );
+        assert_eq!("0000-0001", headers::from_response(&response).get(headers::REQUEST_ID).unwrap());
+    }
+
+    async fn fail_unexpectedly(_: Greeting) -> Result<String, &'static str> {
+        Err("Unit Test")
+    }
+
+    async fn fail_at_runtime(_: Greeting) -> Error {
+        Error::from("Unit Test")
+    }
+
+    #[tokio::test]
+    async fn should_render_errors_as_text_by_default() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+
+        let response = handle_rpc_req(&Options::default(), &fail_unexpectedly, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(500, response.status_code);
+        assert_eq!(Some(Body::Text(r#"Internal Server Error: "Unit Test""#.to_string())), response.body);
+        assert_eq!(content_types::PLAIN_TEXT, headers::from_response(&response).get(headers::CONTENT_TYPE).unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_render_errors_in_the_configured_format() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options { error_format: ErrorFormat::Json, ..Default::default() };
+
+        let response = handle_rpc_req(&options, &fail_unexpectedly, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(500, response.status_code);
+        assert_eq!(
+            Some(Body::Text(r#"{"error":"Unit Test","request_id":"0000-0001","type":"&str"}"#.to_string())),
+            response.body
+        );
+        assert_eq!(content_types::JSON, headers::from_response(&response).get(headers::CONTENT_TYPE).unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_render_runtime_errors_in_both_formats() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let text = handle_rpc_req(&Options::default(), &fail_at_runtime, req.clone(), create_context("0000-0001")).await.unwrap();
+        assert_eq!(Some(Body::Text("Unit Test".to_string())), text.body);
+
+        let options = Options { error_format: ErrorFormat::Json, ..Default::default() };
+        let json = handle_rpc_req(&options, &fail_at_runtime, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(
+            Some(Body::Text(r#"{"error":"Unit Test","request_id":"0000-0001","type":"mu_runtime::error::Error"}"#.to_string())),
+            json.body
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_include_request_id_on_successful_responses() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let ctx = create_context("0000-0001");
+
+        let response = handle_rpc_req(&Options::default(), &greet, req, ctx).await.unwrap();
+        assert_eq!(200, response.status_code);
+        assert_eq!(None, headers::from_response(&response).get(headers::REQUEST_ID));
+    }
+
+    #[tokio::test]
+    async fn should_hand_both_payload_and_original_request_to_full_handlers() {
+        let req = create_request(
+            Some(r#"{"name":"John"}"#),
+            &[("Content-Type", "application/json"), ("X-Correlation-Id", "abc-123")]);
+        let ctx = create_context("0000-0001");
+
+        let handler = |greeting: Greeting, ctx: Context, req: AlbTargetGroupRequest| async move {
+            let correlation_id = headers::from_request(&req).get("X-Correlation-Id").unwrap();
+            response::create_as_plain_text(200, Some(format!(
+                "{} {} {}", greeting.name, correlation_id.to_str().unwrap(), ctx.request_id)))
+        };
+
+        let response = handle_full_rpc_req(&Options::default(), &handler, req, ctx).await.unwrap();
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(Body::Text("John abc-123 0000-0001".to_string())), response.body);
+    }
+
+    /// Fails the test if deserialized from an owned request.
+    struct BorrowingOnly(String);
+
+    impl AlbDeserialize<BorrowingOnly> for BorrowingOnly {
+        fn from_alb_request(_: AlbTargetGroupRequest, _: Context) -> Result<BorrowingOnly, Error> {
+            panic!("Full handlers should deserialize from the borrowed request")
+        }
+
+        fn from_alb_request_ref(req: &AlbTargetGroupRequest, _: &Context) -> Result<BorrowingOnly, Error> {
+            Ok(BorrowingOnly(req.body.clone().unwrap_or_default()))
+        }
+    }
+
+    #[tokio::test]
+    async fn should_deserialize_full_requests_without_taking_them() {
+        let req = create_request(Some("John"), &[]);
+        let handler = |payload: BorrowingOnly, _ctx: Context, req: AlbTargetGroupRequest| async move {
+            response::create_as_plain_text(200, Some(format!("{} {}", payload.0, req.body.unwrap())))
+        };
+
+        let response = handle_full_rpc_req(&Options::default(), &handler, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(Some(Body::Text("John John".to_string())), response.body);
+    }
+
+    #[tokio::test]
+    async fn should_apply_the_options_to_full_handlers() {
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let options = Options::default().require_header("Authorization");
+
+        let handler = |greeting: Greeting, _ctx: Context, _req: AlbTargetGroupRequest| greet(greeting);
+
+        let response = handle_full_rpc_req(&options, &handler, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(401, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unexpected_content_types() {
+        let req = create_request(
+            Some("name=John"), &[("Content-Type", "application/x-www-form-urlencoded")]);
+
+        let response = handle_rpc_req(&Options::default(), &greet, req, Context::default()).await.unwrap();
+        assert_eq!(415, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_accept_expected_content_types() {
+        let req = create_request(
+            Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json; charset=utf-8")]);
+
+        let response = handle_rpc_req(&Options::default(), &greet, req, Context::default()).await.unwrap();
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(Body::Text("Hello, John".to_string())), response.body);
+    }
+
+    #[tokio::test]
+    async fn should_reject_bodies_over_the_configured_limit() {
+        let options = Options { max_body_bytes: Some(14), ..Default::default() };
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+
+        let response = handle_rpc_req(&options, &greet, req, Context::default()).await.unwrap();
+        assert_eq!(413, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_accept_bodies_under_the_configured_limit() {
+        let options = Options { max_body_bytes: Some(16), ..Default::default() };
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+
+        let response = handle_rpc_req(&options, &greet, req, Context::default()).await.unwrap();
+        assert_eq!(200, response.status_code);
+    }
+
+    #[tokio::test]
+    async fn should_warn_about_bodies_over_the_warning_threshold_but_still_process_them() {
+        testing::init_logger();
+
+        let options = Options { warn_body_bytes: Some(8), ..Default::default() };
+        let req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        let ctx = create_context("0000-0637");
+
+        let response = handle_rpc_req(&options, &greet, req, ctx).await.unwrap();
+        assert_eq!(200, response.status_code);
+
+        let logs = testing::captured_logs(log::Level::Warn);
+        let expected = "Request 0000-0637 has a body of 15 bytes, exceeding the warning threshold of 8 bytes";
+        assert!(logs.iter().any(|entry| entry == expected), "{:?}", logs);
+    }
+
+    #[tokio::test]
+    async fn should_measure_the_decoded_size_of_base64_bodies() {
+        // 15 bytes once decoded, 20 bytes encoded
+        let mut req = create_request(Some("eyJuYW1lIjoiSm9obiJ9"), &[("Content-Type", "application/json")]);
+        req.is_base64_encoded = true;
+        assert_eq!(15, decoded_body_len(&req));
+
+        let options = Options { max_body_bytes: Some(14), ..Default::default() };
+        let response = handle_rpc_req(&options, &greet, req.clone(), Context::default()).await.unwrap();
+        assert_eq!(413, response.status_code);
+
+        let options = Options { max_body_bytes: Some(16), ..Default::default() };
+        let response = handle_rpc_req(&options, &greet, req, Context::default()).await.unwrap();
+        assert_ne!(413, response.status_code);
+    }
+
+    #[cfg(feature = "forms")]
+    #[tokio::test]
+    async fn should_reject_content_types_without_a_deserializer() {
+        #[derive(Deserialize)]
+        struct Untyped { name: String }
+        impl RpcRequest for Untyped {}
+
+        let req = create_request(Some("<name>John</name>"), &[("Content-Type", "application/xml")]);
+        let handler = |untyped: Untyped| async move {
+            response::create_as_plain_text(200, Some(untyped.name))
+        };
+
+        let response = handle_rpc_req(&Options::default(), &handler, req, create_context("0000-0001")).await.unwrap();
+        assert_eq!(415, response.status_code);
+        assert_eq!(
+            Some(Body::Text(r#"{"error":"Unsupported Media Type: application/xml","request_id":"0000-0001"}"#.to_string())),
+            response.body
+        );
+    }
+
+    #[tokio::test]
+    async fn should_log_the_status_of_each_request() {
+        testing::init_logger();
+
+        let mut req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        req.path = Some("/greetings/ok".to_string());
+        let response = handle_logged_rpc_req(&Options::default(), &greet, req, Context::default()).await.unwrap();
+        assert_eq!(200, response.status_code);
+
+        let mut req = create_request(Some(r#"{"name":"John"}"#), &[("Content-Type", "application/json")]);
+        req.path = Some("/greetings/failure".to_string());
+        let response = handle_logged_rpc_req(&Options::default(), &fail_greeting, req, Context::default()).await.unwrap();
+        assert_eq!(500, response.status_code);
+
+        let logs = testing::captured_logs(log::Level::Info);
+        assert_access_logged(&logs, "/greetings/ok", 200);
+        assert_access_logged(&logs, "/greetings/failure", 500);
+    }
+
+    #[cfg(not(feature = "json_access_log"))]
+    fn assert_access_logged(logs: &[String], path: &str, status: i64) {
+        let prefix = format!("POST {} {} ", path, status);
+        assert!(logs.iter().any(|entry| entry.starts_with(&prefix)), "{:?}", logs);
+    }
+
+    #[cfg(feature = "json_access_log")]
+    fn assert_access_logged(logs: &[String], path: &str, status: i64) {
+        let logged = logs.iter()
+            .filter_map(|entry| serde_json::from_str::<serde_json::Value>(entry).ok())
+            .any(|entry| entry["method"] == "POST" && entry["path"] == path && entry["status"] == status);
+        assert!(logged, "{:?}", logs);
+    }
 }
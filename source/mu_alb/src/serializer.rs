@@ -1,15 +1,34 @@
 //! Provides abstractions for Alb Request serialization.
 
-use std::fmt::Debug;
+use std::any::type_name;
+use std::fmt::{Debug, Display};
 
+use aws_lambda_events::encodings::Body;
 use aws_lambda_events::event::alb::AlbTargetGroupResponse;
 use serde::Serialize;
 
-use crate::response;
+use mu_runtime::Context;
+
+use crate::error::{self, ApiError, ErrorFormat, Problem};
+use crate::response::{self, headers, ResponseParts};
 
 /// Serialize ordinary structures and enums into an ALB valid response.
 pub trait AlbSerialize {
     fn to_alb_response(&self) -> AlbTargetGroupResponse;
+
+    /// Serializes into an ALB response, having access to the invocation context.
+    /// Error responses use it to include the request id in their body, so clients
+    /// can report it to support. Defaults to [AlbSerialize::to_alb_response].
+    fn to_alb_response_with(&self, _ctx: &Context) -> AlbTargetGroupResponse {
+        self.to_alb_response()
+    }
+
+    /// Serializes into an ALB response, as [AlbSerialize::to_alb_response_with] does,
+    /// rendering failures lacking their own representation in the given `format`.
+    /// Defaults to [AlbSerialize::to_alb_response_with].
+    fn to_alb_response_formatted(&self, ctx: &Context, _format: ErrorFormat) -> AlbTargetGroupResponse {
+        self.to_alb_response_with(ctx)
+    }
 }
 
 impl AlbSerialize for AlbTargetGroupResponse {
@@ -20,27 +39,208 @@ impl AlbSerialize for AlbTargetGroupResponse {
 
 impl AlbSerialize for mu_runtime::Error {
     fn to_alb_response(&self) -> AlbTargetGroupResponse {
-        let body = format!("{}", self);
-        response::create_as_plain_text(500, Some(body))
+        runtime_error(self, ErrorFormat::default(), None)
+    }
+
+    fn to_alb_response_with(&self, ctx: &Context) -> AlbTargetGroupResponse {
+        runtime_error(self, ErrorFormat::default(), Some(ctx))
+    }
+
+    fn to_alb_response_formatted(&self, ctx: &Context, format: ErrorFormat) -> AlbTargetGroupResponse {
+        runtime_error(self, format, Some(ctx))
+    }
+}
+
+fn runtime_error(cause: &mu_runtime::Error, format: ErrorFormat, ctx: Option<&Context>) -> AlbTargetGroupResponse {
+    let message = format!("{}", cause);
+    error::render_internal_error(format, message.clone(), message, type_name::<mu_runtime::Error>(), ctx)
+}
+
+/// Present values are sent as JSON, while absent ones are reported as
+/// `404 Not Found` with an empty body.
+impl<T: Serialize> AlbSerialize for Option<T> {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        match self {
+            Some(value) => response::create_json_from_obj(200, value),
+            None => response::create(404, None, headers::HeaderMap::new()),
+        }
+    }
+}
+
+/// Handlers with nothing to return reply with an empty-bodied `204 No Content`.
+impl AlbSerialize for () {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        response::no_content()
+    }
+}
+
+/// Dynamically built JSON values are sent as they are, with a `200 OK` status.
+impl AlbSerialize for serde_json::Value {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        response::create_as_json(200, Some(response::json_value_to_string(self)))
+    }
+}
+
+/// Responses built with the `http` crate are sent as they are, preserving their
+/// status code and headers.
+impl AlbSerialize for http::Response<String> {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        from_http_response(self, Body::Text(self.body().clone()), false)
+    }
+}
+
+/// Binary responses built with the `http` crate are sent base64 encoded,
+/// preserving their status code and headers.
+impl AlbSerialize for http::Response<Vec<u8>> {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        from_http_response(self, Body::Text(base64::encode(self.body())), true)
     }
 }
 
+/// The body is sent as JSON, using the status code and headers of the [ResponseParts].
+impl<T: Serialize> AlbSerialize for (ResponseParts, T) {
+    fn to_alb_response(&self) -> AlbTargetGroupResponse {
+        let (parts, body) = self;
+        match response::to_json(body) {
+            Ok(serialized) => {
+                let mut alb_response = response::create_as_json(parts.status, Some(serialized));
+                headers::from_response_mut(&mut alb_response).extend(parts.headers.clone());
+                alb_response
+            },
+            Err(cause) => response::create_as_plain_text(500, Some(format!("{}", cause))),
+        }
+    }
+}
+
+fn from_http_response<T>(source: &http::Response<T>, body: Body, is_base64_encoded: bool) -> AlbTargetGroupResponse {
+    let mut alb_response = response::create_with_description(
+        source.status().as_u16() as i64, None, headers::HeaderMap::new(), None);
+    headers::from_response_mut(&mut alb_response).extend(source.headers().clone());
+    alb_response.body = Some(body);
+    alb_response.is_base64_encoded = is_base64_encoded;
+    alb_response
+}
+
+/// Failures handlers can return within a `Result`. By default, they are reported as
+/// `500 Internal Server Error`, thus custom error types only need an empty impl:
+///
+/// ```
+/// use std::fmt::{Display, Formatter, Result};
+/// use mu_alb::IntoAlbError;
+///
+/// #[derive(Debug)]
+/// struct OutOfStock;
+///
+/// impl Display for OutOfStock {
+///     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+///         write!(f, "The product is out of stock")
+///     }
+/// }
+///
+/// impl IntoAlbError for OutOfStock {}
+/// ```
+///
+/// [ApiError] and [Problem] override it to be sent with their own status code.
+///
+/// Since 0.3.0, handlers returning `Result<T, E>` need `E` to implement this trait,
+/// where any `E: Debug` used to be accepted. Boxed errors (e.g. `Box<dyn Error>`) are
+/// covered already, keeping the status of the [ApiError] or [Problem] they may wrap,
+/// while custom error types need the impl above.
+pub trait IntoAlbError: Debug + Display {
+    /// Serializes the failure into an ALB response in the given `format`, including
+    /// the request id of the invocation `ctx` when it's available.
+    fn to_alb_error(&self, ctx: Option<&Context>, format: ErrorFormat) -> AlbTargetGroupResponse {
+        internal_server_error(self, format, ctx)
+    }
+}
+
+impl IntoAlbError for ApiError {
+    fn to_alb_error(&self, ctx: Option<&Context>, _format: ErrorFormat) -> AlbTargetGroupResponse {
+        match ctx {
+            Some(ctx) => self.to_alb_response_with(ctx),
+            None => self.to_alb_response(),
+        }
+    }
+}
+
+impl IntoAlbError for Problem {
+    fn to_alb_error(&self, _ctx: Option<&Context>, _format: ErrorFormat) -> AlbTargetGroupResponse {
+        self.to_alb_response()
+    }
+}
+
+/// Wrapped failures are sent as the failure they wrap.
+impl<E: IntoAlbError + ?Sized> IntoAlbError for Box<E> {
+    fn to_alb_error(&self, ctx: Option<&Context>, format: ErrorFormat) -> AlbTargetGroupResponse {
+        (**self).to_alb_error(ctx, format)
+    }
+}
+
+impl IntoAlbError for mu_runtime::Error {}
+impl IntoAlbError for String {}
+impl IntoAlbError for &str {}
+impl IntoAlbError for std::io::Error {}
+impl IntoAlbError for serde_json::Error {}
+
+/// Error trait objects wrapping an [ApiError] or a [Problem] are sent as them.
+macro_rules! impl_into_alb_error_for_dyn_error {
+    ($($error:ty),*) => {$(
+        impl IntoAlbError for $error {
+            fn to_alb_error(&self, ctx: Option<&Context>, format: ErrorFormat) -> AlbTargetGroupResponse {
+                if let Some(cause) = self.downcast_ref::<ApiError>() {
+                    return cause.to_alb_error(ctx, format)
+                }
+                if let Some(cause) = self.downcast_ref::<Problem>() {
+                    return cause.to_alb_error(ctx, format)
+                }
+                internal_server_error(self, format, ctx)
+            }
+        }
+    )*};
+}
+
+impl_into_alb_error_for_dyn_error!(
+    dyn std::error::Error,
+    dyn std::error::Error + Send,
+    dyn std::error::Error + Send + Sync
+);
+
+/// Successful results are sent as JSON, while failures are sent as defined
+/// by their [IntoAlbError] implementation.
 impl<T, E> AlbSerialize for Result<T, E>
 where
     T: Serialize,
-    E: Debug,
+    E: IntoAlbError,
 {
     fn to_alb_response(&self) -> AlbTargetGroupResponse {
         match self {
             Ok(response) => response::create_json_from_obj(200, response),
-            Err(cause) => response::create_as_plain_text(
-                500,
-                Some(format!("Internal Server Error: {:?}", cause)),
-            ),
+            Err(cause) => cause.to_alb_error(None, ErrorFormat::default()),
+        }
+    }
+
+    fn to_alb_response_with(&self, ctx: &Context) -> AlbTargetGroupResponse {
+        self.to_alb_response_formatted(ctx, ErrorFormat::default())
+    }
+
+    fn to_alb_response_formatted(&self, ctx: &Context, format: ErrorFormat) -> AlbTargetGroupResponse {
+        match self {
+            Ok(response) => response::create_json_from_obj(200, response),
+            Err(cause) => cause.to_alb_error(Some(ctx), format),
         }
     }
 }
 
+fn internal_server_error<E: Debug + Display + ?Sized>(cause: &E, format: ErrorFormat, ctx: Option<&Context>) -> AlbTargetGroupResponse {
+    error::render_internal_error(
+        format,
+        format!("Internal Server Error: {:?}", cause),
+        format!("{}", cause),
+        type_name::<E>(),
+        ctx,
+    )
+}
+
 #[cfg(test)]
 mod custom_serializer_tests {
     use serde::Serialize;
@@ -114,8 +314,123 @@ mod result_object_serialization_tests {
         let response = res.to_alb_response();
         assert_eq!(500, response.status_code);
         assert_eq!(
-            Body::Text("Internal Server Error: Error(\"Unit Test\")".to_string()),
+            Body::Text("Internal Server Error: Runtime(\"Unit Test\")".to_string()),
             response.body.unwrap()
         );
     }
 }
+
+#[cfg(test)]
+mod option_serialization_tests {
+    use aws_lambda_events::encodings::Body;
+    use super::*;
+
+    #[test]
+    fn should_serialize_present_values_as_json() {
+        let response = Some(vec![1, 2, 3]).to_alb_response();
+
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(Body::Text("[1,2,3]".to_string())), response.body);
+    }
+
+    #[test]
+    fn should_serialize_absent_values_as_not_found() {
+        let response = None::<Vec<i32>>.to_alb_response();
+
+        assert_eq!(404, response.status_code);
+        assert_eq!(Some(Body::Empty), response.body);
+    }
+}
+
+#[cfg(test)]
+mod unit_serialization_tests {
+    use aws_lambda_events::encodings::Body;
+
+    use crate::response::headers;
+    use super::*;
+
+    #[test]
+    fn should_serialize_unit_as_no_content() {
+        let response = ().to_alb_response();
+
+        assert_eq!(204, response.status_code);
+        assert_eq!(Some(Body::Empty), response.body);
+        assert!(!headers::from_response(&response).contains_key(headers::CONTENT_TYPE));
+    }
+}
+
+#[cfg(test)]
+mod http_response_tests {
+    use aws_lambda_events::encodings::Body;
+
+    use crate::response::headers;
+    use super::*;
+
+    #[test]
+    fn should_carry_over_status_and_headers_of_text_responses() {
+        let res = http::Response::builder()
+            .status(201)
+            .header("Content-Type", "text/csv")
+            .header("Location", "/reports/42")
+            .body("id,name\n42,John".to_string())
+            .unwrap();
+
+        let response = res.to_alb_response();
+        assert_eq!(201, response.status_code);
+        assert_eq!(Some("Created".to_string()), response.status_description);
+        assert_eq!(Some(Body::Text("id,name\n42,John".to_string())), response.body);
+        assert!(!response.is_base64_encoded);
+
+        let headers = headers::from_response(&response);
+        assert_eq!("text/csv", headers.get("Content-Type").unwrap());
+        assert_eq!("/reports/42", headers.get("Location").unwrap());
+    }
+
+    #[test]
+    fn should_encode_binary_responses() {
+        let res = http::Response::builder()
+            .status(200)
+            .header("Content-Type", "image/png")
+            .body(vec![0x89, 0x50, 0x4e, 0x47])
+            .unwrap();
+
+        let response = res.to_alb_response();
+        assert_eq!(200, response.status_code);
+        assert_eq!(Some(Body::Text("iVBORw==".to_string())), response.body);
+        assert!(response.is_base64_encoded);
+        assert_eq!("image/png", headers::from_response(&response).get("Content-Type").unwrap());
+    }
+}
+
+#[cfg(test)]
+mod response_parts_tests {
+    use aws_lambda_events::encodings::Body;
+    use serde::Serialize;
+
+    use crate::response::{content_types, headers, ResponseParts};
+    use super::*;
+
+    #[derive(Serialize)]
+    struct User {
+        id: u64,
+    }
+
+    #[test]
+    fn should_use_the_status_and_headers_of_the_parts() {
+        let parts = ResponseParts::new(201).with_header("Location", "/users/42");
+
+        let response = (parts, User { id: 42 }).to_alb_response();
+        assert_eq!(201, response.status_code);
+        assert_eq!(Some(Body::Text(r#"{"id":42}"#.to_string())), response.body);
+
+        let headers = headers::from_response(&response);
+        assert_eq!("/users/42", headers.get("Location").unwrap());
+        assert_eq!(content_types::JSON, headers.get(headers::CONTENT_TYPE).unwrap());
+    }
+
+    #[test]
+    fn should_default_to_ok() {
+        let response = (ResponseParts::default(), User { id: 42 }).to_alb_response();
+        assert_eq!(200, response.status_code);
+    }
+}
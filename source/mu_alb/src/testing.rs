@@ -0,0 +1,44 @@
+//! Shared utilities for the unit tests of this crate.
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use aws_lambda_events::event::alb::{AlbTargetGroupRequest, AlbTargetGroupRequestContext, ElbContext};
+use http::header::HeaderName;
+use http::{HeaderMap, HeaderValue, Method};
+
+use mu_runtime::Context;
+pub use mu_runtime::testing::{captured_logs, init_logger};
+
+/// Creates a request with the given body and headers. Headers are
+/// populated in both single and multi-value maps, so tests behave the
+/// same regardless of the `multi_header` feature.
+pub fn create_request(body: Option<&str>, headers: &[(&str, &str)]) -> AlbTargetGroupRequest {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        header_map.append(
+            HeaderName::from_str(name).unwrap(),
+            HeaderValue::from_str(value).unwrap()
+        );
+    }
+
+    AlbTargetGroupRequest {
+        http_method: Method::POST,
+        path: Some("/".to_string()),
+        query_string_parameters: HashMap::new(),
+        multi_value_query_string_parameters: HashMap::new(),
+        headers: header_map.clone(),
+        multi_value_headers: header_map,
+        request_context: AlbTargetGroupRequestContext {
+            elb: ElbContext { target_group_arn: None }
+        },
+        is_base64_encoded: false,
+        body: body.map(str::to_string),
+    }
+}
+
+/// Creates an invocation context with the given request id.
+pub fn create_context(request_id: &str) -> Context {
+    let mut ctx = Context::default();
+    ctx.request_id = request_id.to_string();
+    ctx
+}
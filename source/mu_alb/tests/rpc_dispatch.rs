@@ -0,0 +1,77 @@
+#![cfg(feature = "derive")]
+
+use aws_lambda_events::encodings::Body;
+use aws_lambda_events::event::alb::{
+    AlbTargetGroupRequest, AlbTargetGroupRequestContext, AlbTargetGroupResponse, ElbContext
+};
+use http::{HeaderMap, Method};
+use serde::Deserialize;
+
+use mu_alb::*;
+
+#[derive(Deserialize)]
+struct CreateUser {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteUser {
+    id: u64,
+}
+
+#[derive(Deserialize, RpcDispatch)]
+#[serde(tag = "type")]
+enum Operation {
+    #[rpc(handler = "create_user")]
+    CreateUser(CreateUser),
+    #[rpc(handler = "delete_user")]
+    DeleteUser(DeleteUser),
+}
+
+async fn create_user(req: CreateUser) -> Result<String, ApiError> {
+    Ok(format!("Created {}", req.name))
+}
+
+async fn delete_user(req: DeleteUser) -> Result<u64, ApiError> {
+    Err(ApiError::not_found(format!("User {} not found", req.id)))
+}
+
+fn create_request(body: &str) -> AlbTargetGroupRequest {
+    AlbTargetGroupRequest {
+        http_method: Method::POST,
+        path: Some("/".to_string()),
+        query_string_parameters: Default::default(),
+        multi_value_query_string_parameters: Default::default(),
+        headers: HeaderMap::new(),
+        multi_value_headers: HeaderMap::new(),
+        request_context: AlbTargetGroupRequestContext {
+            elb: ElbContext { target_group_arn: None }
+        },
+        is_base64_encoded: false,
+        body: Some(body.to_string()),
+    }
+}
+
+async fn dispatch(body: &str) -> Result<AlbTargetGroupResponse, Error> {
+    let operation = Operation::from_alb_request(create_request(body), Context::default())?;
+    Ok(operation.dispatch().await)
+}
+
+#[tokio::test]
+async fn should_route_each_variant_to_its_handler() {
+    let created = dispatch(r#"{"type":"CreateUser","name":"John"}"#).await.unwrap();
+    assert_eq!(200, created.status_code);
+    assert_eq!(Some(Body::Text(r#""Created John""#.to_string())), created.body);
+
+    let deleted = dispatch(r#"{"type":"DeleteUser","id":42}"#).await.unwrap();
+    assert_eq!(404, deleted.status_code);
+    assert_eq!(Some(Body::Text(r#"{"error":"User 42 not found"}"#.to_string())), deleted.body);
+}
+
+#[tokio::test]
+async fn should_reject_unknown_tags() {
+    let result = dispatch(r#"{"type":"RenameUser","name":"John"}"#).await;
+
+    let cause = result.err().unwrap();
+    assert!(format!("{}", cause).contains("unknown variant `RenameUser`"));
+}
@@ -0,0 +1,73 @@
+//! Derive macros for `mu_alb`. Rather than depending on this crate directly,
+//! enable the `derive` feature of `mu_alb`, which re-exports them.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitStr, Path};
+
+/// Turns an internally tagged enum into an RPC request, routing each variant
+/// to its handler. See `mu_alb::RpcDispatch` for details.
+#[proc_macro_derive(RpcDispatch, attributes(rpc))]
+pub fn derive_rpc_dispatch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_rpc_dispatch(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_rpc_dispatch(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => return Err(Error::new_spanned(&input.ident, "RpcDispatch can only be derived for enums")),
+    };
+
+    let mut arms = Vec::with_capacity(data.variants.len());
+    for variant in &data.variants {
+        let variant_name = &variant.ident;
+        match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {},
+            _ => return Err(Error::new_spanned(variant,
+                "RpcDispatch variants must wrap exactly one payload, e.g. `CreateUser(CreateUser)`")),
+        }
+
+        let handler = find_handler(variant)?;
+        arms.push(quote! {
+            #name::#variant_name(payload) => ::mu_alb::Responder::into_alb_response(#handler(payload).await),
+        });
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mu_alb::RpcRequest for #name #ty_generics #where_clause {}
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Routes the request to the handler of its variant, serializing its response.
+            pub async fn dispatch(self) -> ::mu_alb::__private::AlbTargetGroupResponse {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+    })
+}
+
+/// Reads the handler path from the `#[rpc(handler = "path::to::function")]` attribute.
+fn find_handler(variant: &syn::Variant) -> syn::Result<Path> {
+    let mut handler = None;
+
+    for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("rpc")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("handler") {
+                let value: LitStr = meta.value()?.parse()?;
+                handler = Some(value.parse::<Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported rpc attribute, expected `handler`"))
+            }
+        })?;
+    }
+
+    handler.ok_or_else(|| Error::new_spanned(&variant.ident,
+        "missing `#[rpc(handler = \"...\")]` attribute"))
+}
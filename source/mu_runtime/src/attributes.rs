@@ -0,0 +1,99 @@
+//! Typed access to the message attributes of SQS and SNS events, sparing
+//! handlers from decoding their `DataType`/`Value` structure by hand.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::sqs::SqsMessage;
+//! use mu_runtime::attributes::MessageAttributes;
+//! use mu_runtime::Error;
+//!
+//! async fn handle_message(msg: SqsMessage) -> Result<(), Error> {
+//!   let tenant = msg.message_attributes.get_string("tenant");
+//!   println!("Received {:?} for {:?}", msg.body, tenant);
+//!   Ok(())
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use aws_lambda_events::event::sqs::SqsMessageAttribute;
+use serde_json::Value;
+
+/// Typed getters over the raw message attribute maps. Attributes that are
+/// missing, or not of the expected data type, yield `None`.
+pub trait MessageAttributes {
+    /// The value of a `String` (or `Number`) attribute.
+    fn get_string(&self, name: &str) -> Option<String>;
+
+    /// The decoded value of a `Binary` attribute.
+    fn get_binary(&self, name: &str) -> Option<Vec<u8>>;
+}
+
+/// The attributes of an SQS message (`SqsMessage::message_attributes`).
+impl MessageAttributes for HashMap<String, SqsMessageAttribute> {
+    fn get_string(&self, name: &str) -> Option<String> {
+        self.get(name)?.string_value.clone()
+    }
+
+    fn get_binary(&self, name: &str) -> Option<Vec<u8>> {
+        self.get(name)?.binary_value.as_ref()
+            .map(|value| value.to_vec())
+    }
+}
+
+/// The attributes of an SNS message (`SnsEntity::message_attributes`), shaped
+/// as `{ "Type": "Binary", "Value": "<base64>" }`.
+impl MessageAttributes for HashMap<String, Value> {
+    fn get_string(&self, name: &str) -> Option<String> {
+        match sns_attribute(self, name)? {
+            ("Binary", _) => None,
+            (_, value) => Some(value.to_string()),
+        }
+    }
+
+    fn get_binary(&self, name: &str) -> Option<Vec<u8>> {
+        match sns_attribute(self, name)? {
+            ("Binary", value) => base64::decode(value).ok(),
+            _ => None,
+        }
+    }
+}
+
+fn sns_attribute<'a>(attributes: &'a HashMap<String, Value>, name: &str) -> Option<(&'a str, &'a str)> {
+    let attribute = attributes.get(name)?;
+    let data_type = attribute.get("Type")?.as_str()?;
+    let value = attribute.get("Value")?.as_str()?;
+    Some((data_type, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_lambda_events::event::sns::SnsEvent;
+    use aws_lambda_events::event::sqs::SqsEvent;
+
+    use crate::attributes::MessageAttributes;
+
+    const SQS_EVENT: &str = include_str!("../tests/sample_sqs_event.json");
+    const SNS_EVENT: &str = include_str!("../tests/sample_sns_event.json");
+
+    #[test]
+    fn should_read_sqs_attributes() {
+        let event: SqsEvent = serde_json::from_str(SQS_EVENT).unwrap();
+        let attributes = &event.records[0].message_attributes;
+
+        assert_eq!(Some("AttributeValue1".to_string()), attributes.get_string("Attribute1"));
+        assert_eq!(Some(b"1100".to_vec()), attributes.get_binary("Attribute3"));
+        assert_eq!(None, attributes.get_binary("Attribute1"));
+        assert_eq!(None, attributes.get_string("Unknown"));
+    }
+
+    #[test]
+    fn should_read_sns_attributes() {
+        let event: SnsEvent = serde_json::from_str(SNS_EVENT).unwrap();
+        let attributes = &event.records[0].sns.message_attributes;
+
+        assert_eq!(Some("TestString".to_string()), attributes.get_string("Test"));
+        assert_eq!(Some(b"TestBinary".to_vec()), attributes.get_binary("TestBinary"));
+        assert_eq!(None, attributes.get_string("TestBinary"));
+        assert_eq!(None, attributes.get_binary("Unknown"));
+    }
+}
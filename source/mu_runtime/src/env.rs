@@ -0,0 +1,139 @@
+//! Typed access to environment variables, producing consistent error
+//! messages keyed by the variable name.
+//!
+//! ```no_run
+//! use mu_runtime::env;
+//!
+//! let table_name = env::require("TABLE_NAME").unwrap();
+//! let batch_size: usize = env::get_or("BATCH_SIZE", 10).unwrap();
+//! ```
+
+use std::env::VarError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+/// Reads the environment variable `key`, failing if it is not defined.
+pub fn require(key: &str) -> Result<String> {
+    std::env::var(key)
+        .map_err(|cause| Error::Config(format!("missing env var {}: {}", key, cause)))
+}
+
+/// Reads and parses the environment variable `key`, failing if it is
+/// not defined or can't be parsed into `T`.
+pub fn get<T>(key: &str) -> Result<T>
+    where T: FromStr,
+          T::Err: Display
+{
+    let value = require(key)?;
+    parse(key, &value)
+}
+
+/// Reads and parses the environment variable `key`, falling back to `default`
+/// when it is not defined. Values that can't be parsed into `T` are still
+/// reported as failures.
+pub fn get_or<T>(key: &str, default: T) -> Result<T>
+    where T: FromStr,
+          T::Err: Display
+{
+    match std::env::var(key) {
+        Ok(value) => parse(key, &value),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Reads and parses the environment variable `key`, as [get] does, but also failing
+/// when it is empty. Some container-based emulators set variables to an empty string
+/// rather than leaving them unset.
+pub fn get_non_empty<T>(key: &str) -> Result<T>
+    where T: FromStr,
+          T::Err: Display
+{
+    match lookup_non_empty(key) {
+        Some(value) => parse(key, &value),
+        None => Err(Error::Config(format!("missing env var {}: {}", key, VarError::NotPresent))),
+    }
+}
+
+/// Reads and parses the environment variable `key`, as [get_or] does, but also
+/// falling back to `default` when it is empty.
+pub fn get_non_empty_or<T>(key: &str, default: T) -> Result<T>
+    where T: FromStr,
+          T::Err: Display
+{
+    match lookup_non_empty(key) {
+        Some(value) => parse(key, &value),
+        None => Ok(default),
+    }
+}
+
+fn lookup_non_empty(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+fn parse<T>(key: &str, value: &str) -> Result<T>
+    where T: FromStr,
+          T::Err: Display
+{
+    value.parse::<T>()
+        .map_err(|cause| Error::Config(format!("invalid env var {}: {}", key, cause)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::env;
+
+    #[test]
+    fn should_parse_integers() {
+        std::env::set_var("MU_TEST_ENV_INTEGER", "128");
+
+        let value: i32 = env::get("MU_TEST_ENV_INTEGER").unwrap();
+        assert_eq!(128, value);
+    }
+
+    #[test]
+    fn should_fallback_to_default_when_missing() {
+        let value: i32 = env::get_or("MU_TEST_ENV_MISSING", 42).unwrap();
+        assert_eq!(42, value);
+    }
+
+    #[test]
+    fn should_report_missing_variables() {
+        let error = env::require("MU_TEST_ENV_MISSING").unwrap_err();
+        assert!(format!("{}", error).starts_with("missing env var MU_TEST_ENV_MISSING"));
+    }
+
+    #[test]
+    fn should_report_parse_failures() {
+        std::env::set_var("MU_TEST_ENV_NOT_A_NUMBER", "abc");
+
+        let error = env::get_or::<i32>("MU_TEST_ENV_NOT_A_NUMBER", 42).unwrap_err();
+        assert_eq!("invalid env var MU_TEST_ENV_NOT_A_NUMBER: invalid digit found in string", format!("{}", error));
+    }
+
+    #[test]
+    fn should_report_empty_variables_as_missing() {
+        std::env::set_var("MU_TEST_ENV_EMPTY", "");
+
+        let error = env::get_non_empty::<i32>("MU_TEST_ENV_EMPTY").unwrap_err();
+        assert_eq!("missing env var MU_TEST_ENV_EMPTY: environment variable not found", format!("{}", error));
+    }
+
+    #[test]
+    fn should_fallback_to_default_when_empty() {
+        std::env::set_var("MU_TEST_ENV_EMPTY_WITH_DEFAULT", "");
+
+        let value: i32 = env::get_non_empty_or("MU_TEST_ENV_EMPTY_WITH_DEFAULT", 128).unwrap();
+        assert_eq!(128, value);
+    }
+
+    #[test]
+    fn should_parse_non_empty_variables() {
+        std::env::set_var("MU_TEST_ENV_NON_EMPTY", "512");
+
+        assert_eq!(512, env::get_non_empty::<i32>("MU_TEST_ENV_NON_EMPTY").unwrap());
+        assert_eq!(512, env::get_non_empty_or("MU_TEST_ENV_NON_EMPTY", 128).unwrap());
+    }
+}
@@ -8,59 +8,165 @@ use std::fmt::Formatter;
 /// main function will return a [Result] instance that will handled by
 /// the async/await runtime. Therefore, we don't need a fancy concrete type
 /// for error handling here, but a way to convey what happened upon the
-/// occurrence of an error.
+/// occurrence of an error. A few scenarios the runtime is able to recover
+/// from, though, have their own variant. More may be added over time, thus
+/// matching on it requires a wildcard arm.
 #[derive(Debug, Eq, PartialEq)]
-pub struct Error(String);
+#[non_exhaustive]
+pub enum Error {
+    /// A failure conveyed by its message.
+    Runtime(String),
+    /// The Lambda Runtime API answered the `/next` long-poll without a payload.
+    EmptyInvocation,
+    /// The handler didn't complete within the given time.
+    Timeout(std::time::Duration),
+    /// The long poll for the next invocation didn't start answering within the given time.
+    FetchTimeout(std::time::Duration),
+    /// The Lambda Runtime API couldn't be reached. Usually worth restarting over.
+    Transport(String),
+    /// The runtime is misconfigured (e.g. a required environment variable is missing),
+    /// thus restarting it won't help.
+    Config(String),
+}
+
+impl Error {
+
+    /// Tags the message of this error with the id of the invocation it happened
+    /// on (e.g. `[req 0000-0001] ...`), easing untangling the logs of multiple
+    /// invocations. The variant is kept, so the error can still be told apart.
+    pub fn for_invocation(self, request_id: &str) -> Self {
+        let tag = |msg: String| format!("[req {}] {}", request_id, msg);
+        match self {
+            Error::Runtime(msg) => Error::Runtime(tag(msg)),
+            Error::Transport(msg) => Error::Transport(tag(msg)),
+            Error::Config(msg) => Error::Config(tag(msg)),
+            other => other,
+        }
+    }
+}
 
 impl std::error::Error for Error {}
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0)
+        match self {
+            Error::Runtime(msg) => f.write_str(msg),
+            Error::EmptyInvocation => f.write_str("Empty invocation received from the Lambda Runtime API"),
+            Error::Timeout(timeout) => write!(f, "Handler timed out after {:?}", timeout),
+            Error::FetchTimeout(timeout) => write!(f, "No invocation received within {:?}", timeout),
+            Error::Transport(msg) => f.write_str(msg),
+            Error::Config(msg) => f.write_str(msg),
+        }
     }
 }
 
 impl From<String> for Error {
     fn from(s: String) -> Self {
-        Error(s)
+        Error::Runtime(s)
     }
 }
 
 impl From<&str> for Error {
     fn from(s: &str) -> Self {
-        Error(s.to_string())
+        Error::Runtime(s.to_string())
     }
 }
 
 impl From<std::string::FromUtf8Error> for Error {
     fn from(cause: std::string::FromUtf8Error) -> Self {
-        Error(format!("{}", cause))
+        Error::Runtime(format!("{}", cause))
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(cause: serde_json::Error) -> Self {
-        Error(format!("{}", cause))
+        Error::Runtime(format!("{}", cause))
     }
 }
 
 impl From<hyper::Error> for Error {
     fn from(cause: hyper::Error) -> Self {
-        Error(format!("{}", cause))
+        Error::Transport(format!("{}", cause))
     }
 }
 
 impl From<hyper::http::Error> for Error {
     fn from(cause: hyper::http::Error) -> Self {
-        Error(format!("{}", cause))
+        Error::Runtime(format!("{}", cause))
     }
 }
 
 impl From<hyper::http::uri::InvalidUri> for Error {
     fn from(cause: hyper::http::uri::InvalidUri) -> Self {
-        Error(format!("{}", cause))
+        Error::Runtime(format!("{}", cause))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(cause: std::io::Error) -> Self {
+        Error::Runtime(format!("{}", cause))
+    }
+}
+
+impl From<std::num::ParseIntError> for Error {
+    fn from(cause: std::num::ParseIntError) -> Self {
+        Error::Runtime(format!("{}", cause))
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Error {
+    fn from(cause: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Error::Runtime(format!("{}", cause))
     }
 }
 
 /// Short-hand result definition.
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use crate::error::{Error, Result};
+
+    fn read_config() -> Result<String> {
+        Err(io::Error::new(io::ErrorKind::NotFound, "config.toml not found"))?
+    }
+
+    fn parse_port(port: &str) -> Result<u16> {
+        Ok(port.parse::<u16>()?)
+    }
+
+    fn load_settings() -> std::result::Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        Err("settings bucket not reachable".into())
+    }
+
+    fn read_settings() -> Result<String> {
+        Ok(load_settings()?)
+    }
+
+    #[test]
+    fn should_convert_io_errors() {
+        assert_eq!(Err(Error::from("config.toml not found")), read_config())
+    }
+
+    #[test]
+    fn should_convert_parse_int_errors() {
+        assert_eq!(Ok(8080), parse_port("8080"));
+        assert_eq!(Err(Error::from("invalid digit found in string")), parse_port("80a0"))
+    }
+
+    #[test]
+    fn should_tag_errors_with_the_request_id() {
+        assert_eq!(
+            Error::Transport("[req 0000-0001] connection reset".to_string()),
+            Error::Transport("connection reset".to_string()).for_invocation("0000-0001")
+        );
+        assert_eq!(Error::EmptyInvocation, Error::EmptyInvocation.for_invocation("0000-0001"));
+    }
+
+    #[test]
+    fn should_convert_boxed_errors() {
+        assert_eq!(Err(Error::from("settings bucket not reachable")), read_settings())
+    }
+}
@@ -1,39 +1,84 @@
 //! The communication layer for the internal AWS Lambda API
-use std::env;
-
-use hyper::{Body, HeaderMap, Request};
+use std::error::Error as StdError;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use futures_util::{pin_mut, Stream, StreamExt};
+use hyper::{Body, HeaderMap, Request, Response, Uri};
+use hyper::header::HeaderValue;
+use hyper::http::{request, response};
 use hyper::body::Bytes;
 use hyper::client::{Client, HttpConnector};
+use hyper::client::connect::Connect;
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
 
 use crate::error::Error as LambdaApiError;
-use crate::model::{Config, Context};
+use crate::model::{Config, Context, InvocationStats, RawResponse};
 
 /// The Lambda Api Client. Abstracts the communication with the internal
 /// Lambda Runtime rest API, as documented [here].
 ///
+/// By default, it talks plain HTTP through hyper's [HttpConnector]. A custom
+/// connector (e.g. one adding metrics or proxy support) can be plugged in
+/// through [LambdaApiClient::with_connector].
+///
 /// [here]: https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html
-pub struct LambdaApiClient {
-    client: Client<HttpConnector, Body>,
-    config: Config
+pub struct LambdaApiClient<C = HttpConnector> {
+    client: Client<C, Body>,
+    config: Config,
+    timeout_callback: Option<TimeoutCallback>,
+    completion_callback: Option<CompletionCallback>,
+    request_interceptor: Option<RequestInterceptor>,
+    response_interceptor: Option<ResponseInterceptor>,
+    last_processed: Mutex<Option<ProcessedInvocation>>,
 }
 
+/// The response of the last successfully processed invocation.
+struct ProcessedInvocation {
+    request_id: String,
+    response: RawResponse,
+    processed_at: Instant,
+}
+
+/// A callback notified when an invocation is about to time out.
+pub type TimeoutCallback = Arc<dyn Fn(&Context) + Send + Sync>;
+
+/// A callback notified once the handler of an invocation completes.
+pub type CompletionCallback = Arc<dyn Fn(&InvocationStats) + Send + Sync>;
+
+/// A callback notified with the metadata of every request sent to the Lambda Runtime API.
+pub type RequestInterceptor = Arc<dyn Fn(&request::Parts) + Send + Sync>;
+
+/// A callback notified with the metadata of every response received from the Lambda
+/// Runtime API, along with the URI of the request it answers.
+pub type ResponseInterceptor = Arc<dyn Fn(&Uri, &response::Parts) + Send + Sync>;
+
+/// Announces a streamed response to the Lambda Runtime API.
+pub const RESPONSE_MODE_HEADER: &str = "Lambda-Runtime-Function-Response-Mode";
+
+/// The trailer holding the type of the error that interrupted a streamed response.
+pub const ERROR_TYPE_TRAILER: &str = "Lambda-Runtime-Function-Error-Type";
+
+/// The trailer holding the (base64 encoded) [PublishErrorRequest] of the error
+/// that interrupted a streamed response.
+pub const ERROR_BODY_TRAILER: &str = "Lambda-Runtime-Function-Error-Body";
+
+/// Whether no invocation has been received by this process yet, regardless of the
+/// client it was received through.
+static COLD_START: AtomicBool = AtomicBool::new(true);
+
+/// A hyper connector the [LambdaApiClient] can use to reach the Lambda Runtime API.
+pub trait Connector: Connect + Clone + Send + Sync + 'static {}
+
+impl<C> Connector for C where C: Connect + Clone + Send + Sync + 'static {}
+
 impl Default for LambdaApiClient {
     fn default() -> Self {
-        let config = Config {
-            endpoint: env::var("AWS_LAMBDA_RUNTIME_API").unwrap(),
-            function_name: env::var("AWS_LAMBDA_FUNCTION_NAME").unwrap(),
-            memory: env::var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE").unwrap()
-                .parse::<i32>().unwrap(),
-            version: env::var("AWS_LAMBDA_FUNCTION_VERSION").unwrap(),
-            log_stream: env::var("AWS_LAMBDA_LOG_STREAM_NAME").unwrap(),
-            log_group: env::var("AWS_LAMBDA_LOG_GROUP_NAME").unwrap(),
-        };
-
-        LambdaApiClient {
-            client: Client::new(),
-            config
-        }
+        LambdaApiClient::create(Config::from_env())
     }
 }
 
@@ -44,30 +89,150 @@ impl LambdaApiClient {
     /// break the communication with the intended endpoint in case
     /// of misconfiguration.
     pub fn create(config: Config) -> Self {
+        LambdaApiClient::with_connector(HttpConnector::new(), config)
+    }
+}
+
+impl<C> LambdaApiClient<C>
+    where C: Connector
+{
+
+    /// Creates a new instance of this client that reaches the Lambda Runtime API
+    /// through the given `connector`. See [LambdaApiClient::create].
+    pub fn with_connector(connector: C, config: Config) -> Self {
         LambdaApiClient {
-            client: Client::new(),
-            config
+            client: Client::builder().build(connector),
+            config,
+            timeout_callback: None,
+            completion_callback: None,
+            request_interceptor: None,
+            response_interceptor: None,
+            last_processed: Mutex::new(None),
+        }
+    }
+
+    /// Registers a callback to be notified when the remaining time of an invocation
+    /// drops below [Config::timeout_warning_threshold]. It gives the handler a chance
+    /// to flush partial results or emit metrics before the platform kills the function.
+    pub fn on_timeout_imminent<F>(mut self, callback: F) -> Self
+        where F: Fn(&Context) + Send + Sync + 'static
+    {
+        self.timeout_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Registers a callback to be notified once the handler of an invocation completes,
+    /// with its duration and whether it was a cold start. Handy for performance dashboards.
+    pub fn on_complete<F>(mut self, callback: F) -> Self
+        where F: Fn(&InvocationStats) + Send + Sync + 'static
+    {
+        self.completion_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Registers a callback to be notified before every request is sent to the Lambda
+    /// Runtime API. Meant to diagnose the runtime protocol, it sits below the handler.
+    pub fn on_request<F>(mut self, interceptor: F) -> Self
+        where F: Fn(&request::Parts) + Send + Sync + 'static
+    {
+        self.request_interceptor = Some(Arc::new(interceptor));
+        self
+    }
+
+    /// Registers a callback to be notified of every response received from the
+    /// Lambda Runtime API, e.g. to find out why an invocation failed to publish.
+    pub fn on_response<F>(mut self, interceptor: F) -> Self
+        where F: Fn(&Uri, &response::Parts) + Send + Sync + 'static
+    {
+        self.response_interceptor = Some(Arc::new(interceptor));
+        self
+    }
+
+    /// The configuration used by this client.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The callback registered through [LambdaApiClient::on_timeout_imminent].
+    pub fn timeout_callback(&self) -> Option<&TimeoutCallback> {
+        self.timeout_callback.as_ref()
+    }
+
+    /// The callback registered through [LambdaApiClient::on_complete].
+    pub fn completion_callback(&self) -> Option<&CompletionCallback> {
+        self.completion_callback.as_ref()
+    }
+
+    /// Remembers the response of a successfully processed invocation, as long as
+    /// [Config::duplicate_invocation_window] is enabled.
+    pub(crate) fn remember_processed(&self, request_id: &str, response: &RawResponse) {
+        if self.config.duplicate_invocation_window.is_none() {
+            return
         }
+
+        *self.last_processed.lock().unwrap() = Some(ProcessedInvocation {
+            request_id: request_id.to_string(),
+            response: response.clone(),
+            processed_at: Instant::now(),
+        });
+    }
+
+    /// The response of the invocation identified by `request_id`, when it was the last
+    /// one processed and the [Config::duplicate_invocation_window] hasn't elapsed yet.
+    pub(crate) fn processed_response(&self, request_id: &str) -> Option<RawResponse> {
+        let window = self.config.duplicate_invocation_window?;
+        self.last_processed.lock().unwrap().as_ref()
+            .filter(|processed| processed.request_id == request_id)
+            .filter(|processed| processed.processed_at.elapsed() < window)
+            .map(|processed| processed.response.clone())
     }
 
-    /// Fetches the next message to be processed.
+    /// Fetches the next message to be processed. Fails with [LambdaApiError::FetchTimeout]
+    /// when no message starts arriving within [Config::fetch_timeout]. Once it does,
+    /// its payload is read to the end, so delivered invocations are never dropped.
     pub async fn fetch_next_message(&self) -> Result<(Bytes, Context), LambdaApiError> {
         let uri = format!("http://{}/2018-06-01/runtime/invocation/next", &self.config.endpoint);
-        let uri = uri.parse()?;
-        let resp = self.client.get(uri).await?;
+        let req = Request::get(uri).body(Body::empty())?;
+        let resp = self.with_fetch_timeout(self.send(req)).await?;
         let (parts, body) = resp.into_parts();
         let body = hyper::body::to_bytes(body).await?;
+
+        if !parts.status.is_success() {
+            let error_msg = String::from_utf8(body.to_vec())?;
+            return Err(LambdaApiError::from(error_msg))
+        }
+
+        if body.is_empty() {
+            return Err(LambdaApiError::EmptyInvocation)
+        }
+
+        #[cfg(feature = "record")]
+        crate::record::record_from_env(&parts.headers, &body);
+
         let context = self.create_execution_context_from(parts.headers);
+        Ok((body, context))
+    }
+
+    /// Fetches the next message to be processed, without buffering its payload.
+    /// Unlike [LambdaApiClient::fetch_next_message], empty payloads aren't detected.
+    /// Only the arrival of the message is bound to [Config::fetch_timeout].
+    pub async fn fetch_next_stream(&self) -> Result<(Body, Context), LambdaApiError> {
+        let uri = format!("http://{}/2018-06-01/runtime/invocation/next", &self.config.endpoint);
+        let req = Request::get(uri).body(Body::empty())?;
+        let resp = self.with_fetch_timeout(self.send(req)).await?;
+        let (parts, body) = resp.into_parts();
 
         if !parts.status.is_success() {
+            let body = hyper::body::to_bytes(body).await?;
             let error_msg = String::from_utf8(body.to_vec())?;
             return Err(LambdaApiError::from(error_msg))
         }
 
+        let context = self.create_execution_context_from(parts.headers);
         Ok((body, context))
     }
 
-    fn create_execution_context_from(&self, headers: HeaderMap) -> Context {
+    pub(crate) fn create_execution_context_from(&self, headers: HeaderMap) -> Context {
         Context {
             request_id: headers["lambda-runtime-aws-request-id"]
                 .to_str()
@@ -86,13 +251,12 @@ impl LambdaApiClient {
                 .to_str()
                 .expect("Invalid XRayTraceID sent by Lambda; this is a bug")
                 .to_owned(),
-            client_context: headers.get("lambda-runtime-client-context")
-                .map(|h| h.to_str().expect("Invalid ClientContext sent by lambda"))
-                .map(|s| serde_json::from_str(s).expect("Invalid ClientContext sent by lambda")),
-            identity: headers.get("lambda-runtime-cognito-identity")
-                .map(|h| h.to_str().expect("Invalid CognitoIdentity sent by lambda"))
-                .map(|s| serde_json::from_str(s).expect("Invalid CognitoIdentity sent by lambda")),
+            client_context: parse_optional_header(&headers, "lambda-runtime-client-context"),
+            identity: parse_optional_header(&headers, "lambda-runtime-cognito-identity"),
             env_config: self.config.clone(),
+            // only the first invocation received by this process is a cold start
+            cold_start: COLD_START.swap(false, Ordering::SeqCst),
+            env_override: None,
         }
     }
 
@@ -109,20 +273,76 @@ impl LambdaApiClient {
         self.post_message(request_id, "error", payload).await
     }
 
+    /// Publish a pre-serialized response, sending its body verbatim.
+    pub async fn publish_raw_response(&self, request_id: String, response: RawResponse) -> Result<(), LambdaApiError>
+    {
+        self.post_bytes(request_id, "response", &response.content_type, response.body).await
+    }
+
+    /// Publish a response in the streaming mode, sending the chunks of `stream` as they
+    /// are produced. When the stream fails mid-way, the error is reported through the
+    /// [ERROR_TYPE_TRAILER] and [ERROR_BODY_TRAILER] trailers.
+    ///
+    /// hyper 0.14 never writes HTTP/1.1 trailers, thus the request is written by hand,
+    /// over a TCP connection of its own to the Lambda Runtime API. The custom connector
+    /// (if any) is bypassed, while the interceptors are still notified.
+    pub async fn publish_streamed_response<S, E>(&self, request_id: String, content_type: &str, stream: S) -> Result<(), LambdaApiError>
+        where S: Stream<Item=Result<Bytes, E>>,
+              E: StdError
+    {
+        let uri: Uri = format!(
+            "http://{}/2018-06-01/runtime/invocation/{}/response",
+            &self.config.endpoint, request_id).parse()?;
+
+        let (parts, _) = Request::post(uri.clone())
+            .header("host", &self.config.endpoint)
+            .header("content-type", content_type)
+            .header(RESPONSE_MODE_HEADER, "streaming")
+            .header("transfer-encoding", "chunked")
+            .header("trailer", format!("{}, {}", ERROR_TYPE_TRAILER, ERROR_BODY_TRAILER))
+            .header("connection", "close")
+            .body(())?
+            .into_parts();
+        if let Some(interceptor) = &self.request_interceptor {
+            (interceptor)(&parts);
+        }
+
+        let mut connection = TcpStream::connect(&self.config.endpoint).await
+            .map_err(|cause| LambdaApiError::Transport(format!("error trying to connect: {}", cause)))?;
+        write_streamed_request(&mut connection, &parts, stream).await?;
+        let (parts, body) = read_response(&mut connection).await?;
+        if let Some(interceptor) = &self.response_interceptor {
+            (interceptor)(&uri, &parts);
+        }
+
+        if !parts.status.is_success() {
+            let error_msg = String::from_utf8(body)?;
+            return Err(LambdaApiError::from(error_msg))
+        }
+
+        Ok(())
+    }
+
     async fn post_message<T>(&self, request_id: String, path: &str, payload: T) -> Result<(), LambdaApiError>
         where T: Serialize
     {
-        let payload = serde_json::to_string(&payload)?;
+        // serialized straight into bytes, avoiding an intermediate String copy
+        let payload = serde_json::to_vec(&payload)?;
+        self.post_bytes(request_id, path, "application/json", payload).await
+    }
 
+    async fn post_bytes<B>(&self, request_id: String, path: &str, content_type: &str, payload: B) -> Result<(), LambdaApiError>
+        where B: Into<Body>
+    {
         let uri = format!(
             "http://{}/2018-06-01/runtime/invocation/{}/{}",
             &self.config.endpoint, request_id, path);
 
         let req = Request::post(uri)
-            .header("content-type", "application/json")
-            .body(Body::from(payload))?;
+            .header("content-type", content_type)
+            .body(payload.into())?;
 
-        let resp = self.client.request(req).await?;
+        let resp = self.send(req).await?;
         let (parts, body) = resp.into_parts();
 
         if !parts.status.is_success() {
@@ -133,6 +353,148 @@ impl LambdaApiClient {
 
         Ok(())
     }
+
+    /// Bounds the fetch of the next invocation to [Config::fetch_timeout], if any,
+    /// failing with [LambdaApiError::FetchTimeout] once it elapses.
+    async fn with_fetch_timeout<Fut, T>(&self, fetch: Fut) -> Result<T, LambdaApiError>
+        where Fut: Future<Output=Result<T, LambdaApiError>>
+    {
+        let timeout = match self.config.fetch_timeout {
+            None => return fetch.await,
+            Some(timeout) => timeout,
+        };
+
+        tokio::time::timeout(timeout, fetch).await
+            .unwrap_or(Err(LambdaApiError::FetchTimeout(timeout)))
+    }
+
+    /// Sends the request, notifying the registered interceptors.
+    async fn send(&self, req: Request<Body>) -> Result<Response<Body>, LambdaApiError> {
+        let uri = req.uri().clone();
+        let req = match &self.request_interceptor {
+            None => req,
+            Some(interceptor) => {
+                let (parts, body) = req.into_parts();
+                (interceptor)(&parts);
+                Request::from_parts(parts, body)
+            },
+        };
+
+        let resp = self.client.request(req).await?;
+        match &self.response_interceptor {
+            None => Ok(resp),
+            Some(interceptor) => {
+                let (parts, body) = resp.into_parts();
+                (interceptor)(&uri, &parts);
+                Ok(Response::from_parts(parts, body))
+            },
+        }
+    }
+}
+
+/// Writes the request with a chunked body made of the chunks of `stream`, reporting
+/// its failure as trailers.
+async fn write_streamed_request<W, S, E>(writer: &mut W, parts: &request::Parts, stream: S) -> Result<(), LambdaApiError>
+    where W: AsyncWrite + Unpin,
+          S: Stream<Item=Result<Bytes, E>>,
+          E: StdError
+{
+    let path = parts.uri.path_and_query().map(|path| path.as_str()).unwrap_or("/");
+    let mut head = format!("{} {} HTTP/1.1\r\n", parts.method, path).into_bytes();
+    write_header_lines(&mut head, &parts.headers);
+    head.extend_from_slice(b"\r\n");
+    writer.write_all(&head).await?;
+
+    let mut trailers = HeaderMap::new();
+    pin_mut!(stream);
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            // an empty chunk would end the body prematurely
+            Ok(bytes) if bytes.is_empty() => {},
+            Ok(bytes) => {
+                writer.write_all(format!("{:x}\r\n", bytes.len()).as_bytes()).await?;
+                writer.write_all(&bytes).await?;
+                writer.write_all(b"\r\n").await?;
+            },
+            Err(cause) => {
+                log::error!("Streamed response interrupted: {}", cause);
+                trailers = create_error_trailers(&cause);
+                break
+            },
+        }
+    }
+
+    let mut last_chunk = b"0\r\n".to_vec();
+    write_header_lines(&mut last_chunk, &trailers);
+    last_chunk.extend_from_slice(b"\r\n");
+    writer.write_all(&last_chunk).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn write_header_lines(buffer: &mut Vec<u8>, headers: &HeaderMap) {
+    for (name, value) in headers {
+        buffer.extend_from_slice(name.as_str().as_bytes());
+        buffer.extend_from_slice(b": ");
+        buffer.extend_from_slice(value.as_bytes());
+        buffer.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Reads the response of a request sent with `connection: close`, up to the
+/// connection being closed by the Lambda Runtime API.
+async fn read_response<R>(reader: &mut R) -> Result<(response::Parts, Vec<u8>), LambdaApiError>
+    where R: AsyncRead + Unpin
+{
+    let mut raw = Vec::new();
+    reader.read_to_end(&mut raw).await?;
+
+    let mut headers = [httparse::EMPTY_HEADER; 32];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let head_len = match parsed.parse(&raw) {
+        Ok(httparse::Status::Complete(head_len)) => head_len,
+        Ok(httparse::Status::Partial) => return Err(LambdaApiError::Transport("incomplete response".to_string())),
+        Err(cause) => return Err(LambdaApiError::Transport(format!("malformed response: {}", cause))),
+    };
+
+    let mut builder = Response::builder().status(parsed.code.unwrap_or_default());
+    for header in parsed.headers.iter() {
+        builder = builder.header(header.name, header.value);
+    }
+    let (parts, _) = builder.body(())?.into_parts();
+
+    let mut body = raw.split_off(head_len);
+    let content_length = parts.headers.get("content-length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if let Some(content_length) = content_length {
+        body.truncate(content_length);
+    }
+    Ok((parts, body))
+}
+
+/// The name of the type of `value`, as `std::any::type_name_of_val` does. The
+/// latter is only available since Rust 1.76.
+pub(crate) fn type_name_of_val<T: ?Sized>(_: &T) -> &'static str {
+    std::any::type_name::<T>()
+}
+
+fn create_error_trailers<E: StdError>(cause: &E) -> HeaderMap {
+    let payload = PublishErrorRequest {
+        error_type: type_name_of_val(cause).to_string(),
+        error_message: format!("{}", cause),
+        stack_trace: None,
+    };
+    let error_body = serde_json::to_vec(&payload).unwrap_or_default();
+
+    let mut trailers = HeaderMap::new();
+    if let Ok(error_type) = HeaderValue::from_str(&payload.error_type) {
+        trailers.insert(ERROR_TYPE_TRAILER, error_type);
+    }
+    if let Ok(error_body) = HeaderValue::from_str(&base64::encode(error_body)) {
+        trailers.insert(ERROR_BODY_TRAILER, error_body);
+    }
+    trailers
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -140,13 +502,37 @@ impl LambdaApiClient {
 pub struct PublishErrorRequest {
     pub error_type: String,
     pub error_message: String,
+    /// The frames leading to the error, shown by the Lambda console when informed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_trace: Option<Vec<String>>,
+}
+
+/// Parses an optional JSON header. Malformed values are reported with a `WARN`
+/// log entry and ignored, as they shouldn't bring the whole function down.
+fn parse_optional_header<T>(headers: &HeaderMap, name: &str) -> Option<T>
+    where T: for<'de> Deserialize<'de>
+{
+    let value = headers.get(name)?;
+    let parsed = value.to_str()
+        .map_err(|cause| cause.to_string())
+        .and_then(|s| serde_json::from_str(s).map_err(|cause| cause.to_string()));
+
+    match parsed {
+        Ok(parsed) => Some(parsed),
+        Err(cause) => {
+            log::warn!("Ignoring malformed {} header: {}", name, cause);
+            None
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use crate::error::Error;
     use crate::lambda_api::LambdaApiClient;
     use crate::model::Config;
+    use crate::testing;
     use httpmock::MockServer;
     use maplit::hashmap;
 
@@ -154,6 +540,7 @@ mod tests {
 
         use super::*;
         use crate::model::{ClientContext, ClientApplication, CognitoIdentity};
+        use std::time::{Duration, Instant};
 
         // taken from the C# SDK
         // Check this commit for details: https://git.io/J3FGH
@@ -165,10 +552,8 @@ mod tests {
 
         #[tokio::test]
         async fn should_handle_failures() {
-            let unreachable_host = "localhost:4312";
-
             let api = LambdaApiClient::create(Config {
-                endpoint: unreachable_host.to_string(),
+                endpoint: testing::unreachable_endpoint(),
                 ..Config::default()
             });
 
@@ -182,6 +567,26 @@ mod tests {
             }
         }
 
+        #[tokio::test]
+        async fn should_time_out_when_no_invocation_arrives() {
+            let mock_server = MockServer::start();
+            let _next_endpoint = mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/next");
+                then.status(200).delay(Duration::from_secs(5));
+            });
+
+            let api = LambdaApiClient::create(Config {
+                endpoint: format!("127.0.0.1:{}", mock_server.port()),
+                fetch_timeout: Some(Duration::from_millis(100)),
+                ..Config::default()
+            });
+
+            let started_at = Instant::now();
+            let result = api.fetch_next_message().await;
+            assert_eq!(Err(Error::FetchTimeout(Duration::from_millis(100))), result.map(|_| ()));
+            assert!(started_at.elapsed() < Duration::from_secs(2));
+        }
+
         #[tokio::test]
         async fn should_handle_successful_requests() {
             let mock_server = MockServer::start();
@@ -215,6 +620,29 @@ mod tests {
             }
         }
 
+        #[tokio::test]
+        async fn should_report_empty_invocations() {
+            let mock_server = MockServer::start();
+
+            let next_endpoint = mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/next");
+                then.status(200)
+                    .header("lambda-runtime-aws-request-id", "0000-0001")
+                    .header("lambda-runtime-deadline-ms", "1000")
+                    .header("lambda-runtime-invoked-function-arn", "arn::something")
+                    .header("lambda-runtime-trace-id", "0001-0001");
+            });
+
+            let api = LambdaApiClient::create(Config {
+                endpoint: format!("localhost:{}", mock_server.port()),
+                ..Config::default()
+            });
+
+            let result = api.fetch_next_message().await;
+            assert_eq!(Some(Error::EmptyInvocation), result.err());
+            next_endpoint.assert();
+        }
+
         #[tokio::test]
         async fn should_serialize_client_context() {
             let mock_server = MockServer::start();
@@ -305,16 +733,50 @@ mod tests {
         }
     }
 
+    mod malformed_context_headers {
+        use super::*;
+
+        #[tokio::test]
+        async fn should_ignore_malformed_client_context_and_identity() {
+            let mock_server = MockServer::start();
+
+            mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/next");
+
+                then.status(200)
+                    .header("lambda-runtime-aws-request-id", "0000-0001")
+                    .header("lambda-runtime-deadline-ms", "1000")
+                    .header("lambda-runtime-invoked-function-arn", "arn::something")
+                    .header("lambda-runtime-trace-id", "0001-0001")
+                    .header("lambda-runtime-client-context", "{not json")
+                    .header("lambda-runtime-cognito-identity", "garbage")
+                    .body(r#"{ "body": "hello" }"#);
+            });
+
+            let api = LambdaApiClient::create(Config {
+                endpoint: format!("localhost:{}", mock_server.port()),
+                ..Config::default()
+            });
+
+            match api.fetch_next_message().await {
+                Err(cause) => panic!("Unexpected: {}", cause),
+                Ok((_bytes, ctx)) => {
+                    assert_eq!("0000-0001", ctx.request_id);
+                    assert_eq!(None, ctx.client_context);
+                    assert_eq!(None, ctx.identity);
+                }
+            }
+        }
+    }
+
     mod publish_successful_response {
 
         use super::*;
 
         #[tokio::test]
         async fn should_handle_failures() {
-            let unreachable_host = "localhost:4312";
-
             let api = LambdaApiClient::create(Config {
-                endpoint: unreachable_host.to_string(),
+                endpoint: testing::unreachable_endpoint(),
                 ..Config::default()
             });
 
@@ -352,6 +814,125 @@ mod tests {
 
             success_endpoint.assert();
         }
+
+        #[tokio::test]
+        async fn should_be_able_to_publish_large_responses() {
+            let mock_server = MockServer::start();
+            let large_payload = "x".repeat(4 * 1024 * 1024);
+            let expected_body = format!("\"{}\"", large_payload);
+
+            let success_endpoint = mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                    .body(&expected_body)
+                    .method("POST");
+
+                then.status(200);
+            });
+
+            let api = LambdaApiClient::create(Config {
+                endpoint: format!("localhost:{}", mock_server.port()),
+                ..Config::default()
+            });
+
+            let result = api.publish_response("0000-0001".to_string(), large_payload).await;
+            if let Err(cause) = result {
+                panic!("Returned unsuccessful result: {}", cause)
+            }
+
+            success_endpoint.assert();
+        }
+    }
+
+    mod publish_streamed_response {
+
+        use super::*;
+        use crate::lambda_api::{create_error_trailers, ERROR_BODY_TRAILER, ERROR_TYPE_TRAILER};
+        use hyper::body::Bytes;
+
+        #[tokio::test]
+        async fn should_announce_the_streaming_mode() {
+            let mock_server = MockServer::start();
+
+            let streaming_endpoint = mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                    .method("POST")
+                    .header("Lambda-Runtime-Function-Response-Mode", "streaming")
+                    .body("Hello, world");
+
+                then.status(202);
+            });
+
+            let api = LambdaApiClient::create(Config {
+                endpoint: format!("localhost:{}", mock_server.port()),
+                ..Config::default()
+            });
+
+            let chunks = futures_util::stream::iter(vec![
+                Ok::<_, Error>(Bytes::from("Hello, ")),
+                Ok(Bytes::from("world")),
+            ]);
+
+            let result = api.publish_streamed_response("0000-0001".to_string(), "text/plain", chunks).await;
+            assert_eq!(Ok(()), result);
+            streaming_endpoint.assert();
+        }
+
+        #[tokio::test]
+        async fn should_send_the_error_trailers_when_the_stream_fails() {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+            let listener = tokio::net::TcpListener::bind("localhost:0").await.unwrap();
+            let endpoint = format!("localhost:{}", listener.local_addr().unwrap().port());
+            let server = tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut raw_request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !is_complete(&raw_request) {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    assert_ne!(0, read, "the request ended before its last chunk");
+                    raw_request.extend_from_slice(&buffer[..read]);
+                }
+                socket.write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n").await.unwrap();
+                String::from_utf8(raw_request).unwrap()
+            });
+
+            let api = LambdaApiClient::create(Config { endpoint, ..Config::default() });
+            let chunks = futures_util::stream::iter(vec![
+                Ok::<_, Error>(Bytes::from("Hello, ")),
+                Err(Error::from("boom")),
+            ]);
+
+            let result = api.publish_streamed_response("0000-0001".to_string(), "text/plain", chunks).await;
+            assert_eq!(Ok(()), result);
+
+            let raw_request = server.await.unwrap();
+            let trailers = create_error_trailers(&Error::from("boom"));
+            let (_, body) = raw_request.split_once("\r\n\r\n").unwrap();
+            assert_eq!(
+                format!(
+                    "7\r\nHello, \r\n0\r\n{}: {}\r\n{}: {}\r\n\r\n",
+                    ERROR_TYPE_TRAILER.to_lowercase(), trailers[ERROR_TYPE_TRAILER].to_str().unwrap(),
+                    ERROR_BODY_TRAILER.to_lowercase(), trailers[ERROR_BODY_TRAILER].to_str().unwrap()),
+                body
+            );
+        }
+
+        fn is_complete(raw_request: &[u8]) -> bool {
+            let raw_request = String::from_utf8_lossy(raw_request);
+            raw_request.contains("\r\n0\r\n") && raw_request.ends_with("\r\n\r\n")
+        }
+
+        #[test]
+        fn should_describe_interruptions_as_trailers() {
+            let trailers = create_error_trailers(&Error::from("boom"));
+
+            assert_eq!("mu_runtime::error::Error", trailers[ERROR_TYPE_TRAILER]);
+            let error_body = base64::decode(trailers[ERROR_BODY_TRAILER].as_bytes()).unwrap();
+            assert_eq!(
+                r#"{"errorType":"mu_runtime::error::Error","errorMessage":"boom"}"#,
+                String::from_utf8(error_body).unwrap()
+            );
+        }
     }
 
     mod publish_error_response {
@@ -360,10 +941,8 @@ mod tests {
 
         #[tokio::test]
         async fn should_handle_failures() {
-            let unreachable_host = "localhost:4312";
-
             let api = LambdaApiClient::create(Config {
-                endpoint: unreachable_host.to_string(),
+                endpoint: testing::unreachable_endpoint(),
                 ..Config::default()
             });
 
@@ -371,7 +950,8 @@ mod tests {
                 "0000-0001".to_string(),
                 PublishErrorRequest {
                     error_type: "CompileError".to_string(),
-                    error_message: "Not implemented".to_string()
+                    error_message: "Not implemented".to_string(),
+                    stack_trace: None,
                 }).await;
 
             match result {
@@ -404,7 +984,8 @@ mod tests {
                 "0000-0001".to_string(),
                 PublishErrorRequest {
                     error_type: "CompileError".to_string(),
-                    error_message: "Not implemented".to_string()
+                    error_message: "Not implemented".to_string(),
+                    stack_trace: None,
                 }).await;
 
             if let Err(cause) = result {
@@ -413,5 +994,149 @@ mod tests {
 
             error_endpoint.assert();
         }
+
+        #[test]
+        fn should_omit_stack_trace_when_absent() {
+            let payload = PublishErrorRequest {
+                error_type: "CompileError".to_string(),
+                error_message: "Not implemented".to_string(),
+                stack_trace: None,
+            };
+
+            assert_eq!(
+                r#"{"errorType":"CompileError","errorMessage":"Not implemented"}"#,
+                serde_json::to_string(&payload).unwrap()
+            );
+        }
+
+        #[test]
+        fn should_serialize_stack_trace_when_present() {
+            let payload = PublishErrorRequest {
+                error_type: "Panic".to_string(),
+                error_message: "boom".to_string(),
+                stack_trace: Some(vec!["0: main".to_string(), "1: start".to_string()]),
+            };
+
+            assert_eq!(
+                r#"{"errorType":"Panic","errorMessage":"boom","stackTrace":["0: main","1: start"]}"#,
+                serde_json::to_string(&payload).unwrap()
+            );
+        }
     }
-}
\ No newline at end of file
+
+    mod interceptors {
+
+        use super::*;
+        use std::sync::{Arc, Mutex};
+
+        #[tokio::test]
+        async fn should_notify_requests_and_responses_exchanged_with_the_api() {
+            let mock_server = MockServer::start();
+
+            mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/next");
+                then.status(200)
+                    .header("lambda-runtime-aws-request-id", "0000-0001")
+                    .header("lambda-runtime-deadline-ms", "1000")
+                    .header("lambda-runtime-invoked-function-arn", "arn::something")
+                    .header("lambda-runtime-trace-id", "0001-0001")
+                    .body("{}");
+            });
+
+            mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/0000-0001/response");
+                then.status(202);
+            });
+
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let responses = Arc::new(Mutex::new(Vec::new()));
+            let (sent, received) = (requests.clone(), responses.clone());
+
+            let api = LambdaApiClient::create(Config {
+                endpoint: format!("localhost:{}", mock_server.port()),
+                ..Config::default()
+            })
+                .on_request(move |parts| sent.lock().unwrap()
+                    .push(format!("{} {}", parts.method, parts.uri.path())))
+                .on_response(move |uri, parts| received.lock().unwrap()
+                    .push(format!("{} {}", uri.path(), parts.status.as_u16())));
+
+            let (_, ctx) = api.fetch_next_message().await.unwrap();
+            api.publish_response(ctx.request_id, "42").await.unwrap();
+
+            assert_eq!(vec![
+                "GET /2018-06-01/runtime/invocation/next",
+                "POST /2018-06-01/runtime/invocation/0000-0001/response",
+            ], *requests.lock().unwrap());
+            assert_eq!(vec![
+                "/2018-06-01/runtime/invocation/next 200",
+                "/2018-06-01/runtime/invocation/0000-0001/response 202",
+            ], *responses.lock().unwrap());
+        }
+    }
+
+    mod custom_connector {
+
+        use super::*;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::task::{Context, Poll};
+        use hyper::client::HttpConnector;
+        use hyper::service::Service;
+        use hyper::Uri;
+
+        /// Counts the connections established through the wrapped [HttpConnector].
+        #[derive(Clone)]
+        struct CountingConnector {
+            inner: HttpConnector,
+            connections: Arc<AtomicUsize>,
+        }
+
+        impl Service<Uri> for CountingConnector {
+            type Response = <HttpConnector as Service<Uri>>::Response;
+            type Error = <HttpConnector as Service<Uri>>::Error;
+            type Future = <HttpConnector as Service<Uri>>::Future;
+
+            fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                self.inner.poll_ready(cx)
+            }
+
+            fn call(&mut self, uri: Uri) -> Self::Future {
+                self.connections.fetch_add(1, Ordering::SeqCst);
+                self.inner.call(uri)
+            }
+        }
+
+        #[tokio::test]
+        async fn should_reach_the_api_through_the_given_connector() {
+            let mock_server = MockServer::start();
+
+            let next_endpoint = mock_server.mock(|when, then| {
+                when.path("/2018-06-01/runtime/invocation/next");
+                then.status(200)
+                    .header("lambda-runtime-aws-request-id", "0000-0001")
+                    .header("lambda-runtime-deadline-ms", "1000")
+                    .header("lambda-runtime-invoked-function-arn", "arn::something")
+                    .header("lambda-runtime-trace-id", "0001-0001")
+                    .body("{}");
+            });
+
+            let connections = Arc::new(AtomicUsize::new(0));
+            let connector = CountingConnector {
+                inner: HttpConnector::new(),
+                connections: connections.clone(),
+            };
+
+            let api = LambdaApiClient::with_connector(connector, Config {
+                endpoint: format!("localhost:{}", mock_server.port()),
+                ..Config::default()
+            });
+
+            let (_, ctx) = api.fetch_next_message().await.unwrap();
+
+            assert_eq!("0000-0001", ctx.request_id);
+            assert_eq!(1, connections.load(Ordering::SeqCst));
+            next_endpoint.assert();
+        }
+    }
+}
@@ -4,6 +4,12 @@
 //!
 //! The idea behind this crate is to provide an easy-to-use api for AWS Serverless Developers,
 //! leveraging enterprise-grade semantics in the powerful Rust ecosystem.
+//!
+//! ## Features
+//! - `test-util`: exposes [Context::new_for_test] and the `testing` module, easing the unit
+//!   testing of handlers.
+//! - `record`: records the received invocations when `MU_RECORD_INVOCATIONS` is set,
+//!   so they can be replayed locally through the `record` module.
 
 pub use runtime::*;
 pub use model::Context;
@@ -14,4 +20,15 @@ pub mod runtime;
 pub mod model;
 pub mod lambda_api;
 pub mod error;
+pub mod env;
+pub mod sqs;
+pub mod attributes;
+pub mod websocket;
+pub mod panic;
+pub mod metrics;
+#[cfg(feature = "record")]
+pub mod record;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
@@ -0,0 +1,109 @@
+//! Custom CloudWatch metrics, published through the [Embedded Metric Format] (EMF).
+//!
+//! Metrics are printed to the standard output as specially-structured JSON lines,
+//! which CloudWatch Logs turns into metrics asynchronously. No extra request is made
+//! by the function, thus emitting them is cheap. Lines emitted while an invocation is
+//! being processed carry its request id as a `requestId` property.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::sqs::SqsEvent;
+//! use mu_runtime::metrics;
+//!
+//! async fn handle_sqs_messages(sqs_events: SqsEvent) -> Result<(), mu_runtime::Error> {
+//!   metrics::emit("Orders", &[("Queue", "orders")], &[
+//!     ("ProcessedMessages", sqs_events.records.len() as f64, "Count"),
+//!   ]);
+//!   Ok(())
+//! }
+//! ```
+//!
+//! [Embedded Metric Format]: https://docs.aws.amazon.com/AmazonCloudWatch/latest/monitoring/CloudWatch_Embedded_Metric_Format_Specification.html
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Map, Value};
+
+use crate::panic;
+
+/// Emits the given `metrics` (name, value and unit - e.g. `Count` or `Milliseconds`)
+/// under `namespace`, tagged with the given `dimensions` (name and value).
+pub fn emit(namespace: &str, dimensions: &[(&str, &str)], metrics: &[(&str, f64, &str)]) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let request_id = panic::current_request_id();
+    let line = create_metric_line(
+        namespace, dimensions, metrics, timestamp.as_millis() as u64, request_id.as_deref());
+    println!("{}", line);
+}
+
+/// Creates an EMF log line. Dimensions and metrics are stored as properties of
+/// the root object, as referenced by the `_aws` metadata block.
+fn create_metric_line(
+    namespace: &str,
+    dimensions: &[(&str, &str)],
+    metrics: &[(&str, f64, &str)],
+    timestamp: u64,
+    request_id: Option<&str>,
+) -> Value {
+    let dimension_names: Vec<&str> = dimensions.iter().map(|(name, _)| *name).collect();
+    let definitions: Vec<Value> = metrics.iter()
+        .map(|(name, _, unit)| json!({ "Name": name, "Unit": unit }))
+        .collect();
+
+    let mut line = Map::new();
+    line.insert("_aws".to_string(), json!({
+        "Timestamp": timestamp,
+        "CloudWatchMetrics": [{
+            "Namespace": namespace,
+            "Dimensions": [dimension_names],
+            "Metrics": definitions,
+        }]
+    }));
+
+    for (name, value) in dimensions {
+        line.insert(name.to_string(), json!(value));
+    }
+    for (name, value, _) in metrics {
+        line.insert(name.to_string(), json!(value));
+    }
+    if let Some(request_id) = request_id {
+        line.insert("requestId".to_string(), json!(request_id));
+    }
+
+    Value::Object(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::metrics::create_metric_line;
+
+    #[test]
+    fn should_create_emf_lines_for_a_single_metric() {
+        let line = create_metric_line(
+            "Orders", &[("Queue", "orders")], &[("ProcessedMessages", 3.0, "Count")],
+            1620632235000, Some("0000-0001"));
+
+        assert_eq!(json!({
+            "_aws": {
+                "Timestamp": 1620632235000u64,
+                "CloudWatchMetrics": [{
+                    "Namespace": "Orders",
+                    "Dimensions": [["Queue"]],
+                    "Metrics": [{ "Name": "ProcessedMessages", "Unit": "Count" }]
+                }]
+            },
+            "Queue": "orders",
+            "ProcessedMessages": 3.0,
+            "requestId": "0000-0001"
+        }), line);
+    }
+
+    #[test]
+    fn should_omit_the_request_id_outside_invocations() {
+        let line = create_metric_line("Orders", &[], &[("Latency", 12.5, "Milliseconds")], 0, None);
+
+        assert_eq!(None, line.get("requestId"));
+        assert_eq!(json!([[]]), line["_aws"]["CloudWatchMetrics"][0]["Dimensions"]);
+    }
+}
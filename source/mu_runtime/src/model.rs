@@ -5,8 +5,17 @@
 //! original authors.
 
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Once;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use hyper::body::Bytes;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::env;
+use crate::error::Result;
 
 #[non_exhaustive]
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
@@ -30,6 +39,258 @@ pub struct Context {
     /// Includes information such as the function name, memory allocation,
     /// version, and log streams.
     pub env_config: Config,
+    /// Whether this is the first invocation handled by the runtime, thus
+    /// paying for the initialization of the execution environment.
+    #[serde(default)]
+    pub(crate) cold_start: bool,
+    /// The environment variables read through [Context::env], in place of the
+    /// real environment. Only injected on unit tests.
+    #[serde(skip)]
+    pub(crate) env_override: Option<HashMap<String, String>>,
+}
+
+impl Context {
+
+    /// Looks up a custom property of the client context sent by the AWS Mobile SDK.
+    pub fn client_custom(&self, key: &str) -> Option<&str> {
+        self.client_context.as_ref()?
+            .custom.get(key)
+            .map(String::as_str)
+    }
+
+    /// Deserializes the custom properties of the client context (e.g. the metadata an
+    /// orchestrator attaches to nested invocations) into `T`. Properties holding a JSON
+    /// object or array are deserialized as such, allowing nested structures, while the
+    /// others are kept as strings. Yields `None` when there's no client context.
+    pub fn custom_context<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        let client_context = match &self.client_context {
+            None => return Ok(None),
+            Some(client_context) => client_context,
+        };
+
+        let custom = client_context.custom.iter()
+            .map(|(key, value)| (key.clone(), parse_custom_property(value)))
+            .collect();
+        Ok(Some(serde_json::from_value(Value::Object(custom))?))
+    }
+
+    /// The Cognito identity id of the caller, if any.
+    pub fn cognito_identity_id(&self) -> Option<&str> {
+        self.identity.as_ref()
+            .map(|identity| identity.identity_id.as_str())
+    }
+
+    /// Reads the environment variable `key`. Handlers reading their settings through
+    /// it, rather than `std::env::var`, can be unit tested against a fake environment
+    /// (see `Context::with_env`, available with the `test-util` feature).
+    pub fn env(&self, key: &str) -> Option<String> {
+        match &self.env_override {
+            Some(vars) => vars.get(key).cloned(),
+            None => std::env::var(key).ok(),
+        }
+    }
+
+    /// Whether this is the first invocation handled by this process (a cold start).
+    pub fn is_cold_start(&self) -> bool {
+        self.cold_start
+    }
+
+    /// The time left until the invocation [deadline](Context::deadline) is reached.
+    pub fn remaining_time(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Duration::from_millis(self.deadline).saturating_sub(now)
+    }
+
+    /// The components of the [invoked_function_arn](Context::invoked_function_arn),
+    /// e.g. to build the ARN of other resources on the same account and region.
+    /// Malformed ARNs yield `None`.
+    pub fn parsed_arn(&self) -> Option<FunctionArn> {
+        FunctionArn::parse(&self.invoked_function_arn)
+    }
+
+    /// The invocation [deadline](Context::deadline) as a [SystemTime], easing the
+    /// interop with scheduling and timeout libraries.
+    pub fn deadline_time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.deadline)
+    }
+}
+
+/// Helpers to create a [Context] on unit tests, without relying on the
+/// environment variables populated by the AWS Lambda platform.
+#[cfg(feature = "test-util")]
+impl Context {
+
+    /// Creates a context populated with dummy values. Its deadline is set three
+    /// seconds from now, mimicking the default timeout of a Lambda function.
+    pub fn new_for_test() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Context {
+            request_id: "00000000-0000-0000-0000-000000000000".to_string(),
+            deadline: (now + Duration::from_secs(3)).as_millis() as u64,
+            invoked_function_arn: "arn:aws:lambda:us-east-1:000000000000:function:test-function".to_string(),
+            xray_trace_id: "Root=1-00000000-000000000000000000000000;Sampled=0".to_string(),
+            client_context: None,
+            identity: None,
+            env_config: Config {
+                endpoint: "localhost:9001".to_string(),
+                function_name: "test-function".to_string(),
+                memory: 128,
+                version: "$LATEST".to_string(),
+                log_stream: Some("2000/01/01/[$LATEST]00000000000000000000000000000000".to_string()),
+                log_group: Some("/aws/lambda/test-function".to_string()),
+                ..Config::default()
+            },
+            cold_start: false,
+            env_override: None,
+        }
+    }
+
+    /// Replaces the environment read through [Context::env] with the given variables.
+    pub fn with_env(mut self, vars: HashMap<String, String>) -> Self {
+        self.env_override = Some(vars);
+        self
+    }
+
+    /// Overrides the request id.
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        self.request_id = request_id.to_string();
+        self
+    }
+
+    /// Overrides the execution deadline, in milliseconds since the epoch.
+    pub fn with_deadline(mut self, deadline: u64) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Overrides the ARN of the function being invoked.
+    pub fn with_invoked_function_arn(mut self, arn: &str) -> Self {
+        self.invoked_function_arn = arn.to_string();
+        self
+    }
+
+    /// Overrides the X-Ray trace id.
+    pub fn with_xray_trace_id(mut self, xray_trace_id: &str) -> Self {
+        self.xray_trace_id = xray_trace_id.to_string();
+        self
+    }
+
+    /// Overrides the function configuration.
+    pub fn with_env_config(mut self, env_config: Config) -> Self {
+        self.env_config = env_config;
+        self
+    }
+
+    /// Overrides the cold start flag.
+    pub fn with_cold_start(mut self, cold_start: bool) -> Self {
+        self.cold_start = cold_start;
+        self
+    }
+}
+
+fn parse_custom_property(value: &str) -> Value {
+    match serde_json::from_str(value) {
+        Ok(nested @ Value::Object(_)) | Ok(nested @ Value::Array(_)) => nested,
+        _ => Value::String(value.to_string()),
+    }
+}
+
+/// The components of a Lambda function ARN, like
+/// `arn:aws:lambda:us-east-1:123456789012:function:my-func:prod`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionArn {
+    /// The partition of the function (e.g. `aws`, `aws-cn`).
+    pub partition: String,
+    /// The region of the function.
+    pub region: String,
+    /// The AWS account owning the function.
+    pub account_id: String,
+    /// The name of the function.
+    pub function_name: String,
+    /// The alias or version (qualifier) the function was invoked through, if any.
+    pub qualifier: Option<String>,
+}
+
+impl FunctionArn {
+
+    /// Parses a function ARN, yielding `None` when malformed.
+    pub fn parse(arn: &str) -> Option<Self> {
+        let segments: Vec<&str> = arn.split(':').collect();
+        let (partition, region, account_id, function_name, qualifier) = match segments.as_slice() {
+            ["arn", partition, "lambda", region, account_id, "function", function_name] =>
+                (partition, region, account_id, function_name, None),
+            ["arn", partition, "lambda", region, account_id, "function", function_name, qualifier] =>
+                (partition, region, account_id, function_name, Some(qualifier)),
+            _ => return None,
+        };
+
+        let is_blank = [partition, region, account_id, function_name].iter().any(|s| s.is_empty())
+            || qualifier.is_some_and(|q| q.is_empty());
+        if is_blank {
+            return None
+        }
+
+        Some(FunctionArn {
+            partition: partition.to_string(),
+            region: region.to_string(),
+            account_id: account_id.to_string(),
+            function_name: function_name.to_string(),
+            qualifier: qualifier.map(|q| q.to_string()),
+        })
+    }
+
+    /// The alias the function was invoked through, if any.
+    pub fn alias(&self) -> Option<&str> {
+        self.qualifier.as_deref()
+            .filter(|qualifier| !is_version(qualifier))
+    }
+
+    /// The version the function was invoked through, if any (e.g. `42` or `$LATEST`).
+    pub fn version(&self) -> Option<&str> {
+        self.qualifier.as_deref()
+            .filter(|qualifier| is_version(qualifier))
+    }
+}
+
+fn is_version(qualifier: &str) -> bool {
+    qualifier == "$LATEST" || qualifier.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Figures of a completed invocation, handed to the callback registered through
+/// [LambdaApiClient::on_complete](crate::lambda_api::LambdaApiClient::on_complete).
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvocationStats {
+    /// The AWS request ID of the invocation.
+    pub request_id: String,
+    /// Whether it was the first invocation handled by this process.
+    pub cold_start: bool,
+    /// The wall time taken by the handler.
+    pub duration: Duration,
+}
+
+/// A pre-serialized response, published verbatim to the Lambda Runtime API.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawResponse {
+    /// The media type of the `body`.
+    pub content_type: String,
+    /// The response payload.
+    pub body: Bytes,
+}
+
+impl RawResponse {
+
+    /// Creates a raw response with the given content type and body.
+    pub fn new<B: Into<Bytes>>(content_type: &str, body: B) -> Self {
+        RawResponse {
+            content_type: content_type.to_string(),
+            body: body.into(),
+        }
+    }
 }
 
 /// Client context sent by the AWS Mobile SDK.
@@ -65,8 +326,9 @@ pub struct ClientApplication {
     pub app_package_name: String,
 }
 
-/// Cognito identity information sent with the event
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+/// Cognito identity information sent with the event. Its `Debug` output redacts
+/// the identity id, so contexts can be logged without leaking it.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct CognitoIdentity {
     /// The unique identity id for the Cognito credentials invoking the function.
     #[serde(alias = "identityId")]
@@ -76,8 +338,31 @@ pub struct CognitoIdentity {
     pub identity_pool_id: String,
 }
 
-/// Configuration derived from environment variables.
+impl fmt::Debug for CognitoIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CognitoIdentity")
+            .field("identity_id", &"<redacted>")
+            .field("identity_pool_id", &self.identity_pool_id)
+            .finish()
+    }
+}
+
+/// Configuration derived from environment variables. New fields may be added over
+/// time, thus it is built through [Config::from_env], or [Config::default], and the
+/// `with_*` methods rather than struct literals.
+///
+/// ```
+/// use std::time::Duration;
+/// use mu_runtime::model::Config;
+///
+/// let config = Config::default()
+///     .with_endpoint("localhost:9001")
+///     .with_max_invocations(10)
+///     .with_slow_invocation_threshold(Duration::from_secs(1));
+/// assert_eq!(Some(10), config.max_invocations);
+/// ```
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Config {
     /// The host and port of the [runtime API](https://docs.aws.amazon.com/lambda/latest/dg/runtimes-api.html).
     pub endpoint: String,
@@ -87,8 +372,407 @@ pub struct Config {
     pub memory: i32,
     /// The version of the function being executed.
     pub version: String,
-    /// The name of the Amazon CloudWatch Logs stream for the function.
-    pub log_stream: String,
-    /// The name of the Amazon CloudWatch Logs group for the function.
-    pub log_group: String,
+    /// The name of the Amazon CloudWatch Logs stream for the function, when informed.
+    pub log_stream: Option<String>,
+    /// The name of the Amazon CloudWatch Logs group for the function, when informed.
+    pub log_group: Option<String>,
+    /// Handler executions taking longer than this will be reported with
+    /// a `WARN` log entry. Disabled when `None`.
+    pub slow_invocation_threshold: Option<Duration>,
+    /// The number of events processed before the runtime loop exits cleanly,
+    /// letting the platform recycle the execution environment. Unlimited when `None`.
+    pub max_invocations: Option<usize>,
+    /// When the remaining time of an invocation drops below this threshold, a
+    /// `WARN` log entry is emitted and the callback registered through
+    /// [LambdaApiClient::on_timeout_imminent] notified. Disabled when `None`.
+    ///
+    /// [LambdaApiClient::on_timeout_imminent]: crate::lambda_api::LambdaApiClient::on_timeout_imminent
+    pub timeout_warning_threshold: Option<Duration>,
+    /// The response of the last successfully processed invocation is kept for this
+    /// long. Should the same invocation be received again meanwhile (e.g. after the
+    /// Runtime API rejected its response), the response is re-published rather than
+    /// the handler re-invoked. This is a best-effort guard against double-processing:
+    /// only the last invocation is remembered, in memory, thus it is no dedup store.
+    /// It doesn't outlive the runtime loop either, which ends when the Runtime API
+    /// can't be reached at all. Disabled when `None`.
+    pub duplicate_invocation_window: Option<Duration>,
+    /// The ceiling of the long poll for the next invocation, up to the arrival of its
+    /// headers. Once it elapses, fetching fails with [Error::FetchTimeout](crate::Error::FetchTimeout)
+    /// and the runtime loop polls again, rather than hanging on a wedged Runtime API. The loop
+    /// gives up, returning that error, after a few consecutive timeouts.
+    /// Set to [DEFAULT_FETCH_TIMEOUT] when read from the environment. Disabled when `None`.
+    pub fetch_timeout: Option<Duration>,
+}
+
+/// The memory size (in MB) assumed by [Config::from_env_or_defaults] when
+/// `AWS_LAMBDA_FUNCTION_MEMORY_SIZE` is missing.
+pub const DEFAULT_MEMORY_SIZE: i32 = 128;
+
+/// The environment variable enabling the [summary](Config::summary) of the configuration
+/// to be logged when the runtime starts listening to events (e.g. `MU_LOG_CONFIG=1`).
+pub const LOG_CONFIG_ENV: &str = "MU_LOG_CONFIG";
+
+static SUMMARY_LOGGED: Once = Once::new();
+
+/// The ceiling of the long poll for the next invocation assumed when reading the
+/// configuration from the environment. Generously large, as the poll legitimately
+/// blocks until an event arrives.
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+impl Config {
+
+    /// Creates a new configuration from the reserved environment variables
+    /// populated by the AWS Lambda platform.
+    pub fn from_env() -> Self {
+        Config::read_env(false).unwrap()
+    }
+
+    /// Same as [Config::from_env], but reporting missing or malformed
+    /// variables as an [Error::Config](crate::Error::Config) rather than panicking.
+    pub fn try_from_env() -> Result<Self> {
+        Config::read_env(false)
+    }
+
+    /// Same as [Config::try_from_env], but a missing or empty `AWS_LAMBDA_FUNCTION_MEMORY_SIZE`
+    /// falls back to [DEFAULT_MEMORY_SIZE]. Meant for local runs, as some emulators leave it out.
+    pub fn from_env_or_defaults() -> Result<Self> {
+        Config::read_env(true)
+    }
+
+    fn read_env(allow_defaults: bool) -> Result<Self> {
+        let memory = match allow_defaults {
+            true => env::get_non_empty_or("AWS_LAMBDA_FUNCTION_MEMORY_SIZE", DEFAULT_MEMORY_SIZE),
+            false => env::get_non_empty("AWS_LAMBDA_FUNCTION_MEMORY_SIZE"),
+        };
+
+        Ok(Config {
+            endpoint: env::require("AWS_LAMBDA_RUNTIME_API")?,
+            function_name: env::require("AWS_LAMBDA_FUNCTION_NAME")?,
+            memory: memory?,
+            version: env::require("AWS_LAMBDA_FUNCTION_VERSION")?,
+            // only informational, thus absent on a few emulators
+            log_stream: env::get_non_empty("AWS_LAMBDA_LOG_STREAM_NAME").ok(),
+            log_group: env::get_non_empty("AWS_LAMBDA_LOG_GROUP_NAME").ok(),
+            slow_invocation_threshold: None,
+            max_invocations: None,
+            timeout_warning_threshold: None,
+            duplicate_invocation_window: None,
+            fetch_timeout: Some(DEFAULT_FETCH_TIMEOUT),
+        })
+    }
+
+    /// Overrides the host and port of the runtime API.
+    pub fn with_endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    /// Overrides the name of the function.
+    pub fn with_function_name(mut self, function_name: &str) -> Self {
+        self.function_name = function_name.to_string();
+        self
+    }
+
+    /// Overrides the amount of memory available to the function in MB.
+    pub fn with_memory(mut self, memory: i32) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Overrides the version of the function being executed.
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Sets the name of the Amazon CloudWatch Logs stream for the function.
+    pub fn with_log_stream(mut self, log_stream: &str) -> Self {
+        self.log_stream = Some(log_stream.to_string());
+        self
+    }
+
+    /// Sets the name of the Amazon CloudWatch Logs group for the function.
+    pub fn with_log_group(mut self, log_group: &str) -> Self {
+        self.log_group = Some(log_group.to_string());
+        self
+    }
+
+    /// Reports handler executions taking longer than `threshold`. See [Config::slow_invocation_threshold].
+    pub fn with_slow_invocation_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_invocation_threshold = Some(threshold);
+        self
+    }
+
+    /// Exits the runtime loop after `max_invocations` events. See [Config::max_invocations].
+    pub fn with_max_invocations(mut self, max_invocations: usize) -> Self {
+        self.max_invocations = Some(max_invocations);
+        self
+    }
+
+    /// Warns about invocations about to time out. See [Config::timeout_warning_threshold].
+    pub fn with_timeout_warning_threshold(mut self, threshold: Duration) -> Self {
+        self.timeout_warning_threshold = Some(threshold);
+        self
+    }
+
+    /// Re-publishes invocations received again within `window`. See [Config::duplicate_invocation_window].
+    pub fn with_duplicate_invocation_window(mut self, window: Duration) -> Self {
+        self.duplicate_invocation_window = Some(window);
+        self
+    }
+
+    /// Overrides the ceiling of the long poll for the next invocation, disabling it when `None`.
+    /// See [Config::fetch_timeout].
+    pub fn with_fetch_timeout(mut self, fetch_timeout: Option<Duration>) -> Self {
+        self.fetch_timeout = fetch_timeout;
+        self
+    }
+
+    /// Describes the configuration in a single JSON line, easing the diagnosis of
+    /// deployment issues. Only fields known to be harmless are included, thus it is
+    /// safe to be logged.
+    pub fn summary(&self) -> String {
+        serde_json::json!({
+            "function_name": self.function_name,
+            "version": self.version,
+            "memory_mb": self.memory,
+            "endpoint": self.endpoint,
+            "log_group": self.log_group,
+            "log_stream": self.log_stream,
+            "max_invocations": self.max_invocations,
+            "fetch_timeout_ms": self.fetch_timeout.map(|timeout| timeout.as_millis() as u64),
+        }).to_string()
+    }
+
+    /// Logs the [summary](Config::summary) of the configuration with an `INFO` entry.
+    /// It is logged once per execution environment, no matter how often it is called.
+    pub fn log_summary(&self) {
+        SUMMARY_LOGGED.call_once(|| log::info!("Effective configuration: {}", self.summary()));
+    }
+
+    /// Logs the summary of the configuration, as long as [LOG_CONFIG_ENV] is enabled.
+    pub(crate) fn log_summary_from_env(&self) {
+        let enabled = std::env::var(LOG_CONFIG_ENV)
+            .map(|value| matches!(value.as_str(), "1" | "true"))
+            .unwrap_or(false);
+        if enabled {
+            self.log_summary()
+        }
+    }
+}
+
+#[cfg(test)]
+mod config_summary {
+    use crate::model::Config;
+    use crate::testing;
+
+    #[test]
+    fn should_log_the_summary_once() {
+        testing::init_logger();
+        let config = Config {
+            endpoint: "127.0.0.1:9001".to_string(),
+            function_name: "mu-0653".to_string(),
+            memory: 256,
+            version: "$LATEST".to_string(),
+            ..Config::default()
+        };
+
+        config.log_summary();
+        config.log_summary();
+
+        let summaries: Vec<String> = testing::captured_logs(log::Level::Info).into_iter()
+            .filter(|entry| entry.contains("mu-0653"))
+            .collect();
+        assert_eq!(1, summaries.len(), "Unexpected: {:?}", summaries);
+        assert!(summaries[0].contains(r#""function_name":"mu-0653""#));
+        assert!(summaries[0].contains(r#""memory_mb":256"#));
+    }
+}
+
+#[cfg(test)]
+mod accessors {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::model::{ClientContext, CognitoIdentity, Context, FunctionArn};
+
+    const COGNITO_CLIENT_CONTEXT: &str = include_str!("../tests/sample_cognito_context.json");
+    const COGNITO_IDENTITY: &str = include_str!("../tests/sample_cognito_identity.json");
+
+    #[test]
+    fn should_read_custom_client_context_properties() {
+        let ctx = Context {
+            client_context: Some(serde_json::from_str(COGNITO_CLIENT_CONTEXT).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(Some("CustomValue1"), ctx.client_custom("CustomKey1"));
+        assert_eq!(None, ctx.client_custom("UnknownKey"));
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct CustomContext {
+        custom_key1: String,
+        custom_key2: String,
+    }
+
+    #[test]
+    fn should_deserialize_the_custom_client_context() {
+        let ctx = Context {
+            client_context: Some(serde_json::from_str(COGNITO_CLIENT_CONTEXT).unwrap()),
+            ..Default::default()
+        };
+
+        let custom: Option<CustomContext> = ctx.custom_context().unwrap();
+        assert_eq!(
+            Some(CustomContext { custom_key1: "CustomValue1".to_string(), custom_key2: "CustomValue2".to_string() }),
+            custom
+        );
+        assert_eq!(None, Context::default().custom_context::<CustomContext>().unwrap());
+    }
+
+    #[test]
+    fn should_deserialize_nested_custom_properties() {
+        #[derive(serde::Deserialize)]
+        struct Orchestration {
+            execution: Execution,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Execution {
+            attempt: u32,
+        }
+
+        let mut client_context: ClientContext = serde_json::from_str(COGNITO_CLIENT_CONTEXT).unwrap();
+        client_context.custom.insert("execution".to_string(), r#"{"attempt":3}"#.to_string());
+        let ctx = Context { client_context: Some(client_context), ..Default::default() };
+
+        let orchestration: Orchestration = ctx.custom_context().unwrap().unwrap();
+        assert_eq!(3, orchestration.execution.attempt);
+    }
+
+    #[test]
+    fn should_read_cognito_identity_id() {
+        let ctx = Context {
+            identity: Some(serde_json::from_str(COGNITO_IDENTITY).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(Some("Id1"), ctx.cognito_identity_id());
+    }
+
+    #[test]
+    fn should_convert_the_deadline_into_a_system_time() {
+        let ctx = Context {
+            deadline: 1_620_000_000_123,
+            ..Default::default()
+        };
+
+        let expected = UNIX_EPOCH + Duration::new(1_620_000_000, 123_000_000);
+        assert_eq!(expected, ctx.deadline_time());
+    }
+
+    #[test]
+    fn should_clone_contexts_into_equal_values() {
+        let ctx = Context {
+            request_id: "0000-0001".to_string(),
+            identity: Some(serde_json::from_str(COGNITO_IDENTITY).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(ctx, ctx.clone());
+    }
+
+    #[test]
+    fn should_redact_cognito_identity_ids_when_debugging() {
+        let ctx = Context {
+            identity: Some(CognitoIdentity {
+                identity_id: "us-east-1:secret-identity".to_string(),
+                identity_pool_id: "us-east-1:pool".to_string(),
+            }),
+            ..Default::default()
+        };
+
+        let debugged = format!("{:?}", ctx);
+        assert!(!debugged.contains("secret-identity"));
+        assert!(debugged.contains("identity_id: \"<redacted>\""));
+        assert!(debugged.contains("us-east-1:pool"));
+    }
+
+    #[test]
+    fn should_parse_the_invoked_function_arn() {
+        let ctx = Context {
+            invoked_function_arn: "arn:aws:lambda:us-east-1:123456789012:function:my-func:prod".to_string(),
+            ..Default::default()
+        };
+
+        let arn = ctx.parsed_arn().unwrap();
+        assert_eq!("aws", arn.partition);
+        assert_eq!("us-east-1", arn.region);
+        assert_eq!("123456789012", arn.account_id);
+        assert_eq!("my-func", arn.function_name);
+        assert_eq!(Some("prod"), arn.alias());
+        assert_eq!(None, arn.version());
+    }
+
+    #[test]
+    fn should_tell_versions_apart_from_aliases() {
+        let arn = FunctionArn::parse("arn:aws:lambda:us-east-1:123456789012:function:my-func:42").unwrap();
+        assert_eq!(Some("42"), arn.version());
+        assert_eq!(None, arn.alias());
+
+        let arn = FunctionArn::parse("arn:aws:lambda:us-east-1:123456789012:function:my-func").unwrap();
+        assert_eq!(None, arn.qualifier);
+    }
+
+    #[test]
+    fn should_not_parse_malformed_arns() {
+        assert_eq!(None, FunctionArn::parse(""));
+        assert_eq!(None, FunctionArn::parse("arn:aws:sqs:us-east-1:123456789012:queue"));
+        assert_eq!(None, FunctionArn::parse("arn:aws:lambda:us-east-1::function:my-func"));
+    }
+
+    #[test]
+    fn should_return_none_when_absent() {
+        let ctx = Context::default();
+
+        assert_eq!(None, ctx.client_custom("CustomKey1"));
+        assert_eq!(None, ctx.cognito_identity_id());
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use crate::model::Context;
+
+    #[test]
+    fn should_create_contexts_for_testing() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let ctx = Context::new_for_test();
+
+        assert!(!ctx.request_id.is_empty());
+        assert!(ctx.deadline > now);
+        assert_eq!("test-function", ctx.env_config.function_name);
+    }
+
+    #[test]
+    fn should_override_fields_of_contexts_for_testing() {
+        let ctx = Context::new_for_test()
+            .with_request_id("0000-0001")
+            .with_deadline(1000);
+
+        assert_eq!("0000-0001", ctx.request_id);
+        assert_eq!(1000, ctx.deadline);
+    }
+
+    #[test]
+    fn should_read_the_injected_environment() {
+        let ctx = Context::new_for_test()
+            .with_env(maplit::hashmap! { "TABLE_NAME".to_string() => "users-test".to_string() });
+
+        assert_eq!(Some("users-test".to_string()), ctx.env("TABLE_NAME"));
+        assert_eq!(None, ctx.env("PATH"));
+    }
 }
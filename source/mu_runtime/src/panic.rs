@@ -0,0 +1,215 @@
+//! A panic hook reporting panics to the Lambda Runtime API, as errors of the active invocation.
+//!
+//! Panics happening in tasks spawned by the handler escape the handler future, thus the
+//! invocation would otherwise only fail once the platform times it out. The hook installed
+//! by [install_panic_hook] makes a best-effort, blocking attempt to publish the panic
+//! (including its location and, when `RUST_BACKTRACE` is set, its stack trace) to the
+//! error endpoint of the invocation being processed.
+//!
+//! Limitations:
+//! - The process may still abort (e.g. `panic = "abort"` profiles), in which case the
+//!   platform reports the failure on its own.
+//! - The active invocation is tracked by the runtime. Tasks spawned by the handler
+//!   are attributed to the last invocation that started, which is only accurate when
+//!   invocations are processed one at a time.
+//! - Once the panic has been reported, publishing a response for the same invocation
+//!   will be rejected by the Runtime API.
+//!
+//! ```no_run
+//! use aws_lambda_events::event::sqs::SqsEvent;
+//! use mu_runtime::model::Config;
+//!
+//! #[tokio::main]
+//! async fn main() -> mu_runtime::RuntimeResult {
+//!   mu_runtime::panic::install_panic_hook(&Config::from_env());
+//!   mu_runtime::listen_events(|sqs_events, ctx| {
+//!     handle_sqs_messages(sqs_events)
+//!   }).await
+//! }
+//!
+//! async fn handle_sqs_messages(sqs_events: SqsEvent) -> Result<(), mu_runtime::Error> {
+//!   tokio::spawn(async { panic!("Reported to the Lambda Runtime API") });
+//!   Ok(())
+//! }
+//! ```
+
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::panic::Location;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::lambda_api::PublishErrorRequest;
+use crate::model::Config;
+
+const PANIC_ERROR_TYPE: &str = "Panic";
+const REPORT_TIMEOUT: Duration = Duration::from_secs(1);
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+static ACTIVE_REQUEST_ID: Mutex<Option<String>> = Mutex::new(None);
+
+/// Installs a panic hook that reports panics to the error endpoint of the active
+/// invocation. The previously installed hook is still called afterwards.
+pub fn install_panic_hook(config: &Config) {
+    let endpoint = config.endpoint.clone();
+    let previous_hook = std::panic::take_hook();
+
+    // the type of `info` is inferred, as it was renamed in Rust 1.81 (`PanicHookInfo`)
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(request_id) = current_request_id() {
+            let report = create_panic_report(&panic_message(info.payload()), info.location(), capture_stack_trace());
+            if let Err(cause) = post_error_report(&endpoint, &request_id, &report) {
+                eprintln!("Unable to report panic of invocation {}: {}", request_id, cause);
+            }
+        }
+        previous_hook(info)
+    }));
+}
+
+/// The request id of the invocation being processed, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+        .or_else(|| ACTIVE_REQUEST_ID.lock().ok().and_then(|id| id.clone()))
+}
+
+/// Runs the given future as part of the invocation identified by `request_id`.
+pub(crate) async fn track_invocation<Fut: Future>(request_id: String, future: Fut) -> Fut::Output {
+    set_active_request_id(Some(request_id.clone()));
+    let output = REQUEST_ID.scope(request_id, future).await;
+    set_active_request_id(None);
+    output
+}
+
+fn set_active_request_id(request_id: Option<String>) {
+    if let Ok(mut active) = ACTIVE_REQUEST_ID.lock() {
+        *active = request_id;
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|msg| msg.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Box<dyn Any>".to_string())
+}
+
+/// Captures the frames of the panicking thread. As with the default hook, it
+/// depends on `RUST_BACKTRACE` being set, yielding `None` otherwise.
+fn capture_stack_trace() -> Option<Vec<String>> {
+    let backtrace = Backtrace::capture();
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(backtrace.to_string().lines().map(str::to_string).collect()),
+        _ => None,
+    }
+}
+
+/// Creates the error report of a panic, e.g. `panicked at src/main.rs:10:5: boom`.
+fn create_panic_report(
+    message: &str, location: Option<&Location<'_>>, stack_trace: Option<Vec<String>>
+) -> PublishErrorRequest {
+    let error_message = match location {
+        Some(location) => format!("panicked at {}:{}:{}: {}",
+                                  location.file(), location.line(), location.column(), message),
+        None => format!("panicked: {}", message),
+    };
+
+    PublishErrorRequest {
+        error_type: PANIC_ERROR_TYPE.to_string(),
+        error_message,
+        stack_trace,
+    }
+}
+
+/// Publishes the report with a blocking HTTP/1.1 request, as panic hooks
+/// can't rely on the async runtime.
+fn post_error_report(endpoint: &str, request_id: &str, report: &PublishErrorRequest) -> std::io::Result<()> {
+    let body = serde_json::to_vec(report)?;
+    let mut stream = TcpStream::connect(endpoint)?;
+    stream.set_read_timeout(Some(REPORT_TIMEOUT))?;
+    stream.set_write_timeout(Some(REPORT_TIMEOUT))?;
+
+    let head = format!(
+        "POST /2018-06-01/runtime/invocation/{}/error HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        request_id, endpoint, body.len());
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&body)?;
+
+    // waits for the Runtime API to acknowledge the report
+    let mut status_line = [0u8; 12];
+    stream.read_exact(&mut status_line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::Location;
+
+    use httpmock::MockServer;
+
+    use crate::lambda_api::PublishErrorRequest;
+    use crate::panic::{create_panic_report, current_request_id, post_error_report, track_invocation};
+
+    #[test]
+    fn should_format_panic_reports_with_their_location() {
+        let location = Location::caller();
+        let report = create_panic_report("boom", Some(location), None);
+
+        assert_eq!("Panic", report.error_type);
+        assert_eq!(
+            format!("panicked at {}:{}:{}: boom", location.file(), location.line(), location.column()),
+            report.error_message
+        );
+    }
+
+    #[test]
+    fn should_format_panic_reports_without_location() {
+        let report = create_panic_report("boom", None, None);
+        assert_eq!("panicked: boom", report.error_message);
+    }
+
+    #[test]
+    fn should_include_the_stack_trace_when_captured() {
+        let stack_trace = vec!["0: my_function".to_string()];
+        let report = create_panic_report("boom", None, Some(stack_trace.clone()));
+        assert_eq!(Some(stack_trace), report.stack_trace);
+    }
+
+    #[tokio::test]
+    async fn should_track_the_active_invocation() {
+        let request_id = track_invocation("0000-0001".to_string(), async {
+            current_request_id()
+        }).await;
+
+        assert_eq!(Some("0000-0001".to_string()), request_id);
+    }
+
+    #[test]
+    fn should_post_reports_to_the_error_endpoint() {
+        let mock_server = MockServer::start();
+        let error_endpoint = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/error")
+                .body(r#"{"errorType":"Panic","errorMessage":"panicked: boom"}"#)
+                .method("POST");
+            then.status(202);
+        });
+
+        let report = PublishErrorRequest {
+            error_type: "Panic".to_string(),
+            error_message: "panicked: boom".to_string(),
+            stack_trace: None,
+        };
+        let endpoint = format!("127.0.0.1:{}", mock_server.port());
+        post_error_report(&endpoint, "0000-0001", &report).unwrap();
+
+        error_endpoint.assert();
+    }
+}
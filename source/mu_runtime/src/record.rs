@@ -0,0 +1,218 @@
+//! Records the invocations received from the Lambda Runtime API, so they can be
+//! replayed locally, reproducing production issues deterministically.
+//!
+//! Invocations are only recorded when the [RECORD_ENV] environment variable points
+//! at the file they should be appended to. Enabling the `record` feature alone never
+//! records anything. Only buffered invocations are recorded, thus the ones consumed
+//! through [listen_streamed_events](crate::listen_streamed_events) are left out.
+//!
+//! ```no_run
+//! use mu_runtime::{record, Context, Error};
+//!
+//! #[tokio::main]
+//! async fn main() -> mu_runtime::RuntimeResult {
+//!   let invocations = record::load("invocations.jsonl")?;
+//!   let result = record::replay(&invocations[0], |payload: String, _ctx: Context| async move {
+//!     Ok::<_, Error>(payload.len())
+//!   }).await?;
+//!   println!("Replayed: {:?}", result);
+//!   Ok(())
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::str::FromStr;
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::HeaderMap;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::lambda_api::LambdaApiClient;
+use crate::model::{Config, Context};
+
+/// The environment variable holding the path of the file invocations are recorded to.
+pub const RECORD_ENV: &str = "MU_RECORD_INVOCATIONS";
+
+/// The headers every invocation is sent with, needed to rebuild its [Context].
+const REQUIRED_HEADERS: [&str; 4] = [
+    "lambda-runtime-aws-request-id",
+    "lambda-runtime-deadline-ms",
+    "lambda-runtime-invoked-function-arn",
+    "lambda-runtime-trace-id",
+];
+
+/// An invocation as received from the Lambda Runtime API.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInvocation {
+    /// The headers of the invocation, carrying its context.
+    pub headers: HashMap<String, String>,
+    /// The raw payload of the invocation, base64 encoded.
+    pub payload: String,
+}
+
+impl RecordedInvocation {
+
+    /// Captures an invocation from its headers and raw payload. Headers whose
+    /// value isn't valid UTF-8 are left out.
+    pub fn new(headers: &HeaderMap, payload: &[u8]) -> Self {
+        let headers = headers.iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        RecordedInvocation {
+            headers,
+            payload: base64::encode(payload),
+        }
+    }
+
+    /// The raw payload of the invocation.
+    pub fn payload(&self) -> Result<Vec<u8>> {
+        base64::decode(&self.payload)
+            .map_err(|cause| Error::Runtime(format!("invalid recorded payload: {}", cause)))
+    }
+
+    fn header_map(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::with_capacity(self.headers.len());
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_str(name)
+                .map_err(|cause| Error::Runtime(format!("invalid recorded header {}: {}", name, cause)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|cause| Error::Runtime(format!("invalid recorded header {}: {}", name, cause)))?;
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+
+    /// Same as [RecordedInvocation::header_map], but failing when the headers the
+    /// [Context] is built from are missing or malformed (e.g. hand-edited recordings).
+    fn context_headers(&self) -> Result<HeaderMap> {
+        let headers = self.header_map()?;
+        if let Some(missing) = REQUIRED_HEADERS.iter().find(|name| !headers.contains_key(**name)) {
+            return Err(Error::Runtime(format!("missing recorded header {}", missing)))
+        }
+        let deadline = &headers["lambda-runtime-deadline-ms"];
+        if deadline.to_str().ok().and_then(|deadline| deadline.parse::<u64>().ok()).is_none() {
+            return Err(Error::Runtime(format!("invalid recorded header lambda-runtime-deadline-ms: {:?}", deadline)))
+        }
+        Ok(headers)
+    }
+}
+
+/// Appends the invocation to the file at `path`, one JSON document per line.
+pub fn record<P: AsRef<Path>>(path: P, invocation: &RecordedInvocation) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = serde_json::to_vec(invocation)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+/// Records the invocation to the file [RECORD_ENV] points at, if any. Failing to
+/// do so is logged, never interrupting the invocation.
+pub(crate) fn record_from_env(headers: &HeaderMap, payload: &[u8]) {
+    if let Some(path) = std::env::var_os(RECORD_ENV) {
+        if let Err(cause) = record(&path, &RecordedInvocation::new(headers, payload)) {
+            log::warn!("Unable to record invocation to {:?}: {}", path, cause);
+        }
+    }
+}
+
+/// Loads the invocations recorded to the file at `path`, in the order they were received.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<RecordedInvocation>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut invocations = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            invocations.push(serde_json::from_str(&line)?);
+        }
+    }
+    Ok(invocations)
+}
+
+/// Feeds a recorded invocation to the `handler`, rebuilding the [Context] it was
+/// received with. The Lambda Runtime API isn't involved, so the handler result
+/// is returned rather than published.
+pub async fn replay<F, Fut, A, B, E>(invocation: &RecordedInvocation, handler: F) -> Result<StdResult<B, E>>
+    where F: FnOnce(A, Context) -> Fut,
+          Fut: Future<Output=StdResult<B, E>>,
+          A: DeserializeOwned
+{
+    let lambda_api = LambdaApiClient::create(Config::default());
+    let context = lambda_api.create_execution_context_from(invocation.context_headers()?);
+    let payload = serde_json::from_slice(&invocation.payload()?)?;
+    Ok((handler)(payload, context).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::HeaderMap;
+    use serde::Deserialize;
+
+    use crate::error::Error;
+    use crate::record::{self, RecordedInvocation};
+
+    #[derive(Deserialize)]
+    struct Greeting {
+        name: String,
+    }
+
+    fn create_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("lambda-runtime-aws-request-id", "0000-0640".parse().unwrap());
+        headers.insert("lambda-runtime-deadline-ms", "1000".parse().unwrap());
+        headers.insert("lambda-runtime-invoked-function-arn", "arn::something".parse().unwrap());
+        headers.insert("lambda-runtime-trace-id", "0001-0001".parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn should_replay_recorded_invocations() {
+        let path = std::env::temp_dir().join(format!("mu-record-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let invocation = RecordedInvocation::new(&create_headers(), br#"{"name":"John"}"#);
+        record::record(&path, &invocation).unwrap();
+
+        let invocations = record::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(vec![invocation], invocations);
+
+        let result = record::replay(&invocations[0], |greeting: Greeting, ctx| async move {
+            Ok::<_, Error>(format!("Hello, {} ({})", greeting.name, ctx.request_id))
+        }).await.unwrap();
+        assert_eq!(Ok("Hello, John (0000-0640)".to_string()), result);
+    }
+
+    #[tokio::test]
+    async fn should_fail_replaying_invocations_lacking_context_headers() {
+        let mut invocation = RecordedInvocation::new(&create_headers(), br#"{"name":"John"}"#);
+        invocation.headers.remove("lambda-runtime-trace-id");
+
+        let result = record::replay(&invocation, |greeting: Greeting, _ctx| async move {
+            Ok::<_, Error>(greeting.name)
+        }).await;
+        assert_eq!(Err(Error::Runtime("missing recorded header lambda-runtime-trace-id".to_string())), result);
+    }
+
+    #[tokio::test]
+    async fn should_fail_replaying_invocations_with_malformed_deadlines() {
+        let mut invocation = RecordedInvocation::new(&create_headers(), br#"{"name":"John"}"#);
+        invocation.headers.insert("lambda-runtime-deadline-ms".to_string(), "soon".to_string());
+
+        let result = record::replay(&invocation, |greeting: Greeting, _ctx| async move {
+            Ok::<_, Error>(greeting.name)
+        }).await;
+        assert_eq!(
+            Err(Error::Runtime(r#"invalid recorded header lambda-runtime-deadline-ms: "soon""#.to_string())),
+            result
+        );
+    }
+}
@@ -5,12 +5,19 @@
 use std::error::Error as StdError;
 use std::future::Future;
 use std::result::Result as StdResult;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use futures_util::future::{join_all, select, Either};
+use futures_util::pin_mut;
+use hyper::Body;
 use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
-use crate::lambda_api::{LambdaApiClient, PublishErrorRequest};
-use crate::model::Context;
+use crate::lambda_api::{type_name_of_val, Connector, LambdaApiClient, PublishErrorRequest};
+use crate::model::{Config, Context, InvocationStats, RawResponse};
+use crate::panic;
 
 /// Represents the result of the Lambda runtime execution.
 pub type RuntimeResult = StdResult<(), Error>;
@@ -51,109 +58,1288 @@ pub async fn listen_events<F, Fut, A, B, E>(handler: F) -> RuntimeResult
     listen_events_with(lambda_api, handler).await
 }
 
+/// Listen to AWS Lambda events, as [listen_events] does, driven by a tokio runtime
+/// of its own. It spares simple functions from wiring up `#[tokio::main]`, thus it
+/// must not be called from within another tokio runtime.
+///
+/// The runtime uses the `current_thread` flavor, with its IO and time drivers enabled.
+/// A multi-thread runtime is usually wrong on AWS Lambda: an execution environment
+/// handles one invocation at a time on a fraction of a vCPU, so its worker threads
+/// have no parallelism to exploit, while costing extra memory and cold start time.
+///
+/// ```no_run
+/// use aws_lambda_events::event::sqs::SqsEvent;
+///
+/// fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::run(|sqs_events: SqsEvent, _ctx| async move {
+///     println!("Received {} events", sqs_events.records.len());
+///     Ok::<(), mu_runtime::Error>(())
+///   })
+/// }
+/// ```
+pub fn run<F, Fut, A, B, E>(handler: F) -> RuntimeResult
+    where F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    let runtime = create_current_thread_runtime()?;
+    runtime.block_on(listen_events(handler))
+}
+
+/// Same as [run], using the given [LambdaApiClient].
+pub fn run_with<C, F, Fut, A, B, E>(lambda_api: LambdaApiClient<C>, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    let runtime = create_current_thread_runtime()?;
+    runtime.block_on(listen_events_with(lambda_api, handler))
+}
+
+fn create_current_thread_runtime() -> StdResult<tokio::runtime::Runtime, Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()?;
+    Ok(runtime)
+}
+
+/// Listen to AWS Lambda events, as [listen_events] does, but reporting a misconfigured
+/// environment as an [Error::Config] rather than panicking. It returns after the first
+/// fatal error, whose variant tells whether restarting the loop is worth it.
+///
+/// ```no_run
+/// use mu_runtime::{Context, Error};
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   loop {
+///     match mu_runtime::try_listen_events(|name: String, _: Context| greet(name)).await {
+///       Err(Error::Transport(cause)) => eprintln!("Restarting after: {}", cause),
+///       result => return result,
+///     }
+///   }
+/// }
+///
+/// async fn greet(name: String) -> Result<String, Error> {
+///   Ok(format!("Hello, {}", name))
+/// }
+/// ```
+pub async fn try_listen_events<F, Fut, A, B, E>(handler: F) -> RuntimeResult
+    where F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    let lambda_api = LambdaApiClient::create(Config::try_from_env()?);
+    listen_events_with(lambda_api, handler).await
+}
+
 /// Listen to AWS Lambda events and delegates the received payload to
 /// the `handler` function. This method allows one to define the LambdaApi
 /// instance that will be used in the Lambda-consumption mainloop. This
 /// might be desirable for local testing.
+///
+/// The loop runs until a fatal error happens or, when [Config::max_invocations]
+/// is defined, until that many events have been processed. Empty invocations
+/// are not considered fatal, being retried instead.
+///
+/// [Config::max_invocations]: crate::model::Config::max_invocations
 #[inline]
-pub async fn listen_events_with<F, Fut, A, B, E>(lambda_api: LambdaApiClient, handler: F) -> RuntimeResult
+pub async fn listen_events_with<C, F, Fut, A, B, E>(lambda_api: LambdaApiClient<C>, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    lambda_api.config().log_summary_from_env();
+    let invocations = AtomicUsize::new(0);
+    let invoke = || try_invoke_lambda_handler(&lambda_api, &handler, json_response);
+    run_worker(&invoke, &invocations, lambda_api.config().max_invocations).await
+}
+
+/// Listen to AWS Lambda events, as [listen_events] does, until exactly `n` events have
+/// been processed (or a fatal error happens), then exits cleanly. Handy for canary
+/// deployments and controlled load tests.
+///
+/// ```no_run
+/// use aws_lambda_events::event::sqs::SqsEvent;
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_events_n(10, |sqs_events: SqsEvent, _ctx| async move {
+///     println!("Received {} events", sqs_events.records.len());
+///     Ok::<(), mu_runtime::Error>(())
+///   }).await
+/// }
+/// ```
+pub async fn listen_events_n<F, Fut, A, B, E>(n: usize, handler: F) -> RuntimeResult
+    where F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    println!("Preparing to listen to events...");
+    let lambda_api = LambdaApiClient::default();
+    listen_events_n_with(lambda_api, n, handler).await
+}
+
+/// Same as [listen_events_n], using the given [LambdaApiClient]. The `n` events
+/// take precedence over [Config::max_invocations].
+///
+/// [Config::max_invocations]: crate::model::Config::max_invocations
+pub async fn listen_events_n_with<C, F, Fut, A, B, E>(lambda_api: LambdaApiClient<C>, n: usize, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    lambda_api.config().log_summary_from_env();
+    let invocations = AtomicUsize::new(0);
+    let invoke = || try_invoke_lambda_handler(&lambda_api, &handler, json_response);
+    run_worker(&invoke, &invocations, Some(n)).await
+}
+
+/// Listen to AWS Lambda events, as [listen_events] does, for handlers that don't
+/// need the invocation [Context].
+///
+/// ```no_run
+/// use aws_lambda_events::event::sqs::SqsEvent;
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_events_simple(handle_sqs_messages).await
+/// }
+///
+/// async fn handle_sqs_messages(sqs_events: SqsEvent) -> Result<(), mu_runtime::Error> {
+///   println!("Received {} events", sqs_events.records.len());
+///   Ok(())
+/// }
+/// ```
+pub async fn listen_events_simple<F, Fut, A, B, E>(handler: F) -> RuntimeResult
+    where F: Fn(A) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    listen_events(|event, _ctx| handler(event)).await
+}
+
+/// Same as [listen_events_simple], but allowing one to define the LambdaApi
+/// instance that will be used.
+pub async fn listen_events_simple_with<C, F, Fut, A, B, E>(lambda_api: LambdaApiClient<C>, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    listen_events_with(lambda_api, |event, _ctx| handler(event)).await
+}
+
+/// Listen to AWS Lambda events, as [listen_events] does, failing invocations whose
+/// `handler` doesn't complete within `timeout` with [Error::Timeout]. The invocation
+/// deadline still applies, thus the effective timeout is whichever comes first.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use aws_lambda_events::event::sqs::SqsEvent;
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_events_with_timeout(Duration::from_secs(2), |sqs_events, ctx| {
+///     handle_sqs_messages(sqs_events)
+///   }).await
+/// }
+///
+/// async fn handle_sqs_messages(sqs_events: SqsEvent) -> Result<(), mu_runtime::Error> {
+///   println!("Received {} events", sqs_events.records.len());
+///   Ok(())
+/// }
+/// ```
+pub async fn listen_events_with_timeout<F, Fut, A, B, E>(timeout: Duration, handler: F) -> RuntimeResult
+    where F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError + From<Error>
+{
+    listen_events(|event, ctx| {
+        let timed = with_timeout(timeout, &ctx, handler(event, ctx.clone()));
+        async move { timed.await.unwrap_or_else(|cause| Err(cause.into())) }
+    }).await
+}
+
+/// Races the `future` against `timeout`, or the remaining time of the invocation
+/// when shorter, failing with [Error::Timeout] once it has elapsed.
+pub fn with_timeout<Fut>(timeout: Duration, context: &Context, future: Fut) -> impl Future<Output=StdResult<Fut::Output, Error>>
+    where Fut: Future
+{
+    let effective_timeout = timeout.min(context.remaining_time());
+    async move {
+        tokio::time::timeout(effective_timeout, future).await
+            .map_err(|_| Error::Timeout(effective_timeout))
+    }
+}
+
+/// Listen to AWS Lambda events, keeping up to `max_inflight` invocations in flight.
+/// Each of them polls for the next event, runs the `handler` and publishes its
+/// response independently, thus no ordering between invocations is guaranteed.
+///
+/// Beware that AWS typically sends a single invocation at a time per execution
+/// environment, leaving the extra pollers idle. This is only worth enabling
+/// on environments known to deliver events concurrently, with I/O-bound handlers.
+///
+/// Pollers run concurrently within the calling task, interleaving while they wait on
+/// I/O, rather than in parallel across threads. Once one of them fails fatally, the
+/// others finish the invocation they're processing and stop, the first failure being
+/// returned. Pollers also share a few process-wide facilities:
+/// - the [Config::duplicate_invocation_window] remembers only the last invocation
+///   processed by any of them;
+/// - panics of tasks spawned by handlers are attributed to the last invocation
+///   started by any of them (see [crate::panic]).
+///
+/// ```no_run
+/// use aws_lambda_events::event::sqs::SqsEvent;
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_events_concurrent(4, |sqs_events, ctx| {
+///     handle_sqs_messages(sqs_events)
+///   }).await
+/// }
+///
+/// async fn handle_sqs_messages(sqs_events: SqsEvent) -> Result<(), mu_runtime::Error> {
+///   println!("Received {} events", sqs_events.records.len());
+///   Ok(())
+/// }
+/// ```
+pub async fn listen_events_concurrent<F, Fut, A, B, E>(max_inflight: usize, handler: F) -> RuntimeResult
+    where F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    println!("Preparing to listen to events...");
+    let lambda_api = LambdaApiClient::default();
+    listen_events_concurrent_with(lambda_api, max_inflight, handler).await
+}
+
+/// Same as [listen_events_concurrent], but allowing one to define the LambdaApi
+/// instance that will be used. When [Config::max_invocations] is defined, it
+/// limits the events processed by all pollers combined.
+///
+/// [Config::max_invocations]: crate::model::Config::max_invocations
+pub async fn listen_events_concurrent_with<C, F, Fut, A, B, E>(
+    lambda_api: LambdaApiClient<C>, max_inflight: usize, handler: F
+) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    lambda_api.config().log_summary_from_env();
+    let invocations = AtomicUsize::new(0);
+    let invoke = || try_invoke_lambda_handler(&lambda_api, &handler, json_response);
+    run_workers(max_inflight.max(1), &invoke, &invocations, lambda_api.config().max_invocations).await
+}
+
+/// Listen to AWS Lambda events, as [listen_events] does, but publishing the
+/// [RawResponse] returned by the `handler` verbatim. Useful for handlers that
+/// already produce serialized payloads (e.g. protobuf, pre-rendered templates),
+/// avoiding a needless round-trip through `serde_json`.
+///
+/// ```no_run
+/// use aws_lambda_events::event::sqs::SqsEvent;
+/// use mu_runtime::model::RawResponse;
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_raw_response(|sqs_events, ctx| {
+///     count_sqs_messages(sqs_events)
+///   }).await
+/// }
+///
+/// async fn count_sqs_messages(sqs_events: SqsEvent) -> Result<RawResponse, mu_runtime::Error> {
+///   let total = sqs_events.records.len().to_string();
+///   Ok(RawResponse::new("text/plain", total))
+/// }
+/// ```
+pub async fn listen_raw_response<F, Fut, A, E>(handler: F) -> RuntimeResult
+    where F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<RawResponse, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          E: StdError
+{
+    println!("Preparing to listen to events...");
+    let lambda_api = LambdaApiClient::default();
+    listen_raw_response_with(lambda_api, handler).await
+}
+
+/// Same as [listen_raw_response], but allowing one to define the LambdaApi
+/// instance that will be used.
+pub async fn listen_raw_response_with<C, F, Fut, A, E>(lambda_api: LambdaApiClient<C>, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<RawResponse, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          E: StdError
+{
+    lambda_api.config().log_summary_from_env();
+    let invocations = AtomicUsize::new(0);
+    let invoke = || try_invoke_raw_handler(&lambda_api, &handler);
+    run_worker(&invoke, &invocations, lambda_api.config().max_invocations).await
+}
+
+/// Listen to synchronous invocations carrying custom JSON payloads (e.g. direct `Invoke`
+/// calls, EventBridge Pipes enrichments), publishing the `handler` result as the literal
+/// JSON response. Unlike [listen_events], results serialized as `null` (e.g. `()` or
+/// `None`) are published as an empty body, sparing callers from handling a `"null"` payload.
+///
+/// ```no_run
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Order { id: u64 }
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_invoke(|order: Order, _ctx| async move {
+///     println!("Processing order {}", order.id);
+///     Ok::<(), mu_runtime::Error>(())
+///   }).await
+/// }
+/// ```
+pub async fn listen_invoke<F, Fut, A, B, E>(handler: F) -> RuntimeResult
     where F: Fn(A, Context) -> Fut + Sync + Send,
           Fut: Future<Output=StdResult<B, E>> + Send,
           A: for<'de> Deserialize<'de> + Send,
           B: Serialize,
           E: StdError
 {
-    loop {
-        try_invoke_lambda_handler(&lambda_api, &handler).await?;
-        // allows one to perform single request tests during the Integration Tests.
-        if cfg!(test) {
-            return Ok(())
+    println!("Preparing to listen to events...");
+    let lambda_api = LambdaApiClient::default();
+    listen_invoke_with(lambda_api, handler).await
+}
+
+/// Same as [listen_invoke], but allowing one to define the LambdaApi
+/// instance that will be used.
+pub async fn listen_invoke_with<C, F, Fut, A, B, E>(lambda_api: LambdaApiClient<C>, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    lambda_api.config().log_summary_from_env();
+    let invocations = AtomicUsize::new(0);
+    let invoke = || try_invoke_lambda_handler(&lambda_api, &handler, invoke_response);
+    run_worker(&invoke, &invocations, lambda_api.config().max_invocations).await
+}
+
+/// Listen to AWS Lambda events, handing the `handler` the invocation payload as a stream
+/// of bytes, as received from the Lambda Runtime API, rather than buffering it in memory.
+/// This allows large payloads to be parsed incrementally (e.g. JSON lines), keeping the
+/// memory footprint low on functions with little memory available.
+///
+/// The trade-off is that the handler takes over the parsing: the payload is neither
+/// deserialized nor validated beforehand, and malformed or empty payloads are only
+/// noticed (and should be reported as errors) by the handler itself.
+///
+/// ```no_run
+/// use hyper::body::HttpBody;
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   mu_runtime::listen_streamed_events(|mut body: hyper::Body, ctx| async move {
+///     let mut total_bytes = 0;
+///     while let Some(chunk) = body.data().await {
+///       total_bytes += chunk?.len();
+///     }
+///     Ok::<usize, mu_runtime::Error>(total_bytes)
+///   }).await
+/// }
+/// ```
+pub async fn listen_streamed_events<F, Fut, B, E>(handler: F) -> RuntimeResult
+    where F: Fn(Body, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          B: Serialize,
+          E: StdError
+{
+    println!("Preparing to listen to events...");
+    let lambda_api = LambdaApiClient::default();
+    listen_streamed_events_with(lambda_api, handler).await
+}
+
+/// Same as [listen_streamed_events], but allowing one to define the LambdaApi
+/// instance that will be used.
+pub async fn listen_streamed_events_with<C, F, Fut, B, E>(lambda_api: LambdaApiClient<C>, handler: F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(Body, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          B: Serialize,
+          E: StdError
+{
+    lambda_api.config().log_summary_from_env();
+    let invocations = AtomicUsize::new(0);
+    let invoke = || try_invoke_streamed_handler(&lambda_api, &handler);
+    run_worker(&invoke, &invocations, lambda_api.config().max_invocations).await
+}
+
+/// The delay before polling again after an empty invocation. It doubles on every
+/// consecutive one, up to [MAX_EMPTY_INVOCATION_BACKOFF].
+const MIN_EMPTY_INVOCATION_BACKOFF: Duration = Duration::from_millis(10);
+const MAX_EMPTY_INVOCATION_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How many consecutive polls for the next invocation may time out before the
+/// Lambda Runtime API is deemed wedged, ending the loop.
+const MAX_CONSECUTIVE_FETCH_TIMEOUTS: usize = 3;
+
+/// Processes events until a fatal error happens or the (shared) invocation
+/// counter reaches `max_invocations`, usually [Config::max_invocations](crate::model::Config::max_invocations).
+/// Failing to publish the outcome of an invocation is only fatal when the Lambda Runtime
+/// API can't be reached, as [settle_publishing] tells.
+/// Neither empty invocations nor polls for the next invocation exceeding
+/// [Config::fetch_timeout](crate::model::Config::fetch_timeout) are fatal, being retried instead.
+/// Empty invocations are retried after a growing delay, reset once an invocation succeeds.
+/// Timed out polls are retried up to [MAX_CONSECUTIVE_FETCH_TIMEOUTS] times in a row.
+async fn run_worker<I, Fut>(
+    invoke: &I, invocations: &AtomicUsize, max_invocations: Option<usize>
+) -> RuntimeResult
+    where I: Fn() -> Fut,
+          Fut: Future<Output=RuntimeResult>
+{
+    run_worker_until(invoke, invocations, max_invocations, &AtomicBool::new(false)).await
+}
+
+/// Runs `count` workers concurrently. Once one of them fails, the others are `stopped`
+/// right after the invocation they're processing, rather than dropped in the middle of
+/// it. The first failure is returned once all of them are done.
+async fn run_workers<I, Fut>(
+    count: usize, invoke: &I, invocations: &AtomicUsize, max_invocations: Option<usize>
+) -> RuntimeResult
+    where I: Fn() -> Fut,
+          Fut: Future<Output=RuntimeResult>
+{
+    let stopped = AtomicBool::new(false);
+    let first_failure = Mutex::new(None);
+    let workers = (0..count).map(|_| async {
+        if let Err(cause) = run_worker_until(invoke, invocations, max_invocations, &stopped).await {
+            stopped.store(true, Ordering::SeqCst);
+            first_failure.lock().unwrap().get_or_insert(cause);
+        }
+    });
+
+    join_all(workers).await;
+    match first_failure.into_inner().unwrap() {
+        Some(cause) => Err(cause),
+        None => Ok(()),
+    }
+}
+
+/// Same as [run_worker], but also returning cleanly once `stopped` is set.
+async fn run_worker_until<I, Fut>(
+    invoke: &I, invocations: &AtomicUsize, max_invocations: Option<usize>, stopped: &AtomicBool
+) -> RuntimeResult
+    where I: Fn() -> Fut,
+          Fut: Future<Output=RuntimeResult>
+{
+    let has_pending_invocations = || !stopped.load(Ordering::SeqCst) && match max_invocations {
+        Some(max) => invocations.fetch_add(1, Ordering::SeqCst) < max,
+        None => true,
+    };
+
+    let mut backoff = MIN_EMPTY_INVOCATION_BACKOFF;
+    let mut fetch_timeouts = 0;
+    while has_pending_invocations() {
+        let result = (invoke)().await;
+        if !matches!(result, Err(Error::FetchTimeout(_))) {
+            fetch_timeouts = 0;
+        }
+
+        let retried = match result {
+            Ok(()) => {
+                backoff = MIN_EMPTY_INVOCATION_BACKOFF;
+                false
+            },
+            Err(Error::FetchTimeout(timeout)) => {
+                fetch_timeouts += 1;
+                if fetch_timeouts >= MAX_CONSECUTIVE_FETCH_TIMEOUTS {
+                    return Err(Error::FetchTimeout(timeout))
+                }
+                log::warn!("No invocation received within {:?} ({}/{}). Polling again...",
+                           timeout, fetch_timeouts, MAX_CONSECUTIVE_FETCH_TIMEOUTS);
+                true
+            },
+            Err(Error::EmptyInvocation) => {
+                log::warn!("Received an empty invocation. Retrying in {}ms...", backoff.as_millis());
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_EMPTY_INVOCATION_BACKOFF);
+                true
+            },
+            Err(cause) => return Err(cause),
+        };
+
+        if retried && max_invocations.is_some() {
+            invocations.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    Ok(())
+}
+
+/// Performs the actual Lambda Invocation lifecycle. Failures happening once the
+/// invocation was fetched are [settled](settle_publishing) against its request id.
+#[inline]
+async fn try_invoke_lambda_handler<C, F, Fut, A, B, E>(
+    lambda_api: &LambdaApiClient<C>, handler: &F, to_response: fn(B) -> StdResult<RawResponse, Error>
+) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          B: Serialize,
+          E: StdError
+{
+    let (request_id, result) = match invoke_handler(lambda_api, handler).await? {
+        Some(invocation) => invocation,
+        None => return Ok(())
+    };
+
+    let published = match result {
+        Ok(payload) => publish_payload(lambda_api, &request_id, to_response(payload)).await,
+        Err(error) => publish_handler_error(lambda_api, request_id.clone(), error).await
+    };
+
+    settle_publishing(&request_id, published)
+}
+
+async fn publish_payload<C>(
+    lambda_api: &LambdaApiClient<C>, request_id: &str, response: StdResult<RawResponse, Error>
+) -> RuntimeResult
+    where C: Connector
+{
+    let response = match response {
+        Ok(response) => response,
+        Err(cause) => return publish_handler_error(lambda_api, request_id.to_string(), cause).await,
+    };
+    lambda_api.remember_processed(request_id, &response);
+    lambda_api.publish_raw_response(request_id.to_string(), response).await
+}
+
+/// Serializes the payload as JSON.
+fn json_response<B: Serialize>(payload: B) -> StdResult<RawResponse, Error> {
+    Ok(RawResponse::new("application/json", serde_json::to_vec(&payload)?))
+}
+
+/// Serializes the payload as JSON, leaving the body empty rather than `null`.
+fn invoke_response<B: Serialize>(payload: B) -> StdResult<RawResponse, Error> {
+    let body = serde_json::to_vec(&payload)?;
+    match body.as_slice() {
+        b"null" => Ok(RawResponse::new("application/json", Vec::new())),
+        _ => Ok(RawResponse::new("application/json", body)),
+    }
+}
+
+/// Performs the Lambda Invocation lifecycle of handlers producing raw responses.
+#[inline]
+async fn try_invoke_raw_handler<C, F, Fut, A, E>(lambda_api: &LambdaApiClient<C>, handler: &F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<RawResponse, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          E: StdError
+{
+    let (request_id, result) = match invoke_handler(lambda_api, handler).await? {
+        Some(invocation) => invocation,
+        None => return Ok(())
+    };
+
+    let published = match result {
+        Ok(response) => {
+            lambda_api.remember_processed(&request_id, &response);
+            lambda_api.publish_raw_response(request_id.clone(), response).await
+        },
+        Err(error) => publish_handler_error(lambda_api, request_id.clone(), error).await
+    };
+
+    settle_publishing(&request_id, published)
+}
+
+/// Performs the Lambda Invocation lifecycle of handlers consuming the payload as a stream.
+#[inline]
+async fn try_invoke_streamed_handler<C, F, Fut, B, E>(lambda_api: &LambdaApiClient<C>, handler: &F) -> RuntimeResult
+    where C: Connector,
+          F: Fn(Body, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          B: Serialize,
+          E: StdError
+{
+    let (body, context) = lambda_api.fetch_next_stream().await?;
+    let request_id = context.request_id.clone();
+
+    let published = match supervise(lambda_api, context.clone(), (handler)(body, context)).await {
+        Ok(payload) => lambda_api.publish_response(request_id.clone(), payload).await,
+        Err(error) => publish_handler_error(lambda_api, request_id.clone(), error).await
+    };
+
+    settle_publishing(&request_id, published)
+}
+
+/// Fetches the next event and hands it to the `handler`, returning its result
+/// along with the request id it should be published to. Events that can't be
+/// deserialized are reported straight away, yielding `None`. So are invocations
+/// already processed within the [Config::duplicate_invocation_window], whose
+/// response is re-published instead.
+///
+/// [Config::duplicate_invocation_window]: crate::model::Config::duplicate_invocation_window
+#[inline]
+async fn invoke_handler<C, F, Fut, A, B, E>(lambda_api: &LambdaApiClient<C>, handler: &F) -> StdResult<Option<(String, StdResult<B, E>)>, Error>
+    where C: Connector,
+          F: Fn(A, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<B, E>> + Send,
+          A: for<'de> Deserialize<'de> + Send,
+          E: StdError
+{
+    let (bytes, context) = lambda_api.fetch_next_message().await?;
+    let request_id = context.request_id.clone();
+    if let Some(response) = lambda_api.processed_response(&request_id) {
+        log::warn!("Invocation {} was already processed. Re-publishing its response...", request_id);
+        let published = lambda_api.publish_raw_response(request_id.clone(), response).await;
+        settle_publishing(&request_id, published)?;
+        return Ok(None)
+    }
+
+    let body = match serde_json::from_slice(&bytes) {
+        Ok(body) => body,
+        Err(cause) => {
+            log::warn!("Unable to deserialize the payload of invocation {}: {}", request_id, cause);
+            let published = publish_handler_error(lambda_api, request_id.clone(), cause).await;
+            settle_publishing(&request_id, published)?;
+            return Ok(None)
+        }
+    };
+    let result = supervise(lambda_api, context.clone(), (handler)(body, context)).await;
+    Ok(Some((request_id, result)))
+}
+
+/// Settles the outcome of publishing the result of the invocation `request_id`. Failing to
+/// reach the Lambda Runtime API is fatal, being [tagged](Error::for_invocation) with the
+/// request id. Any other failure (e.g. the Runtime API rejecting an oversized response)
+/// only concerns this invocation, thus it's logged and the loop carries on.
+fn settle_publishing(request_id: &str, published: RuntimeResult) -> RuntimeResult {
+    match published {
+        Err(Error::Transport(cause)) => Err(Error::Transport(cause).for_invocation(request_id)),
+        Err(cause) => {
+            log::error!("Unable to publish the outcome of invocation {}: {}", request_id, cause);
+            Ok(())
+        },
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Runs the `handler` future of an invocation, tracking it for panic reports,
+/// watching its deadline, reporting it when slow and notifying its completion.
+async fn supervise<C, Fut>(lambda_api: &LambdaApiClient<C>, context: Context, handler: Fut) -> Fut::Output
+    where C: Connector,
+          Fut: Future
+{
+    let request_id = context.request_id.clone();
+    let cold_start = context.is_cold_start();
+    let slow_invocation_threshold = context.env_config.slow_invocation_threshold;
+    let watchdog = watch_deadline(lambda_api, context);
+    let started_at = Instant::now();
+    let handler_future = panic::track_invocation(request_id.clone(), handler);
+    let result = run_watched(handler_future, watchdog).await;
+    let duration = started_at.elapsed();
+    report_slow_invocation(&request_id, duration, slow_invocation_threshold);
+
+    if let Some(callback) = lambda_api.completion_callback() {
+        (callback)(&InvocationStats { request_id, cold_start, duration });
+    }
+    result
+}
+
+async fn publish_handler_error<C, E>(lambda_api: &LambdaApiClient<C>, request_id: String, error: E) -> RuntimeResult
+    where C: Connector,
+          E: StdError
+{
+    let payload = PublishErrorRequest {
+        error_type: type_name_of_val(&error).to_string(),
+        error_message: format!("{}", error),
+        stack_trace: None,
+    };
+    lambda_api.publish_error(request_id, payload).await
+}
+
+/// Runs the `handler` future alongside its `watchdog`, which is dropped (thus
+/// cancelled) as soon as the handler completes.
+async fn run_watched<Fut, W>(handler: Fut, watchdog: W) -> Fut::Output
+    where Fut: Future,
+          W: Future<Output=()>
+{
+    pin_mut!(handler, watchdog);
+    match select(handler, watchdog).await {
+        Either::Left((result, _)) => result,
+        Either::Right(((), handler)) => handler.await,
+    }
+}
+
+/// Warns, and notifies the configured callback, once the remaining time of the invocation
+/// drops below [Config::timeout_warning_threshold](crate::model::Config::timeout_warning_threshold).
+async fn watch_deadline<C>(lambda_api: &LambdaApiClient<C>, context: Context)
+    where C: Connector
+{
+    if let Some(threshold) = lambda_api.config().timeout_warning_threshold {
+        tokio::time::sleep(context.remaining_time().saturating_sub(threshold)).await;
+
+        log::warn!("Invocation {} is about to time out: less than {:?} remaining",
+                   context.request_id, threshold);
+        if let Some(callback) = lambda_api.timeout_callback() {
+            (callback)(&context);
+        }
+    }
+}
+
+/// Emits a warning in case the handler took longer than the configured threshold.
+fn report_slow_invocation(request_id: &str, elapsed: Duration, threshold: Option<Duration>) {
+    if let Some(threshold) = threshold {
+        if elapsed > threshold {
+            log::warn!("Slow invocation {}: handler took {:?} (threshold: {:?})",
+                       request_id, elapsed, threshold);
+        }
+    }
+}
+
+// Integration tests live in this file, so they can rely on the crate-private
+// testing utilities.
+#[cfg(test)]
+mod integration_tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+    use httpmock::{MockRef, MockServer};
+    use hyper::body::HttpBody;
+    use log::Level;
+    use rusoto_core::Region;
+    use rusoto_dynamodb::DynamoDbClient;
+
+    use crate::Error;
+    use crate::lambda_api::LambdaApiClient;
+    use crate::{
+        listen_events_concurrent_with, listen_events_n_with, listen_events_simple_with, listen_events_with,
+        listen_invoke_with, listen_raw_response_with, listen_streamed_events_with, run_with, try_listen_events, with_timeout
+    };
+    use crate::model::{Config, Context, RawResponse};
+    use crate::runtime::{run_worker, run_workers, settle_publishing, MAX_CONSECUTIVE_FETCH_TIMEOUTS};
+    use crate::testing;
+
+    /// A simulates a complex repository that relies on DynamoDB to
+    /// store data. This was designed to mimic real-world scenario
+    /// where users has to interact with complex structures that holds
+    /// state between sub-sequent lambda requests.
+    struct DynamoDbRepository {
+        #[allow(dead_code)]
+        dynamo_db: DynamoDbClient,
+    }
+
+    impl DynamoDbRepository {
+
+        fn create() -> Self {
+            let region = Region::default();
+            Self { dynamo_db: DynamoDbClient::new(region) }
+        }
+
+        async fn a_method_that_will_succeed(&self) -> Result<i32, Error> {
+            Ok(42)
+        }
+
+        async fn a_method_that_will_fail(&self) -> Result<(), Error> {
+            Err("Not implemented".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn should_classify_unreachable_apis_as_transport_failures() {
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: testing::unreachable_endpoint(),
+            ..Config::default()
+        });
+
+        let result = listen_events_with(lambda_api, |req: String, _ctx| async { Ok::<_, Error>(req) }).await;
+        assert!(matches!(result, Err(Error::Transport(_))), "Unexpected: {:?}", result);
+    }
+
+    #[tokio::test]
+    async fn should_poll_again_when_fetching_the_next_invocation_times_out() {
+        let attempts = AtomicUsize::new(0);
+        let invoke = || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match attempt {
+                    0 => Err(Error::FetchTimeout(Duration::from_millis(100))),
+                    _ => Ok(()),
+                }
+            }
+        };
+
+        let result = run_worker(&invoke, &AtomicUsize::new(0), Some(1)).await;
+        assert_eq!(Ok(()), result);
+        assert_eq!(2, attempts.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_let_inflight_invocations_finish_when_a_worker_fails() {
+        let attempts = AtomicUsize::new(0);
+        let finished = AtomicUsize::new(0);
+        let invoke = || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let finished = &finished;
+            async move {
+                match attempt {
+                    0 => {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        finished.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    },
+                    _ => Err(Error::Transport("connection refused".to_string())),
+                }
+            }
+        };
+
+        let result = run_workers(2, &invoke, &AtomicUsize::new(0), None).await;
+        assert_eq!(Err(Error::Transport("connection refused".to_string())), result);
+        assert_eq!(1, finished.load(Ordering::SeqCst));
+        assert_eq!(2, attempts.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_give_up_after_consecutive_fetch_timeouts() {
+        testing::init_logger();
+        let attempts = AtomicUsize::new(0);
+        let invoke = || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match attempt {
+                    // a successful invocation resets the count
+                    1 => Ok(()),
+                    _ => Err(Error::FetchTimeout(Duration::from_millis(100))),
+                }
+            }
+        };
+
+        let result = run_worker(&invoke, &AtomicUsize::new(0), None).await;
+        assert_eq!(Err(Error::FetchTimeout(Duration::from_millis(100))), result);
+        assert_eq!(2 + MAX_CONSECUTIVE_FETCH_TIMEOUTS, attempts.load(Ordering::SeqCst));
+        assert!(testing::captured_logs(Level::Warn).iter()
+            .any(|msg| msg == "No invocation received within 100ms (2/3). Polling again..."));
+    }
+
+    #[tokio::test]
+    async fn should_back_off_on_consecutive_empty_invocations() {
+        testing::init_logger();
+        let attempts = AtomicUsize::new(0);
+        let invoke = || {
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match attempt {
+                    0..=2 | 4 => Err(Error::EmptyInvocation),
+                    _ => Ok(()),
+                }
+            }
+        };
+
+        let started_at = Instant::now();
+        let result = run_worker(&invoke, &AtomicUsize::new(0), Some(2)).await;
+        assert_eq!(Ok(()), result);
+        assert_eq!(6, attempts.load(Ordering::SeqCst));
+        assert!(started_at.elapsed() >= Duration::from_millis(10 + 20 + 40 + 10));
+
+        let warnings = testing::captured_logs(Level::Warn);
+        let position = warnings.iter()
+            .position(|msg| msg == "Received an empty invocation. Retrying in 40ms...")
+            .expect("the backoff should have grown");
+        assert!(warnings[position..].iter().any(|msg| msg == "Received an empty invocation. Retrying in 10ms..."),
+            "the backoff should have been reset by the successful invocation");
+    }
+
+    #[tokio::test]
+    async fn should_classify_missing_environment_variables_as_config_failures() {
+        // the reserved variables are only populated by the AWS Lambda platform
+        let result = try_listen_events(|req: String, _ctx| async { Ok::<_, Error>(req) }).await;
+        assert_eq!(
+            Err(Error::Config("missing env var AWS_LAMBDA_RUNTIME_API: environment variable not found".to_string())),
+            result
+        );
+    }
+
+    #[tokio::test]
+    async fn should_handle_successful_requests()
+    {
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = create_lambda_api_for_testing(mock_server.port());
+        let client = DynamoDbRepository::create();
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            client.a_method_that_will_succeed()
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert();
+        success.assert();
+    }
+
+    #[test]
+    fn should_drive_invocations_with_its_own_runtime()
+    {
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = create_lambda_api_for_testing(mock_server.port());
+        let result = run_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| async {
+            Ok::<_, Error>(42)
+        });
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert();
+        success.assert();
+    }
+
+    #[tokio::test]
+    async fn should_handle_failure_requests()
+    {
+        let mock_server = MockServer::start();
+        let (next, _success, error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = create_lambda_api_for_testing(mock_server.port());
+        let client = DynamoDbRepository::create();
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            client.a_method_that_will_fail()
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert();
+        error.assert();
+    }
+
+    #[tokio::test]
+    async fn should_report_undeserializable_payloads_and_keep_running()
+    {
+        let mock_server = MockServer::start();
+        let next = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/next");
+            then.status(200)
+                .header("lambda-runtime-aws-request-id", "0000-0001")
+                .header("lambda-runtime-deadline-ms", "1000")
+                .header("lambda-runtime-invoked-function-arn", "arn::something")
+                .header("lambda-runtime-trace-id", "0001-0001")
+                .body("not json");
+        });
+        let error = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/error")
+                .body_contains(r#""errorType":"serde_json::error::Error""#)
+                .method("POST");
+            then.status(200);
+        });
+
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(2),
+            ..Default::default()
+        });
+        let client = DynamoDbRepository::create();
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            client.a_method_that_will_succeed()
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert_hits(2);
+        error.assert_hits(2);
+    }
+
+    #[tokio::test]
+    async fn should_warn_about_slow_invocations()
+    {
+        testing::init_logger();
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            slow_invocation_threshold: Some(Duration::from_millis(10)),
+            max_invocations: Some(1),
+            ..Default::default()
+        });
+
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok::<i32, Error>(42)
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert();
+        success.assert();
+
+        let warnings = testing::captured_logs(log::Level::Warn);
+        assert!(warnings.iter().any(|msg| msg.starts_with("Slow invocation 0000-0001")))
+    }
+
+    #[tokio::test]
+    async fn should_warn_about_invocations_about_to_time_out()
+    {
+        testing::init_logger();
+        let mock_server = MockServer::start();
+        let (next, success) = mock_invocation_expiring_in(&mock_server, "0000-0002", 300);
+
+        let notified = Arc::new(AtomicBool::new(false));
+        let callback_notified = notified.clone();
+        let lambda_api = create_lambda_api_warning_timeouts(mock_server.port())
+            .on_timeout_imminent(move |_ctx| callback_notified.store(true, Ordering::SeqCst));
+
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| async {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok::<i32, Error>(42)
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
         }
+
+        next.assert();
+        success.assert();
+
+        let warnings = testing::captured_logs(log::Level::Warn);
+        assert!(warnings.iter().any(|msg| msg.starts_with("Invocation 0000-0002 is about to time out")));
+        assert!(notified.load(Ordering::SeqCst));
     }
-}
 
-/// Performs the actual Lambda Invocation lifecycle.
-#[inline]
-async fn try_invoke_lambda_handler<F, Fut, A, B, E>(lambda_api: &LambdaApiClient, handler: &F) -> RuntimeResult
-    where F: Fn(A, Context) -> Fut + Sync + Send,
-          Fut: Future<Output=StdResult<B, E>> + Send,
-          A: for<'de> Deserialize<'de> + Send,
-          B: Serialize,
-          E: StdError
-{
-    let (bytes, context) = lambda_api.fetch_next_message().await?;
-    let request_id = context.request_id.clone();
-    let body = serde_json::from_slice(&bytes)?;
-    let result = (handler)(body, context).await;
-
-    match result {
-        Ok(payload) => lambda_api.publish_response(request_id, payload).await?,
-        Err(error) => {
-            let payload = PublishErrorRequest {
-                error_type: type_name_of_val(&error).to_string(),
-                error_message: format!("{}", error)
-            };
-            lambda_api.publish_error(request_id, payload).await?
+    #[tokio::test]
+    async fn should_not_warn_about_invocations_finishing_in_time()
+    {
+        testing::init_logger();
+        let mock_server = MockServer::start();
+        let (next, success) = mock_invocation_expiring_in(&mock_server, "0000-0003", 300);
+
+        let notified = Arc::new(AtomicBool::new(false));
+        let callback_notified = notified.clone();
+        let lambda_api = create_lambda_api_warning_timeouts(mock_server.port())
+            .on_timeout_imminent(move |_ctx| callback_notified.store(true, Ordering::SeqCst));
+
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| async {
+            Ok::<i32, Error>(42)
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
         }
+
+        next.assert();
+        success.assert();
+
+        // give a leaked watchdog the chance to fire
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let warnings = testing::captured_logs(log::Level::Warn);
+        assert!(!warnings.iter().any(|msg| msg.starts_with("Invocation 0000-0003")));
+        assert!(!notified.load(Ordering::SeqCst));
     }
 
-    Ok(())
-}
+    #[tokio::test]
+    async fn should_stop_after_the_configured_number_of_invocations()
+    {
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
 
-fn type_name_of_val<T>(_: &T) -> &'static str {
-    std::any::type_name::<T>()
-}
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(3),
+            ..Default::default()
+        });
+        let client = DynamoDbRepository::create();
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            client.a_method_that_will_succeed()
+        }).await;
 
-// Integration test has been moved to this file because `if cfg(test)` doesn't
-// work in integration tests.
-//
-// See: https://users.rust-lang.org/t/cfg-test-does-not-work-with-integration-tests/36630/7
-#[cfg(test)]
-mod integration_tests {
-    use aws_lambda_events::event::alb::AlbTargetGroupRequest;
-    use httpmock::{MockRef, MockServer};
-    use rusoto_core::Region;
-    use rusoto_dynamodb::DynamoDbClient;
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
 
-    use crate::Error;
-    use crate::lambda_api::LambdaApiClient;
-    use crate::listen_events_with;
-    use crate::model::Config;
+        next.assert_hits(3);
+        success.assert_hits(3);
+    }
 
-    /// A simulates a complex repository that relies on DynamoDB to
-    /// store data. This was designed to mimic real-world scenario
-    /// where users has to interact with complex structures that holds
-    /// state between sub-sequent lambda requests.
-    struct DynamoDbRepository {
-        #[allow(dead_code)]
-        dynamo_db: DynamoDbClient,
+    #[tokio::test]
+    async fn should_process_exactly_the_given_number_of_invocations()
+    {
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(3),
+            ..Default::default()
+        });
+        let invocations = AtomicUsize::new(0);
+        let result = listen_events_n_with(lambda_api, 2, |_req: AlbTargetGroupRequest, _ctx| {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            async { Ok::<_, Error>(42) }
+        }).await;
+
+        assert_eq!(Ok(()), result);
+        next.assert_hits(2);
+        success.assert_hits(2);
+        assert_eq!(2, invocations.load(Ordering::SeqCst));
     }
 
-    impl DynamoDbRepository {
+    #[tokio::test]
+    async fn should_report_rejected_responses_and_keep_running()
+    {
+        testing::init_logger();
+        let mock_server = MockServer::start();
+        let rejected = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                .body(r#""a huge payload""#)
+                .method("POST");
 
-        fn create() -> Self {
-            let region = Region::default();
-            Self { dynamo_db: DynamoDbClient::new(region) }
-        }
+            then.status(413).body("Payload too large");
+        });
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
 
-        async fn a_method_that_will_succeed(&self) -> Result<i32, Error> {
-            Ok(42)
-        }
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(2),
+            ..Default::default()
+        });
+        let invocations = AtomicUsize::new(0);
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            let response = match invocations.fetch_add(1, Ordering::SeqCst) {
+                0 => serde_json::json!("a huge payload"),
+                _ => serde_json::json!(42),
+            };
+            async { Ok::<_, Error>(response) }
+        }).await;
 
-        async fn a_method_that_will_fail(&self) -> Result<(), Error> {
-            Err("Not implemented".into())
+        assert_eq!(Ok(()), result);
+        next.assert_hits(2);
+        rejected.assert();
+        success.assert();
+        assert!(testing::captured_logs(Level::Error).iter()
+            .any(|msg| msg == "Unable to publish the outcome of invocation 0000-0001: Payload too large"));
+    }
+
+    #[test]
+    fn should_tag_transport_failures_with_the_request_id()
+    {
+        assert_eq!(
+            Err(Error::Transport("[req 0000-0001] connection reset".to_string())),
+            settle_publishing("0000-0001", Err(Error::Transport("connection reset".to_string())))
+        );
+    }
+
+    #[tokio::test]
+    async fn should_republish_repeated_invocations_without_reinvoking_the_handler()
+    {
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(2),
+            duplicate_invocation_window: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+        let invocations = AtomicUsize::new(0);
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            async { Ok::<_, Error>(42) }
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
         }
+
+        next.assert_hits(2);
+        success.assert_hits(2);
+        assert_eq!(1, invocations.load(Ordering::SeqCst));
     }
 
     #[tokio::test]
-    async fn should_handle_successful_requests()
+    async fn should_republish_invocations_whose_response_was_rejected()
+    {
+        static REJECTED: AtomicBool = AtomicBool::new(false);
+
+        let mock_server = MockServer::start();
+        let rejected = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                .matches(|_req| !REJECTED.swap(true, Ordering::SeqCst))
+                .method("POST");
+
+            then.status(500).body("Service unavailable");
+        });
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(2),
+            duplicate_invocation_window: Some(Duration::from_secs(60)),
+            ..Default::default()
+        });
+        let invocations = AtomicUsize::new(0);
+        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+            invocations.fetch_add(1, Ordering::SeqCst);
+            async { Ok::<_, Error>(42) }
+        }).await;
+
+        assert_eq!(Ok(()), result);
+        next.assert_hits(2);
+        rejected.assert_hits(1);
+        success.assert_hits(1);
+        assert_eq!(1, invocations.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_handle_requests_without_context()
     {
         let mock_server = MockServer::start();
         let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
 
         let lambda_api = create_lambda_api_for_testing(mock_server.port());
         let client = DynamoDbRepository::create();
-        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
+        let result = listen_events_simple_with(lambda_api, |_req: AlbTargetGroupRequest| {
             client.a_method_that_will_succeed()
         }).await;
 
@@ -166,15 +1352,56 @@ mod integration_tests {
     }
 
     #[tokio::test]
-    async fn should_handle_failure_requests()
+    async fn should_keep_multiple_invocations_in_flight()
     {
         let mock_server = MockServer::start();
-        let (next, _success, error) = mock_lambda_runtime_endpoints(&mock_server);
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+
+        let lambda_api = LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", mock_server.port()),
+            max_invocations: Some(4),
+            ..Default::default()
+        });
+
+        let inflight = AtomicUsize::new(0);
+        let peak_inflight = AtomicUsize::new(0);
+        let result = listen_events_concurrent_with(lambda_api, 2, |_req: AlbTargetGroupRequest, _ctx| {
+            let current = inflight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak_inflight.fetch_max(current, Ordering::SeqCst);
+            let inflight = &inflight;
+            async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                inflight.fetch_sub(1, Ordering::SeqCst);
+                Ok::<i32, Error>(42)
+            }
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert_hits(4);
+        success.assert_hits(4);
+        assert_eq!(2, peak_inflight.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_publish_raw_responses_verbatim()
+    {
+        let mock_server = MockServer::start();
+        let (next, _success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+        let raw_endpoint = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                .header("content-type", "text/html")
+                .body("<p>42</p>")
+                .method("POST");
+
+            then.status(200);
+        });
 
         let lambda_api = create_lambda_api_for_testing(mock_server.port());
-        let client = DynamoDbRepository::create();
-        let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| {
-            client.a_method_that_will_fail()
+        let result = listen_raw_response_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| async {
+            Ok::<RawResponse, Error>(RawResponse::new("text/html", "<p>42</p>"))
         }).await;
 
         if let Err(cause) = result {
@@ -182,17 +1409,162 @@ mod integration_tests {
         }
 
         next.assert();
-        error.assert();
+        raw_endpoint.assert();
+    }
+
+    #[tokio::test]
+    async fn should_publish_unit_invoke_results_as_empty_bodies()
+    {
+        let mock_server = MockServer::start();
+        let (next, success, _error) = mock_lambda_runtime_endpoints(&mock_server);
+        let empty_endpoint = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                .body("")
+                .method("POST");
+
+            then.status(200);
+        });
+
+        let lambda_api = create_lambda_api_for_testing(mock_server.port());
+        let result = listen_invoke_with(lambda_api, |_req: AlbTargetGroupRequest, _ctx| async {
+            Ok::<(), Error>(())
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert();
+        empty_endpoint.assert();
+        success.assert_hits(0);
+    }
+
+    #[tokio::test]
+    async fn should_stream_the_payload_to_the_handler()
+    {
+        let mock_server = MockServer::start();
+        let next = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/next");
+            then.status(200)
+                .header("lambda-runtime-aws-request-id", "0000-0001")
+                .header("lambda-runtime-deadline-ms", "1000")
+                .header("lambda-runtime-invoked-function-arn", "arn::something")
+                .header("lambda-runtime-trace-id", "0001-0001")
+                .body("{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n");
+        });
+        let success = mock_server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+                .body("6")
+                .method("POST");
+            then.status(200);
+        });
+
+        let lambda_api = create_lambda_api_for_testing(mock_server.port());
+        let result = listen_streamed_events_with(lambda_api, |mut body: hyper::Body, _ctx| async move {
+            let mut pending = Vec::new();
+            let mut sum = 0;
+            while let Some(chunk) = body.data().await {
+                pending.extend_from_slice(&chunk?);
+                while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    let item: serde_json::Value = serde_json::from_slice(&line)?;
+                    sum += item["id"].as_i64().unwrap_or_default();
+                }
+            }
+            Ok::<i64, Error>(sum)
+        }).await;
+
+        if let Err(cause) = result {
+            panic!("Unexpected: {}", cause);
+        }
+
+        next.assert();
+        success.assert();
+    }
+
+    #[tokio::test]
+    async fn should_time_out_once_the_override_elapses()
+    {
+        let ctx = context_expiring_in(Duration::from_secs(5));
+
+        let result = with_timeout(Duration::from_millis(50), &ctx, tokio::time::sleep(Duration::from_secs(1))).await;
+        assert_eq!(Err(Error::Timeout(Duration::from_millis(50))), result);
+    }
+
+    #[tokio::test]
+    async fn should_time_out_at_the_deadline_when_it_comes_first()
+    {
+        let ctx = context_expiring_in(Duration::from_millis(50));
+
+        let result = with_timeout(Duration::from_secs(5), &ctx, tokio::time::sleep(Duration::from_secs(1))).await;
+        match result {
+            Err(Error::Timeout(timeout)) => assert!(timeout <= Duration::from_millis(50)),
+            other => panic!("Unexpected: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_not_time_out_handlers_completing_in_time()
+    {
+        let ctx = context_expiring_in(Duration::from_secs(5));
+
+        let result = with_timeout(Duration::from_secs(1), &ctx, async { 42 }).await;
+        assert_eq!(Ok(42), result);
+    }
+
+    fn context_expiring_in(remaining: Duration) -> Context {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Context {
+            deadline: (now + remaining).as_millis() as u64,
+            ..Default::default()
+        }
     }
 
     fn create_lambda_api_for_testing(port: u16) -> LambdaApiClient {
         LambdaApiClient::create(Config {
             endpoint: format!("127.0.0.1:{}", port),
+            max_invocations: Some(1),
+            ..Default::default()
+        })
+    }
+
+    fn create_lambda_api_warning_timeouts(port: u16) -> LambdaApiClient {
+        LambdaApiClient::create(Config {
+            endpoint: format!("127.0.0.1:{}", port),
+            max_invocations: Some(1),
+            timeout_warning_threshold: Some(Duration::from_millis(200)),
             ..Default::default()
         })
     }
 
-    fn mock_lambda_runtime_endpoints(server: &MockServer) -> (MockRef, MockRef, MockRef) {
+    fn mock_invocation_expiring_in<'a>(server: &'a MockServer, request_id: &str, millis: u64) -> (MockRef<'a>, MockRef<'a>) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let deadline = (now + Duration::from_millis(millis)).as_millis().to_string();
+
+        let next_endpoint = server.mock(|when, then| {
+            when.path("/2018-06-01/runtime/invocation/next");
+
+            let alb_request = include_str!("../tests/sample_alb_request.json");
+            then.status(200)
+                .header("lambda-runtime-aws-request-id", request_id)
+                .header("lambda-runtime-deadline-ms", &deadline)
+                .header("lambda-runtime-invoked-function-arn", "arn::something")
+                .header("lambda-runtime-trace-id", "0001-0001")
+                .body(alb_request);
+        });
+
+        let success_endpoint = server.mock(|when, then| {
+            when.path(format!("/2018-06-01/runtime/invocation/{}/response", request_id))
+                .body("42")
+                .method("POST");
+
+            then.status(200);
+        });
+
+        (next_endpoint, success_endpoint)
+    }
+
+    fn mock_lambda_runtime_endpoints(server: &MockServer) -> (MockRef<'_>, MockRef<'_>, MockRef<'_>) {
         let next_endpoint = server.mock(|when, then| {
             when.path("/2018-06-01/runtime/invocation/next");
 
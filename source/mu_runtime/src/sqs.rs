@@ -0,0 +1,338 @@
+//! Utilities to consume SQS messages in batches, reporting partial failures
+//! back to AWS Lambda so only the failed messages are retried.
+//!
+//! Make sure the event source mapping has `ReportBatchItemFailures` enabled,
+//! otherwise the reported failures will be ignored by the platform.
+//! Handlers composed of multiple processing stages can use [listen_batch_with_context],
+//! marking records as failed from any stage through a shared [BatchContext].
+//! Message attributes can be read through [MessageAttributes](crate::attributes::MessageAttributes).
+//!
+//! ```no_run
+//! use aws_lambda_events::event::sqs::SqsMessage;
+//! use mu_runtime::{sqs, Context, Error};
+//!
+//! #[tokio::main]
+//! async fn main() -> mu_runtime::RuntimeResult {
+//!   sqs::listen_batch_ordered(|msg, _ctx| handle_message(msg)).await
+//! }
+//!
+//! async fn handle_message(msg: SqsMessage) -> Result<(), Error> {
+//!   println!("Received {:?}", msg.body);
+//!   Ok(())
+//! }
+//! ```
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+
+use aws_lambda_events::event::sqs::{SqsEvent, SqsMessage};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::model::Context;
+use crate::runtime::{listen_events, RuntimeResult};
+
+/// The partial batch response expected by AWS Lambda.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResponse {
+    pub batch_item_failures: Vec<BatchItemFailure>,
+}
+
+/// Identifies a message that failed to be processed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemFailure {
+    pub item_identifier: String,
+}
+
+/// Accumulates the records that failed to be processed, by message id, across
+/// all the processing stages of a batch. Records failed more than once are
+/// reported only once. Clones share the same failures, thus it can be moved
+/// into each stage.
+#[derive(Debug, Default, Clone)]
+pub struct BatchContext {
+    failures: Arc<Mutex<Vec<String>>>,
+}
+
+impl BatchContext {
+
+    /// Marks the record identified by `message_id` as failed.
+    pub fn fail<S: Into<String>>(&self, message_id: S) {
+        let message_id = message_id.into();
+        let mut failures = self.failures.lock().unwrap();
+        if !failures.contains(&message_id) {
+            failures.push(message_id);
+        }
+    }
+
+    /// Whether the record identified by `message_id` was marked as failed,
+    /// allowing later stages to skip it.
+    pub fn is_failed(&self, message_id: &str) -> bool {
+        self.failures.lock().unwrap().iter().any(|failed| failed == message_id)
+    }
+
+    /// The partial batch response reporting the failed records, in the order
+    /// they were first marked as failed.
+    pub fn to_response(&self) -> BatchResponse {
+        let batch_item_failures = self.failures.lock().unwrap().iter()
+            .map(|message_id| BatchItemFailure { item_identifier: message_id.clone() })
+            .collect();
+        BatchResponse { batch_item_failures }
+    }
+}
+
+/// Listen to SQS events, handing the whole batch to the handler along with a
+/// [BatchContext] to mark records as failed. Only the marked records are reported
+/// back to be retried. Should the handler fail, all records of the batch are
+/// reported as failed. The [BatchContext] is owned by the handler, thus it can be
+/// moved into its future.
+///
+/// ```no_run
+/// use aws_lambda_events::event::sqs::SqsEvent;
+/// use mu_runtime::{sqs, Error};
+///
+/// #[tokio::main]
+/// async fn main() -> mu_runtime::RuntimeResult {
+///   sqs::listen_batch_with_context(|event: SqsEvent, batch: sqs::BatchContext, _ctx| async move {
+///     for record in event.records.iter().filter(|record| record.body.is_none()) {
+///       batch.fail(record.message_id.clone().unwrap_or_default());
+///     }
+///     Ok::<(), Error>(())
+///   }).await
+/// }
+/// ```
+pub async fn listen_batch_with_context<F, Fut, E>(handler: F) -> RuntimeResult
+    where F: Fn(SqsEvent, BatchContext, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<(), E>> + Send,
+          E: StdError + Send
+{
+    listen_events(|event: SqsEvent, ctx| process_with_context(&handler, event, ctx)).await
+}
+
+/// Listen to SQS events, processing all records of a batch concurrently.
+/// Every record is processed regardless of failures on the others, and only
+/// the failed ones are reported back to be retried.
+pub async fn listen_batch_parallel<F, Fut, E>(handler: F) -> RuntimeResult
+    where F: Fn(SqsMessage, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<(), E>> + Send,
+          E: StdError + Send
+{
+    listen_events(|event: SqsEvent, ctx| process_parallel(&handler, event, ctx)).await
+}
+
+/// Listen to SQS events, processing the records of a batch one after another.
+/// The processing stops on the first failure, reporting the failed record
+/// and all its subsequent ones to be retried. This mirrors the semantics
+/// expected when consuming from FIFO queues.
+pub async fn listen_batch_ordered<F, Fut, E>(handler: F) -> RuntimeResult
+    where F: Fn(SqsMessage, Context) -> Fut + Sync + Send,
+          Fut: Future<Output=StdResult<(), E>> + Send,
+          E: StdError + Send
+{
+    listen_events(|event: SqsEvent, ctx| process_ordered(&handler, event, ctx)).await
+}
+
+async fn process_parallel<F, Fut, E>(handler: &F, event: SqsEvent, ctx: Context) -> StdResult<BatchResponse, Error>
+    where F: Fn(SqsMessage, Context) -> Fut,
+          Fut: Future<Output=StdResult<(), E>>,
+          E: StdError
+{
+    let message_ids: Vec<String> = event.records.iter()
+        .map(message_id_of)
+        .collect();
+
+    let executions = event.records.into_iter()
+        .map(|record| (handler)(record, ctx.clone()));
+    let results = join_all(executions).await;
+
+    let failures = message_ids.into_iter().zip(results)
+        .filter_map(|(message_id, result)| match result {
+            Ok(()) => None,
+            Err(cause) => {
+                log::warn!("Failed to process SQS message {}: {}", message_id, cause);
+                Some(BatchItemFailure { item_identifier: message_id })
+            }
+        })
+        .collect();
+
+    Ok(BatchResponse { batch_item_failures: failures })
+}
+
+async fn process_ordered<F, Fut, E>(handler: &F, event: SqsEvent, ctx: Context) -> StdResult<BatchResponse, Error>
+    where F: Fn(SqsMessage, Context) -> Fut,
+          Fut: Future<Output=StdResult<(), E>>,
+          E: StdError
+{
+    let mut failures = Vec::new();
+
+    for record in event.records {
+        let message_id = message_id_of(&record);
+        if failures.is_empty() {
+            if let Err(cause) = (handler)(record, ctx.clone()).await {
+                log::warn!("Failed to process SQS message {}: {}", message_id, cause);
+                failures.push(BatchItemFailure { item_identifier: message_id });
+            }
+        } else {
+            failures.push(BatchItemFailure { item_identifier: message_id });
+        }
+    }
+
+    Ok(BatchResponse { batch_item_failures: failures })
+}
+
+async fn process_with_context<F, Fut, E>(handler: &F, event: SqsEvent, ctx: Context) -> StdResult<BatchResponse, Error>
+    where F: Fn(SqsEvent, BatchContext, Context) -> Fut,
+          Fut: Future<Output=StdResult<(), E>>,
+          E: StdError
+{
+    let message_ids: Vec<String> = event.records.iter()
+        .map(message_id_of)
+        .collect();
+
+    let batch = BatchContext::default();
+    if let Err(cause) = (handler)(event, batch.clone(), ctx).await {
+        log::warn!("Failed to process SQS batch: {}", cause);
+        message_ids.into_iter().for_each(|message_id| batch.fail(message_id));
+    }
+
+    Ok(batch.to_response())
+}
+
+fn message_id_of(record: &SqsMessage) -> String {
+    record.message_id.clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use aws_lambda_events::event::sqs::{SqsEvent, SqsMessage};
+
+    use crate::error::Error;
+    use crate::model::Context;
+    use crate::sqs::{process_ordered, process_parallel, process_with_context, BatchContext, BatchItemFailure};
+
+    fn create_event(number_of_records: usize) -> SqsEvent {
+        let records = (1..=number_of_records)
+            .map(|id| SqsMessage {
+                message_id: Some(id.to_string()),
+                receipt_handle: None,
+                body: Some(format!("message {}", id)),
+                md5_of_body: None,
+                md5_of_message_attributes: None,
+                attributes: HashMap::new(),
+                message_attributes: HashMap::new(),
+                event_source_arn: None,
+                event_source: None,
+                aws_region: None
+            })
+            .collect();
+
+        SqsEvent { records }
+    }
+
+    fn failures_of(ids: &[&str]) -> Vec<BatchItemFailure> {
+        ids.iter()
+            .map(|id| BatchItemFailure { item_identifier: id.to_string() })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn should_report_failed_record_and_its_subsequent_ones_when_ordered() {
+        let processed = Mutex::new(Vec::new());
+        let handler = |msg: SqsMessage, _ctx| {
+            let id = msg.message_id.unwrap();
+            processed.lock().unwrap().push(id.clone());
+            async move {
+                match id.as_str() {
+                    "3" => Err(Error::from("Unable to process")),
+                    _ => Ok(())
+                }
+            }
+        };
+
+        let response = process_ordered(&handler, create_event(5), Context::default()).await.unwrap();
+
+        assert_eq!(failures_of(&["3", "4", "5"]), response.batch_item_failures);
+        assert_eq!(vec!["1", "2", "3"], *processed.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_report_only_failed_records_when_parallel() {
+        let processed = Mutex::new(Vec::new());
+        let handler = |msg: SqsMessage, _ctx| {
+            let id = msg.message_id.unwrap();
+            processed.lock().unwrap().push(id.clone());
+            async move {
+                match id.as_str() {
+                    "2" | "4" => Err(Error::from("Unable to process")),
+                    _ => Ok(())
+                }
+            }
+        };
+
+        let response = process_parallel(&handler, create_event(5), Context::default()).await.unwrap();
+
+        assert_eq!(failures_of(&["2", "4"]), response.batch_item_failures);
+        assert_eq!(5, processed.lock().unwrap().len());
+    }
+
+    fn validate(msg: &SqsMessage, batch: &BatchContext) {
+        if msg.body.as_deref() != Some("message 1") {
+            batch.fail(msg.message_id.clone().unwrap());
+        }
+    }
+
+    fn enrich(msg: &SqsMessage, batch: &BatchContext) {
+        if msg.message_id.as_deref() == Some("2") {
+            batch.fail(msg.message_id.clone().unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_not_duplicate_records_failed_by_multiple_stages() {
+        let handler = |event: SqsEvent, batch: BatchContext, _ctx| async move {
+            event.records.iter().for_each(|msg| validate(msg, &batch));
+            event.records.iter().for_each(|msg| enrich(msg, &batch));
+            Ok::<(), Error>(())
+        };
+
+        let response = process_with_context(&handler, create_event(3), Context::default()).await.unwrap();
+
+        assert_eq!(failures_of(&["2", "3"]), response.batch_item_failures);
+    }
+
+    #[tokio::test]
+    async fn should_report_all_records_when_the_batch_handler_fails() {
+        let handler = |_event: SqsEvent, batch: BatchContext, _ctx| async move {
+            batch.fail("2");
+            Err(Error::from("Unable to process"))
+        };
+
+        let response = process_with_context(&handler, create_event(3), Context::default()).await.unwrap();
+
+        assert_eq!(failures_of(&["2", "1", "3"]), response.batch_item_failures);
+    }
+
+    #[test]
+    fn should_tell_whether_a_record_failed() {
+        let batch = BatchContext::default();
+        batch.fail("1");
+
+        assert!(batch.is_failed("1"));
+        assert!(!batch.is_failed("2"));
+    }
+
+    #[test]
+    fn should_serialize_batch_response_as_expected_by_lambda() {
+        let response = crate::sqs::BatchResponse { batch_item_failures: failures_of(&["1"]) };
+
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(r#"{"batchItemFailures":[{"itemIdentifier":"1"}]}"#, json);
+    }
+}
@@ -0,0 +1,54 @@
+//! Utilities shared by the unit tests of this crate and of the ones built on top of it,
+//! available with the `test-util` feature.
+use std::net::TcpListener;
+use std::sync::{Mutex, Once};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+static INIT: Once = Once::new();
+static LOGGER: CapturingLogger = CapturingLogger { records: Mutex::new(Vec::new()) };
+
+/// A logger that keeps every emitted record in memory, allowing tests
+/// to assert on what has been logged.
+struct CapturingLogger {
+    records: Mutex<Vec<(Level, String)>>,
+}
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut records = self.records.lock().unwrap();
+        records.push((record.level(), format!("{}", record.args())));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the capturing logger. It is safe to call it multiple times.
+pub fn init_logger() {
+    INIT.call_once(|| {
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+    });
+}
+
+/// Returns the messages logged so far with the given level.
+pub fn captured_logs(level: Level) -> Vec<String> {
+    let records = LOGGER.records.lock().unwrap();
+    records.iter()
+        .filter(|(lvl, _)| *lvl == level)
+        .map(|(_, msg)| msg.clone())
+        .collect()
+}
+
+/// Returns the address of a local port nobody is listening on, so connecting to it
+/// fails right away. The port is picked by the OS and released before returning.
+pub fn unreachable_endpoint() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    endpoint
+}
@@ -0,0 +1,203 @@
+//! Utilities to consume events of API Gateway WebSocket APIs, dispatching
+//! them to the [WebSocketHandler] method matching their route key.
+//!
+//! The `$connect` and `$disconnect` routes are handled by their own methods,
+//! while `$default` and custom routes have their JSON body deserialized and
+//! handed to [WebSocketHandler::on_message]. Posting messages back to the
+//! connected clients requires the API Gateway Management API, which is not
+//! covered by this module.
+//!
+//! ```no_run
+//! use async_trait::async_trait;
+//! use mu_runtime::websocket::{self, WebSocketContext, WebSocketHandler};
+//! use mu_runtime::Error;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct ChatMessage { text: String }
+//!
+//! struct Chat;
+//!
+//! #[async_trait]
+//! impl WebSocketHandler for Chat {
+//!     type Message = ChatMessage;
+//!
+//!     async fn on_message(&self, msg: ChatMessage, ctx: WebSocketContext) -> Result<(), Error> {
+//!         println!("{} said {}", ctx.connection_id, msg.text);
+//!         Ok(())
+//!     }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> mu_runtime::RuntimeResult {
+//!   websocket::listen_websocket_events(Chat).await
+//! }
+//! ```
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::model::Context;
+use crate::runtime::{listen_events, RuntimeResult};
+
+/// The route key of connection requests.
+pub const CONNECT_ROUTE: &str = "$connect";
+/// The route key of disconnection notifications.
+pub const DISCONNECT_ROUTE: &str = "$disconnect";
+
+/// Identifies the connection and route of a WebSocket event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketContext {
+    /// The id of the connection, used to post messages back to the client.
+    pub connection_id: String,
+    /// The route the event has been sent to (e.g. `$connect` or `$default`).
+    pub route_key: String,
+    /// The context of the invocation.
+    pub invocation: Context,
+}
+
+/// The response expected by API Gateway. Failures are reported as invocation
+/// errors instead, which API Gateway turns into an internal server error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketResponse {
+    pub status_code: i64,
+}
+
+/// The fields of `ApiGatewayWebsocketProxyRequest` needed for dispatching. Unlike it,
+/// fields like `headers` and `httpMethod` aren't required, as API Gateway only
+/// sends them along with `$connect` events.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebSocketEvent {
+    request_context: WebSocketRequestContext,
+    body: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WebSocketRequestContext {
+    connection_id: Option<String>,
+    route_key: Option<String>,
+}
+
+/// Handles the events of a WebSocket API, according to their route key.
+#[async_trait]
+pub trait WebSocketHandler: Sync {
+    /// The type the body of messages is deserialized into.
+    type Message: for<'de> Deserialize<'de> + Send;
+
+    /// Handles a `$connect` event. Failing rejects the connection.
+    async fn on_connect(&self, _ctx: WebSocketContext) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Handles a `$disconnect` event.
+    async fn on_disconnect(&self, _ctx: WebSocketContext) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Handles a message sent to the `$default` route or to a custom one.
+    async fn on_message(&self, message: Self::Message, ctx: WebSocketContext) -> Result<(), Error>;
+}
+
+/// Listen to API Gateway WebSocket events, dispatching them to the `handler`.
+pub async fn listen_websocket_events<H>(handler: H) -> RuntimeResult
+    where H: WebSocketHandler
+{
+    listen_events(|event, ctx| dispatch(&handler, event, ctx)).await
+}
+
+async fn dispatch<H>(handler: &H, event: WebSocketEvent, invocation: Context) -> Result<WebSocketResponse, Error>
+    where H: WebSocketHandler
+{
+    let request_context = event.request_context;
+    let ctx = WebSocketContext {
+        connection_id: request_context.connection_id.unwrap_or_default(),
+        route_key: request_context.route_key.unwrap_or_default(),
+        invocation,
+    };
+
+    match ctx.route_key.as_str() {
+        CONNECT_ROUTE => handler.on_connect(ctx).await?,
+        DISCONNECT_ROUTE => handler.on_disconnect(ctx).await?,
+        _ => {
+            let body = event.body.ok_or("No payload defined")?;
+            let message = serde_json::from_str(&body)?;
+            handler.on_message(message, ctx).await?
+        }
+    }
+
+    Ok(WebSocketResponse { status_code: 200 })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use serde::Deserialize;
+
+    use crate::error::Error;
+    use crate::model::Context;
+    use crate::websocket::{dispatch, WebSocketContext, WebSocketEvent, WebSocketHandler, WebSocketResponse};
+
+    const CONNECT_EVENT: &str = include_str!("../tests/sample_websocket_connect.json");
+    const MESSAGE_EVENT: &str = include_str!("../tests/sample_websocket_message.json");
+
+    #[derive(Deserialize)]
+    struct ChatMessage {
+        text: String,
+    }
+
+    #[derive(Default)]
+    struct RecordingHandler {
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl WebSocketHandler for RecordingHandler {
+        type Message = ChatMessage;
+
+        async fn on_connect(&self, ctx: WebSocketContext) -> Result<(), Error> {
+            self.calls.lock().unwrap().push(format!("connect {}", ctx.connection_id));
+            Ok(())
+        }
+
+        async fn on_message(&self, msg: ChatMessage, ctx: WebSocketContext) -> Result<(), Error> {
+            self.calls.lock().unwrap().push(format!("{} {}: {}", ctx.route_key, ctx.connection_id, msg.text));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn should_route_connect_events_to_the_connect_handler() {
+        let handler = RecordingHandler::default();
+        let event: WebSocketEvent = serde_json::from_str(CONNECT_EVENT).unwrap();
+
+        let response = dispatch(&handler, event, Context::default()).await;
+        assert_eq!(Ok(WebSocketResponse { status_code: 200 }), response);
+        assert_eq!(vec!["connect TWegAcC4EowCHnA=".to_string()], *handler.calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_route_default_events_with_their_deserialized_body() {
+        let handler = RecordingHandler::default();
+        let event: WebSocketEvent = serde_json::from_str(MESSAGE_EVENT).unwrap();
+
+        let response = dispatch(&handler, event, Context::default()).await;
+        assert_eq!(Ok(WebSocketResponse { status_code: 200 }), response);
+        assert_eq!(vec!["$default TWegAcC4EowCHnA=: Hello, mate".to_string()], *handler.calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn should_fail_on_malformed_messages() {
+        let handler = RecordingHandler::default();
+        let mut event: WebSocketEvent = serde_json::from_str(MESSAGE_EVENT).unwrap();
+        event.body = Some("not json".to_string());
+
+        assert!(dispatch(&handler, event, Context::default()).await.is_err());
+        assert!(handler.calls.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+use hyper::Body;
+
+use mu_runtime::lambda_api::LambdaApiClient;
+use mu_runtime::model::Config;
+
+// Lives on its own test binary, as it replaces the allocator of the whole process.
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Counts the bytes allocated by the current thread, leaving out the ones
+/// allocated by other tests or by the mock server. Reallocations count
+/// as much as they grow, while shrinking ones count as none.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATED: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + layout.size()));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let growth = new_size.saturating_sub(layout.size());
+        let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + growth));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+fn allocated_by<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATED.with(Cell::get);
+    let result = f();
+    (ALLOCATED.with(Cell::get) - before, result)
+}
+
+const PAYLOAD_SIZE: usize = 4 * 1024 * 1024;
+
+#[test]
+fn should_not_allocate_more_serializing_into_bytes_than_into_a_string() {
+    let payload = "x".repeat(PAYLOAD_SIZE);
+
+    let (as_string, _) = allocated_by(|| Body::from(serde_json::to_string(&payload).unwrap()));
+    let (as_bytes, _) = allocated_by(|| Body::from(serde_json::to_vec(&payload).unwrap()));
+
+    println!("Serializing {} bytes: {} bytes allocated through a String, {} through a Vec",
+             PAYLOAD_SIZE, as_string, as_bytes);
+    assert!(as_bytes <= as_string);
+}
+
+#[tokio::test]
+async fn should_not_copy_large_responses_while_publishing_them() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = listener.local_addr().unwrap().to_string();
+    let server = std::thread::spawn(move || {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut received = 0;
+        let mut buffer = vec![0; 64 * 1024];
+        // the head and the quotes of the JSON string come on top of the payload
+        while received < PAYLOAD_SIZE {
+            received += socket.read(&mut buffer).unwrap();
+        }
+        socket.write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\n\r\n").unwrap();
+    });
+
+    let api = LambdaApiClient::create(Config::default().with_endpoint(&endpoint));
+    let payload = "x".repeat(PAYLOAD_SIZE);
+    let (serialization, _) = allocated_by(|| serde_json::to_vec(&payload).unwrap());
+
+    let before = ALLOCATED.with(Cell::get);
+    api.publish_response("0000-0001".to_string(), payload).await.unwrap();
+    let publishing = ALLOCATED.with(Cell::get) - before;
+    server.join().unwrap();
+
+    println!("Publishing {} bytes: {} bytes allocated, {} of them by the serialization",
+             PAYLOAD_SIZE, publishing, serialization);
+    // anything but a copy of the payload, like the request head, fits the margin
+    assert!(publishing < serialization + PAYLOAD_SIZE / 4, "{} bytes allocated", publishing);
+}
@@ -0,0 +1,57 @@
+use std::sync::{Arc, Mutex};
+
+use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+use httpmock::MockServer;
+
+use mu_runtime::lambda_api::LambdaApiClient;
+use mu_runtime::model::Config;
+use mu_runtime::{listen_events_with, Context, Error};
+
+// Lives on its own test binary, as the cold start is tracked for the whole process.
+#[tokio::test]
+async fn should_only_flag_the_first_invocation_as_cold_start() {
+    let mock_server = MockServer::start();
+    let next = mock_server.mock(|when, then| {
+        when.path("/2018-06-01/runtime/invocation/next");
+        then.status(200)
+            .header("lambda-runtime-aws-request-id", "0000-0001")
+            .header("lambda-runtime-deadline-ms", "1000")
+            .header("lambda-runtime-invoked-function-arn", "arn::something")
+            .header("lambda-runtime-trace-id", "0001-0001")
+            .body(include_str!("sample_alb_request.json"));
+    });
+    let success = mock_server.mock(|when, then| {
+        when.path("/2018-06-01/runtime/invocation/0000-0001/response")
+            .method("POST");
+        then.status(200);
+    });
+
+    let completions = Arc::new(Mutex::new(Vec::new()));
+    let completed = completions.clone();
+    let config = Config::default()
+        .with_endpoint(&format!("127.0.0.1:{}", mock_server.port()))
+        .with_max_invocations(2);
+    let lambda_api = LambdaApiClient::create(config).on_complete(move |stats| completed.lock().unwrap().push(stats.cold_start));
+
+    let cold_starts = Mutex::new(Vec::new());
+    let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, ctx: Context| {
+        cold_starts.lock().unwrap().push(ctx.is_cold_start());
+        async { Ok::<i32, Error>(42) }
+    }).await;
+    assert_eq!(Ok(()), result);
+
+    // the flag is kept across clients, e.g. the ones created to replay invocations
+    let lambda_api = LambdaApiClient::create(Config::default()
+        .with_endpoint(&format!("127.0.0.1:{}", mock_server.port()))
+        .with_max_invocations(1));
+    let result = listen_events_with(lambda_api, |_req: AlbTargetGroupRequest, ctx: Context| {
+        cold_starts.lock().unwrap().push(ctx.is_cold_start());
+        async { Ok::<i32, Error>(42) }
+    }).await;
+    assert_eq!(Ok(()), result);
+
+    next.assert_hits(3);
+    success.assert_hits(3);
+    assert_eq!(vec![true, false, false], *cold_starts.lock().unwrap());
+    assert_eq!(vec![true, false], *completions.lock().unwrap());
+}
@@ -0,0 +1,92 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use mu_runtime::model::{Config, DEFAULT_MEMORY_SIZE};
+
+// Lives on its own test binary, as it changes the environment of the whole process.
+// Tests are serialized through this lock, as they would otherwise race on it.
+static ENV: Mutex<()> = Mutex::new(());
+
+fn set_required_vars(memory_size: &str) {
+    std::env::set_var("AWS_LAMBDA_RUNTIME_API", "localhost:9001");
+    std::env::set_var("AWS_LAMBDA_FUNCTION_NAME", "test-function");
+    std::env::set_var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE", memory_size);
+    std::env::set_var("AWS_LAMBDA_FUNCTION_VERSION", "$LATEST");
+    std::env::remove_var("AWS_LAMBDA_LOG_STREAM_NAME");
+    std::env::remove_var("AWS_LAMBDA_LOG_GROUP_NAME");
+}
+
+#[test]
+fn should_read_the_config_when_only_the_required_variables_are_present() {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_required_vars("256");
+
+    let config = Config::try_from_env().unwrap();
+
+    assert_eq!("localhost:9001", config.endpoint);
+    assert_eq!(256, config.memory);
+    assert_eq!(None, config.log_stream);
+    assert_eq!(None, config.log_group);
+}
+
+#[test]
+fn should_report_an_empty_memory_size_as_missing() {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_required_vars("");
+
+    let error = Config::try_from_env().unwrap_err();
+    assert_eq!(
+        "missing env var AWS_LAMBDA_FUNCTION_MEMORY_SIZE: environment variable not found",
+        format!("{}", error)
+    );
+}
+
+#[test]
+fn should_fallback_to_the_default_memory_size_when_allowed() {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_required_vars("");
+    assert_eq!(DEFAULT_MEMORY_SIZE, Config::from_env_or_defaults().unwrap().memory);
+
+    std::env::remove_var("AWS_LAMBDA_FUNCTION_MEMORY_SIZE");
+    assert_eq!(DEFAULT_MEMORY_SIZE, Config::from_env_or_defaults().unwrap().memory);
+}
+
+#[test]
+fn should_prefer_the_informed_memory_size_over_the_default() {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_required_vars("512");
+
+    assert_eq!(512, Config::from_env_or_defaults().unwrap().memory);
+}
+
+#[test]
+fn should_still_require_the_other_variables_when_defaults_are_allowed() {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_required_vars("512");
+    std::env::remove_var("AWS_LAMBDA_RUNTIME_API");
+
+    let error = Config::from_env_or_defaults().unwrap_err();
+    assert_eq!(
+        "missing env var AWS_LAMBDA_RUNTIME_API: environment variable not found",
+        format!("{}", error)
+    );
+}
+
+#[test]
+fn should_override_the_config_read_from_the_environment() {
+    let _env = ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    set_required_vars("256");
+
+    let config = Config::try_from_env().unwrap()
+        .with_endpoint("127.0.0.1:9002")
+        .with_max_invocations(3)
+        .with_duplicate_invocation_window(Duration::from_secs(60))
+        .with_fetch_timeout(None);
+
+    assert_eq!("127.0.0.1:9002", config.endpoint);
+    assert_eq!("test-function", config.function_name);
+    assert_eq!(256, config.memory);
+    assert_eq!(Some(3), config.max_invocations);
+    assert_eq!(Some(Duration::from_secs(60)), config.duplicate_invocation_window);
+    assert_eq!(None, config.fetch_timeout);
+}
@@ -1,4 +1,5 @@
 use aws_lambda_events::event::alb::AlbTargetGroupRequest;
+#[allow(unused_imports)]
 use std::panic::panic_any;
 
 #[test]